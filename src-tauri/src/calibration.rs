@@ -0,0 +1,78 @@
+use crate::models::{AppError, ModelFit, TokenCalibration};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use tauri::Manager;
+
+/// File in the app data directory holding the per-model calibration factors
+const CALIBRATION_FILE: &str = "token_calibration.json";
+
+/// Reports beyond this many are blended in as a moving average, so the factor keeps
+/// following tokenizer changes instead of settling on old usage
+const CALIBRATION_WINDOW: u32 = 20;
+
+/// Reported usage this far from the estimate is more likely a typo or a different prompt
+/// than a tokenizer difference, so ratios are clamped to this range
+const MIN_RATIO: f64 = 0.25;
+const MAX_RATIO: f64 = 4.0;
+
+fn calibration_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Config(format!("Could not determine app data directory: {}", e)))?;
+    fs::create_dir_all(&data_dir)?;
+    Ok(data_dir.join(CALIBRATION_FILE))
+}
+
+/// Calibration for every model usage has been reported for, keyed by model id
+pub fn load_calibrations(app_handle: &tauri::AppHandle) -> HashMap<String, TokenCalibration> {
+    calibration_path(app_handle)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Fold the token count a provider reported for an output into the model's calibration.
+/// `estimated_tokens` is GPTree's uncalibrated estimate for the same output.
+pub fn record_usage(
+    app_handle: &tauri::AppHandle,
+    model: &str,
+    estimated_tokens: usize,
+    actual_tokens: usize,
+) -> Result<TokenCalibration, AppError> {
+    if estimated_tokens == 0 || actual_tokens == 0 {
+        return Err(AppError::Config(
+            "Token counts must be greater than zero".to_string(),
+        ));
+    }
+
+    let mut calibrations = load_calibrations(app_handle);
+    let calibration = calibrations
+        .entry(model.to_string())
+        .or_insert(TokenCalibration {
+            factor: 1.0,
+            samples: 0,
+        });
+    let ratio = (actual_tokens as f64 / estimated_tokens as f64).clamp(MIN_RATIO, MAX_RATIO);
+    calibration.samples += 1;
+    calibration.factor +=
+        (ratio - calibration.factor) / calibration.samples.min(CALIBRATION_WINDOW) as f64;
+    let updated = calibration.clone();
+
+    let content =
+        serde_json::to_string_pretty(&calibrations).map_err(|e| AppError::Json(e.to_string()))?;
+    fs::write(calibration_path(app_handle)?, content)?;
+    Ok(updated)
+}
+
+/// Scale a model fit's token count by the model's calibration factor, if it has one
+pub fn apply(app_handle: &tauri::AppHandle, fit: &mut ModelFit) {
+    let Some(calibration) = load_calibrations(app_handle).remove(&fit.model) else {
+        return;
+    };
+    fit.calibration_factor = calibration.factor;
+    fit.tokens = (fit.uncalibrated_tokens as f64 * calibration.factor).round() as usize;
+    fit.exceeds_context_window = fit.tokens > fit.context_window;
+}
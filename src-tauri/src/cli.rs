@@ -0,0 +1,140 @@
+use crate::models::{CliCommand, Config};
+use std::path::Path;
+
+/// Name of the gptree CLI executable
+const CLI_PROGRAM: &str = "gptree";
+
+/// Build the gptree CLI invocation equivalent to generating with `config` in `root_dir`
+/// Only options that differ from the CLI defaults are emitted to keep the command short.
+/// The CLI has no way to take an explicit file list, so a selection is expressed with
+/// `--previous`, which reads `previousFiles` from the project's `.gptree_config`.
+/// Projects in the `.gptree/` folder layout need build_headless_command instead.
+pub fn build_cli_command(root_dir: &Path, config: &Config, has_selection: bool) -> CliCommand {
+    let defaults = Config::default();
+    let mut args = vec![root_dir.to_string_lossy().to_string()];
+
+    if !config.use_git_ignore {
+        args.push("--ignore-gitignore".to_string());
+    }
+    if config.include_file_types != defaults.include_file_types {
+        args.push("--include-file-types".to_string());
+        args.push(config.include_file_types.clone());
+    }
+    if !config.exclude_file_types.is_empty() {
+        args.push("--exclude-file-types".to_string());
+        args.push(config.exclude_file_types.clone());
+    }
+    if config.output_file != defaults.output_file {
+        args.push("--output-file".to_string());
+        args.push(config.output_file.clone());
+    }
+    if config.output_file_locally {
+        args.push("--output-file-locally".to_string());
+    }
+    if config.copy_to_clipboard {
+        args.push("--copy".to_string());
+    }
+    if !config.safe_mode {
+        args.push("--disable-safe-mode".to_string());
+    }
+    if config.line_numbers {
+        args.push("--line-numbers".to_string());
+    }
+    if config.show_ignored_in_tree {
+        args.push("--show-ignored-in-tree".to_string());
+    }
+    if config.show_default_ignored_in_tree {
+        args.push("--show-default-ignored-in-tree".to_string());
+    }
+    if has_selection {
+        args.push("--previous".to_string());
+    }
+
+    let command = std::iter::once(CLI_PROGRAM.to_string())
+        .chain(args.iter().map(|arg| shell_quote(arg)))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    CliCommand {
+        command,
+        args,
+        uses_previous_selection: has_selection,
+        unsaved_selection: false,
+    }
+}
+
+/// Build the invocation of this app's headless mode (`executable`) that generates from
+/// `root_dir` with its own config, saved selection included when `has_selection`. Unlike the
+/// gptree CLI it finds the config in the `.gptree/` folder layout.
+pub fn build_headless_command(
+    executable: &Path,
+    root_dir: &Path,
+    has_selection: bool,
+) -> CliCommand {
+    let mut args = vec![
+        "--headless".to_string(),
+        "--dir".to_string(),
+        root_dir.to_string_lossy().to_string(),
+    ];
+    if has_selection {
+        args.push("--select-previous".to_string());
+    }
+
+    let command = std::iter::once(executable.to_string_lossy().to_string())
+        .chain(args.iter().cloned())
+        .map(|arg| shell_quote(&arg))
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    CliCommand {
+        command,
+        args,
+        uses_previous_selection: has_selection,
+        unsaved_selection: false,
+    }
+}
+
+/// Split a command template like `code --goto {path}` into arguments.
+/// Double quotes keep arguments with spaces together; placeholders are left for the caller.
+pub fn split_command_template(template: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_arg = false;
+    for c in template.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_arg = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if has_arg {
+                    args.push(std::mem::take(&mut current));
+                    has_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_arg = true;
+            }
+        }
+    }
+    if has_arg {
+        args.push(current);
+    }
+    args
+}
+
+/// Quote a string for use as a single POSIX shell word
+/// Plain words (letters, digits and a few safe symbols) are left as-is for readability
+pub fn shell_quote(value: &str) -> String {
+    let is_plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./,=:@+".contains(c));
+    if is_plain {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
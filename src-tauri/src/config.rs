@@ -1,29 +1,266 @@
-use crate::models::{AppError, Config, CONFIG_VERSION};
+use crate::fs::{project_relative_path, renamed_path, resolve_project_path};
+use crate::models::{
+    AppError, BatchPreset, Config, ConfigFieldSchema, FavoriteProject, SelectionDrift,
+    CONFIG_VERSION,
+};
 use crate::{AppSettings, SessionState};
+use ignore::gitignore::GitignoreBuilder;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use tauri::Manager;
 
 const PROJECT_CONFIG_FILE: &str = ".gptree_config";
+/// Folder holding all of GPTree's files in a project that has moved to the `.gptree/` layout
+const PROJECT_DIR: &str = ".gptree";
+const PROJECT_DIR_CONFIG_FILE: &str = "config";
+const PROJECT_DIR_OUTPUTS: &str = "outputs";
+const PROJECT_DIR_PRESETS: &str = "presets";
+const PROJECT_DIR_SNAPSHOTS: &str = "snapshots";
+const PROJECT_DIR_CACHE: &str = "cache";
 const GLOBAL_CONFIG_FILE: &str = ".gptreerc";
 const SESSION_STATE_FILE: &str = "session_state.json";
+const FAVORITES_FILE: &str = "favorites.json";
+/// External commands the user has allowed the `command` transform to run, per project
+const APPROVED_COMMANDS_FILE: &str = "approved_commands.json";
 
-/// Load or create a configuration file for the project
-pub fn load_or_create_project_config(root_dir: &Path) -> Result<Config, AppError> {
-    let config_path = root_dir.join(PROJECT_CONFIG_FILE);
+/// Check whether the project already has its own config file
+pub fn project_config_exists(root_dir: &Path) -> bool {
+    project_config_path(root_dir).exists()
+}
+
+/// Whether the project keeps its GPTree files in a `.gptree/` folder
+pub fn uses_project_dir(root_dir: &Path) -> bool {
+    root_dir.join(PROJECT_DIR).is_dir()
+}
+
+/// Path of the project config file: `.gptree/config` in the folder layout,
+/// otherwise the flat `.gptree_config` the gptree CLI also reads
+pub fn project_config_path(root_dir: &Path) -> PathBuf {
+    if uses_project_dir(root_dir) {
+        root_dir.join(PROJECT_DIR).join(PROJECT_DIR_CONFIG_FILE)
+    } else {
+        root_dir.join(PROJECT_CONFIG_FILE)
+    }
+}
+
+/// Directory outputs saved inside the project go to
+pub fn project_output_dir(root_dir: &Path) -> PathBuf {
+    if uses_project_dir(root_dir) {
+        root_dir.join(PROJECT_DIR).join(PROJECT_DIR_OUTPUTS)
+    } else {
+        root_dir.to_path_buf()
+    }
+}
+
+/// Where the post-commit hook writes its context snapshot: `.gptree/snapshots/` in the folder
+/// layout, otherwise `output_path` itself
+pub fn project_snapshot_path(root_dir: &Path, output_path: &Path) -> PathBuf {
+    match output_path.file_name() {
+        Some(file_name) if uses_project_dir(root_dir) => root_dir
+            .join(PROJECT_DIR)
+            .join(PROJECT_DIR_SNAPSHOTS)
+            .join(file_name),
+        _ => output_path.to_path_buf(),
+    }
+}
+
+/// Folder for the project's index and other rebuildable data, when it uses the folder layout.
+/// Other projects keep theirs in the app data directory.
+pub fn project_cache_dir(root_dir: &Path) -> Option<PathBuf> {
+    uses_project_dir(root_dir).then(|| root_dir.join(PROJECT_DIR).join(PROJECT_DIR_CACHE))
+}
+
+fn preset_path(root_dir: &Path, name: &str) -> Result<PathBuf, AppError> {
+    let is_valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    if !is_valid {
+        return Err(AppError::Config(format!(
+            "Invalid preset name {:?}: use letters, digits, '-' and '_'",
+            name
+        )));
+    }
+    Ok(root_dir
+        .join(PROJECT_DIR)
+        .join(PROJECT_DIR_PRESETS)
+        .join(format!("{}.json", name)))
+}
+
+/// The selection presets saved in the project's `.gptree/presets/`, sorted by name
+pub fn list_presets(root_dir: &Path) -> Vec<BatchPreset> {
+    let Ok(entries) = fs::read_dir(root_dir.join(PROJECT_DIR).join(PROJECT_DIR_PRESETS)) else {
+        return Vec::new();
+    };
+    let mut presets: Vec<BatchPreset> = entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| {
+            let content = fs::read_to_string(entry.path()).ok()?;
+            serde_json::from_str(&content)
+                .map_err(|e| {
+                    eprintln!(
+                        "[GPTree] Skipping unreadable preset {:?}: {}",
+                        entry.path(),
+                        e
+                    )
+                })
+                .ok()
+        })
+        .collect();
+    presets.sort_by(|a, b| a.name.cmp(&b.name));
+    presets
+}
+
+/// Save a selection preset to `.gptree/presets/<name>.json`, replacing one of the same name.
+/// The project must already use the folder layout.
+pub fn save_preset(root_dir: &Path, preset: &BatchPreset) -> Result<(), AppError> {
+    if !uses_project_dir(root_dir) {
+        return Err(AppError::Config(format!(
+            "Presets are kept in {}/; move the project to that layout first",
+            PROJECT_DIR
+        )));
+    }
+    let path = preset_path(root_dir, &preset.name)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content =
+        serde_json::to_string_pretty(preset).map_err(|e| AppError::Json(e.to_string()))?;
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Delete the preset called `name`, if there is one
+pub fn delete_preset(root_dir: &Path, name: &str) -> Result<(), AppError> {
+    let path = preset_path(root_dir, name)?;
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Move a project's config file and locally saved output into a `.gptree/` folder, so
+/// GPTree's footprint is a single ignorable directory. Returns the new config path.
+pub fn migrate_to_project_dir(root_dir: &Path) -> Result<PathBuf, AppError> {
+    let legacy_config = root_dir.join(PROJECT_CONFIG_FILE);
+    let legacy_output = load_config(&legacy_config)
+        .ok()
+        .filter(|config| config.output_file_locally)
+        .map(|config| root_dir.join(config.output_file))
+        .filter(|path| path.is_file());
+
+    let project_dir = root_dir.join(PROJECT_DIR);
+    for folder in [
+        PROJECT_DIR_OUTPUTS,
+        PROJECT_DIR_PRESETS,
+        PROJECT_DIR_SNAPSHOTS,
+        PROJECT_DIR_CACHE,
+    ] {
+        fs::create_dir_all(project_dir.join(folder))
+            .map_err(|e| AppError::Config(format!("Failed to create {}: {}", PROJECT_DIR, e)))?;
+    }
+
+    let config_path = project_dir.join(PROJECT_DIR_CONFIG_FILE);
+    if legacy_config.is_file() && !config_path.exists() {
+        fs::rename(&legacy_config, &config_path)
+            .map_err(|e| AppError::Config(format!("Failed to move config file: {}", e)))?;
+    }
+    if let Some(output) = legacy_output {
+        if let Some(file_name) = output.file_name() {
+            let target = project_dir.join(PROJECT_DIR_OUTPUTS).join(file_name);
+            if !target.exists() {
+                if let Err(e) = fs::rename(&output, &target) {
+                    eprintln!("[GPTree] Failed to move output file {:?}: {}", output, e);
+                }
+            }
+        }
+    }
+    Ok(config_path)
+}
+
+/// Entries for GPTree's files that the project's .gitignore doesn't cover yet: the config
+/// file, the `.gptree/` folder, and `output_path` if outputs are saved inside the project.
+pub fn missing_gitignore_entries(root_dir: &Path, output_path: &Path) -> Vec<String> {
+    let mut builder = GitignoreBuilder::new(root_dir);
+    // A missing .gitignore just means nothing is covered yet
+    builder.add(root_dir.join(".gitignore"));
+    let matcher = builder.build().ok();
+
+    let mut candidates = vec![
+        (PROJECT_CONFIG_FILE.to_string(), false),
+        (format!("{}/", PROJECT_DIR), true),
+    ];
+    if let Ok(rel_path) = output_path.strip_prefix(root_dir) {
+        if !rel_path.starts_with(PROJECT_DIR) {
+            candidates.push((rel_path.to_string_lossy().replace('\\', "/"), false));
+        }
+    }
+
+    candidates
+        .into_iter()
+        .filter(|(entry, is_dir)| {
+            let path = root_dir.join(entry.trim_end_matches('/'));
+            !matcher
+                .as_ref()
+                .is_some_and(|m| m.matched(&path, *is_dir).is_ignore())
+        })
+        .map(|(entry, _)| entry)
+        .collect()
+}
+
+/// Append entries to the project's .gitignore, creating it if needed
+pub fn append_gitignore_entries(root_dir: &Path, entries: &[String]) -> Result<(), AppError> {
+    let gitignore_path = root_dir.join(".gitignore");
+    let existing = fs::read_to_string(&gitignore_path).unwrap_or_default();
+
+    let mut addition = String::new();
+    if !existing.is_empty() && !existing.ends_with('\n') {
+        addition.push('\n');
+    }
+    addition.push_str("# GPTree\n");
+    for entry in entries {
+        addition.push_str(entry);
+        addition.push('\n');
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&gitignore_path)?;
+    file.write_all(addition.as_bytes())?;
+    Ok(())
+}
+
+/// Path of the global config file in the user's home directory
+pub fn global_config_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(GLOBAL_CONFIG_FILE))
+}
+
+/// Load the project's configuration. A project without a config file gets a copy of `seed`,
+/// which carries the app-wide defaults; the file is only written when the user first saves.
+pub fn load_project_config(root_dir: &Path, seed: &Config) -> Result<Config, AppError> {
+    let config_path = project_config_path(root_dir);
     if config_path.exists() {
         let config = load_config(&config_path)?;
         Ok(migrate_config(config, false))
     } else {
-        let config = Config::default();
-        save_config(&config_path, &config, false)?;
-        Ok(config)
+        Ok(seed.clone())
     }
 }
 
-/// Load or create a global configuration file
-pub fn load_or_create_global_config() -> Result<Config, AppError> {
+/// Load the project's config file without writing to it; migrations only apply in memory
+pub fn load_project_config_read_only(root_dir: &Path) -> Result<Config, AppError> {
+    let config = load_config(&project_config_path(root_dir))?;
+    Ok(migrate_config(config, false))
+}
+
+/// Load or create a global configuration file.
+/// When `read_only` is set the file is never written: migrations only apply in memory, and a
+/// missing file means the defaults.
+pub fn load_or_create_global_config(read_only: bool) -> Result<Config, AppError> {
     let home_dir = dirs::home_dir()
         .ok_or_else(|| AppError::Config("Could not find home directory".to_string()))?;
 
@@ -31,7 +268,7 @@ pub fn load_or_create_global_config() -> Result<Config, AppError> {
     if config_path.exists() {
         let config = load_config(&config_path)?;
         let migrated = migrate_config(config.clone(), true);
-        if migrated != config {
+        if migrated != config && !read_only {
             // Only save if migration changed something
             eprintln!(
                 "[GPTree] Saving migrated global config to {:?}",
@@ -44,13 +281,28 @@ pub fn load_or_create_global_config() -> Result<Config, AppError> {
         let mut config = Config::default();
         // Remove project-specific fields for global config
         config.previous_files = Vec::new();
-        save_config(&config_path, &config, true)?;
+        if !read_only {
+            save_config(&config_path, &config, true)?;
+        }
         Ok(config)
     }
 }
 
+/// Prefix for GUI-only settings. The gptree CLI treats these lines as comments and skips them,
+/// so a config file shared between the CLI and the GUI never trips up the CLI's parser.
+const GUI_KEY_PREFIX: &str = "#gui:";
+
+/// Prefix of environment variables that override config values, followed by the key in
+/// upper snake case, e.g. `GPTREE_SAFE_MODE=false`
+const ENV_OVERRIDE_PREFIX: &str = "GPTREE_";
+
 /// Load a configuration from a file
-fn load_config(config_path: &Path) -> Result<Config, AppError> {
+pub fn load_config(config_path: &Path) -> Result<Config, AppError> {
+    load_config_with_fields(config_path).map(|(config, _)| config)
+}
+
+/// Load a configuration from a file, along with the (snake_case) fields the file sets
+pub fn load_config_with_fields(config_path: &Path) -> Result<(Config, HashSet<String>), AppError> {
     let mut file = File::open(config_path)
         .map_err(|e| AppError::Config(format!("Failed to open config file: {}", e)))?;
 
@@ -59,80 +311,267 @@ fn load_config(config_path: &Path) -> Result<Config, AppError> {
         .map_err(|e| AppError::Config(format!("Failed to read config file: {}", e)))?;
 
     let mut config = Config::default();
+    let mut fields = HashSet::new();
 
     // Parse the config file line by line
     for line in contents.lines() {
-        let line = line.trim();
-        if line.is_empty() || line.starts_with('#') {
-            continue;
+        if let Some((key, value)) = parse_config_line(line) {
+            if apply_config_entry(&mut config, key, value) {
+                fields.insert(field_name(key));
+            }
         }
+    }
 
-        if let Some((key, value)) = line.split_once(':') {
-            let key = key.trim();
-            let value = value.trim();
+    Ok((config, fields))
+}
 
-            match key {
-                "version" => {
-                    if let Ok(version) = value.parse::<u32>() {
-                        config.version = version;
-                    }
-                }
-                "useGitIgnore" => {
-                    config.use_git_ignore = value == "true";
-                }
-                "includeFileTypes" => {
-                    config.include_file_types = value.to_string();
-                }
-                "excludeFileTypes" => {
-                    config.exclude_file_types = value.to_string();
-                }
-                "outputFile" => {
-                    config.output_file = value.to_string();
-                }
-                "saveOutputFile" => {
-                    config.save_output_file = value == "true";
-                }
-                "outputFileLocally" => {
-                    config.output_file_locally = value == "true";
-                }
-                "copyToClipboard" => {
-                    config.copy_to_clipboard = value == "true";
-                }
-                "safeMode" => {
-                    config.safe_mode = value == "true";
-                }
-                "storeFilesChosen" => {
-                    config.store_files_chosen = value == "true";
-                }
-                "lineNumbers" => {
-                    config.line_numbers = value == "true";
-                }
-                "showIgnoredInTree" => {
-                    config.show_ignored_in_tree = value == "true";
-                }
-                "showDefaultIgnoredInTree" => {
-                    config.show_default_ignored_in_tree = value == "true";
-                }
-                "previousFiles" => {
-                    config.previous_files = if value.is_empty() {
-                        Vec::new()
-                    } else {
-                        value.split(',').map(|s| s.trim().to_string()).collect()
-                    };
+/// Apply `GPTREE_*` environment variables on top of `config`, returning the fields they set
+pub fn apply_env_overrides(config: &mut Config) -> HashSet<String> {
+    let mut fields = HashSet::new();
+    for (name, value) in std::env::vars() {
+        let Some(field) = name
+            .strip_prefix(ENV_OVERRIDE_PREFIX)
+            .map(str::to_lowercase)
+        else {
+            continue;
+        };
+        if apply_config_entry(config, &file_key(&field), value.trim()) {
+            fields.insert(field);
+        }
+    }
+    fields
+}
+
+/// The Config field a config file key sets: `maxOutputSizeMb` -> `max_output_size_mb`
+fn field_name(key: &str) -> String {
+    match key {
+        // Older GUI configs stored only a prefix, which is read into the template
+        "fileHeaderPrefix" => "file_header_template".to_string(),
+        _ => {
+            let mut field = String::with_capacity(key.len() + 4);
+            for c in key.chars() {
+                if c.is_ascii_uppercase() {
+                    field.push('_');
                 }
-                "excludeDirs" => {
-                    config.exclude_dirs = if value.is_empty() {
-                        Vec::new()
-                    } else {
-                        value.split(',').map(|s| s.trim().to_string()).collect()
-                    };
+                field.push(c.to_ascii_lowercase());
+            }
+            field
+        }
+    }
+}
+
+/// The config file key for a Config field: `use_git_ignore` -> `useGitIgnore`
+fn file_key(field: &str) -> String {
+    let mut parts = field.split('_');
+    let mut key = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            key.push(first.to_ascii_uppercase());
+            key.extend(chars);
+        }
+    }
+    key
+}
+
+/// Split a config line into its key and value
+/// GUI extension keys are returned without their prefix; comments and blank lines yield None
+fn parse_config_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    let line = match line.strip_prefix(GUI_KEY_PREFIX) {
+        Some(rest) => rest,
+        None if line.is_empty() || line.starts_with('#') => return None,
+        None => line,
+    };
+    line.split_once(':')
+        .map(|(key, value)| (key.trim(), value.trim()))
+}
+
+/// Apply a single config entry
+/// Returns false if the key isn't known to this version of the GUI
+fn apply_config_entry(config: &mut Config, key: &str, value: &str) -> bool {
+    match key {
+        "version" => {
+            if let Ok(version) = value.parse::<u32>() {
+                config.version = version;
+            }
+        }
+        "useGitIgnore" => {
+            config.use_git_ignore = value == "true";
+        }
+        "includeFileTypes" => {
+            config.include_file_types = value.to_string();
+        }
+        "excludeFileTypes" => {
+            config.exclude_file_types = value.to_string();
+        }
+        "outputFile" => {
+            config.output_file = value.to_string();
+        }
+        "saveOutputFile" => {
+            config.save_output_file = value == "true";
+        }
+        "outputFileLocally" => {
+            config.output_file_locally = value == "true";
+        }
+        "copyToClipboard" => {
+            config.copy_to_clipboard = value == "true";
+        }
+        "safeMode" => {
+            config.safe_mode = value == "true";
+        }
+        "storeFilesChosen" => {
+            config.store_files_chosen = value == "true";
+        }
+        "lineNumbers" => {
+            config.line_numbers = value == "true";
+        }
+        "showIgnoredInTree" => {
+            config.show_ignored_in_tree = value == "true";
+        }
+        "showDefaultIgnoredInTree" => {
+            config.show_default_ignored_in_tree = value == "true";
+        }
+        "treeHeader" => {
+            config.tree_header = value.to_string();
+        }
+        "contentsHeader" => {
+            config.contents_header = value.to_string();
+        }
+        "fileHeaderTemplate" => {
+            config.file_header_template = value.to_string();
+        }
+        "tokenEncoding" => {
+            config.token_encoding = value.to_string();
+        }
+        "outputFormat" => {
+            config.output_format = value.to_string();
+        }
+        "markdownFrontMatter" => {
+            config.markdown_front_matter = value == "true";
+        }
+        "targetModel" => {
+            config.target_model = value.to_string();
+        }
+        "fileHeaderPrefix" => {
+            // Older GUI configs stored only a prefix; fold it into the template
+            config.file_header_template = format!("{} {{path}}", value);
+        }
+        "headerIncludeSize" => {
+            config.header_include_size = value == "true";
+        }
+        "headerIncludeModified" => {
+            config.header_include_modified = value == "true";
+        }
+        "headerIncludeGitCommit" => {
+            config.header_include_git_commit = value == "true";
+        }
+        "includeFileHash" => {
+            config.include_file_hash = value == "true";
+        }
+        "writeManifest" => {
+            config.write_manifest = value == "true";
+        }
+        "respectLinguistAttributes" => {
+            config.respect_linguist_attributes = value == "true";
+        }
+        "respectEditorconfig" => {
+            config.respect_editorconfig = value == "true";
+        }
+        "neverOverwriteOutput" => {
+            config.never_overwrite_output = value == "true";
+        }
+        "includeDependencySummary" => {
+            config.include_dependency_summary = value == "true";
+        }
+        "transforms" => {
+            config.transforms = value.to_string();
+        }
+        "externalCommand" => {
+            config.external_command = value.to_string();
+        }
+        "externalCommandTimeoutSecs" => {
+            if let Ok(secs) = value.parse() {
+                config.external_command_timeout_secs = secs;
+            }
+        }
+        "externalCommandMaxKb" => {
+            if let Ok(limit) = value.parse() {
+                config.external_command_max_kb = limit;
+            }
+        }
+        "replacementRules" => {
+            if value.is_empty() {
+                config.replacement_rules = Vec::new();
+            } else {
+                match serde_json::from_str(value) {
+                    Ok(rules) => config.replacement_rules = rules,
+                    Err(e) => eprintln!("[GPTree] Ignoring invalid replacementRules: {}", e),
                 }
-                _ => {}
             }
         }
+        "followSymlinks" => {
+            config.follow_symlinks = value == "true";
+        }
+        "stubExcludedDirs" => {
+            config.stub_excluded_dirs = value == "true";
+        }
+        "includeLocSummary" => {
+            config.include_loc_summary = value == "true";
+        }
+        "includeSymbolIndex" => {
+            config.include_symbol_index = value == "true";
+        }
+        "maxOutputSizeMb" => {
+            if let Ok(limit) = value.parse() {
+                config.max_output_size_mb = limit;
+            }
+        }
+        "maxFileReadMb" => {
+            if let Ok(limit) = value.parse() {
+                config.max_file_read_mb = limit;
+            }
+        }
+        "defaultIgnores" => {
+            config.default_ignores = value
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect();
+        }
+        "pruneMissingPreviousFiles" => {
+            config.prune_missing_previous_files = value == "true";
+        }
+        "previousFiles" => {
+            config.previous_files = if value.is_empty() {
+                Vec::new()
+            } else {
+                value.split(',').map(|s| s.trim().to_string()).collect()
+            };
+        }
+        "excludeDirs" => {
+            config.exclude_dirs = if value.is_empty() {
+                Vec::new()
+            } else {
+                value.split(',').map(|s| s.trim().to_string()).collect()
+            };
+        }
+        _ => return false,
     }
+    true
+}
 
-    Ok(config)
+/// Collect the entries this version doesn't recognise (options only the CLI or a newer GUI
+/// knows about) so they can be written back untouched when the file is saved
+fn unrecognized_config_lines(contents: &str) -> Vec<String> {
+    let mut scratch = Config::default();
+    contents
+        .lines()
+        .filter(|line| {
+            matches!(parse_config_line(line), Some((key, value)) if !apply_config_entry(&mut scratch, key, value))
+        })
+        .map(|line| line.trim().to_string())
+        .collect()
 }
 
 /// Save a configuration to a file
@@ -157,6 +596,11 @@ pub fn save_config(config_path: &Path, config: &Config, is_global: bool) -> Resu
         }
     }
 
+    // Keep entries we don't understand so other gptree versions don't lose their settings
+    let preserved_lines = fs::read_to_string(config_path)
+        .map(|contents| unrecognized_config_lines(&contents))
+        .unwrap_or_default();
+
     // Attempt to create and write to the file
     let file_result = File::create(config_path);
 
@@ -164,84 +608,8 @@ pub fn save_config(config_path: &Path, config: &Config, is_global: bool) -> Resu
         Ok(mut file) => {
             println!("[GPTree] Successfully opened file for writing");
 
-            // Write the config data
-            let write_result = writeln!(
-                file,
-                "# GPTree {} Config",
-                if is_global { "Global" } else { "Local" }
-            )
-            .and_then(|_| writeln!(file, "version: {}", config.version))
-            .and_then(|_| writeln!(file, "# Whether to use .gitignore"))
-            .and_then(|_| writeln!(file, "useGitIgnore: {}", config.use_git_ignore))
-            .and_then(|_| writeln!(file, "# File types to include (e.g., .py,.js)"))
-            .and_then(|_| writeln!(file, "includeFileTypes: {}", config.include_file_types))
-            .and_then(|_| writeln!(file, "# File types to exclude when includeFileTypes is '*'"))
-            .and_then(|_| writeln!(file, "excludeFileTypes: {}", config.exclude_file_types))
-            .and_then(|_| writeln!(file, "# Output file name"))
-            .and_then(|_| writeln!(file, "outputFile: {}", config.output_file))
-            .and_then(|_| writeln!(file, "# Whether to save the output file at all"))
-            .and_then(|_| writeln!(file, "saveOutputFile: {}", config.save_output_file))
-            .and_then(|_| writeln!(
-                file,
-                "# Whether to output the file locally or relative to the project directory"
-            ))
-            .and_then(|_| writeln!(file, "outputFileLocally: {}", config.output_file_locally))
-            .and_then(|_| writeln!(file, "# Whether to copy the output to the clipboard"))
-            .and_then(|_| writeln!(file, "copyToClipboard: {}", config.copy_to_clipboard))
-            .and_then(|_| writeln!(
-                file,
-                "# Whether to use safe mode (prevent overly large files from being combined)"
-            ))
-            .and_then(|_| writeln!(file, "safeMode: {}", config.safe_mode))
-            .and_then(|_| writeln!(
-                file,
-                "# Whether to store the files chosen in the config file (--save, -s)"
-            ))
-            .and_then(|_| writeln!(file, "storeFilesChosen: {}", config.store_files_chosen))
-            .and_then(|_| writeln!(
-                file,
-                "# Whether to include line numbers in the output (--line-numbers, -n)"
-            ))
-            .and_then(|_| writeln!(file, "lineNumbers: {}", config.line_numbers))
-            .and_then(|_| writeln!(
-                file,
-                "# Whether to show ignored files in the directory tree"
-            ))
-            .and_then(|_| writeln!(file, "showIgnoredInTree: {}", config.show_ignored_in_tree))
-            .and_then(|_| writeln!(file, "# Whether to show only default ignored files in the directory tree while still respecting gitignore"))
-            .and_then(|_| writeln!(
-                file,
-                "showDefaultIgnoredInTree: {}",
-                config.show_default_ignored_in_tree
-            ));
-
-            // Add previous files only for local config
-            if !is_global {
-                let previous_files_result = writeln!(
-                    file,
-                    "# Previously selected files (when using the -s or --save flag previously)"
-                )
-                .and_then(|_| writeln!(file, "previousFiles: {}", config.previous_files.join(",")));
-
-                if let Err(e) = previous_files_result {
-                    let err_msg = format!("Failed to write previous files to config file: {}", e);
-                    println!("[GPTree] Error: {}", err_msg);
-                    return Err(AppError::Config(err_msg));
-                }
-
-                // Add exclude_dirs for local config
-                let exclude_dirs_result = writeln!(
-                    file,
-                    "# Excluded directories (when using the -s or --save flag previously or edited in UI)"
-                )
-                .and_then(|_| writeln!(file, "excludeDirs: {}", config.exclude_dirs.join(",")));
-
-                if let Err(e) = exclude_dirs_result {
-                    let err_msg = format!("Failed to write exclude_dirs to config file: {}", e);
-                    println!("[GPTree] Error: {}", err_msg);
-                    return Err(AppError::Config(err_msg));
-                }
-            }
+            let write_result = write_config_entries(&mut file, config, is_global)
+                .and_then(|_| write_preserved_lines(&mut file, &preserved_lines));
 
             match write_result {
                 Ok(_) => {
@@ -268,21 +636,373 @@ pub fn save_config(config_path: &Path, config: &Config, is_global: bool) -> Resu
     }
 }
 
+/// Write every setting, each preceded by a comment describing it. Previously selected files
+/// and excluded directories only go in local configs.
+fn write_config_entries(
+    file: &mut impl Write,
+    config: &Config,
+    is_global: bool,
+) -> std::io::Result<()> {
+    writeln!(
+        file,
+        "# GPTree {} Config",
+        if is_global { "Global" } else { "Local" }
+    )
+    .and_then(|_| writeln!(file, "version: {}", config.version))
+    .and_then(|_| writeln!(file, "# Whether to use .gitignore"))
+    .and_then(|_| writeln!(file, "useGitIgnore: {}", config.use_git_ignore))
+    .and_then(|_| writeln!(file, "# File types to include (e.g., .py,.js)"))
+    .and_then(|_| writeln!(file, "includeFileTypes: {}", config.include_file_types))
+    .and_then(|_| writeln!(file, "# File types to exclude when includeFileTypes is '*'"))
+    .and_then(|_| writeln!(file, "excludeFileTypes: {}", config.exclude_file_types))
+    .and_then(|_| writeln!(file, "# Output file name"))
+    .and_then(|_| writeln!(file, "outputFile: {}", config.output_file))
+    .and_then(|_| writeln!(file, "# Whether to save the output file at all"))
+    .and_then(|_| writeln!(file, "saveOutputFile: {}", config.save_output_file))
+    .and_then(|_| writeln!(
+        file,
+        "# Whether to output the file locally or relative to the project directory"
+    ))
+    .and_then(|_| writeln!(file, "outputFileLocally: {}", config.output_file_locally))
+    .and_then(|_| writeln!(file, "# Whether to copy the output to the clipboard"))
+    .and_then(|_| writeln!(file, "copyToClipboard: {}", config.copy_to_clipboard))
+    .and_then(|_| writeln!(
+        file,
+        "# Whether to use safe mode (prevent overly large files from being combined)"
+    ))
+    .and_then(|_| writeln!(file, "safeMode: {}", config.safe_mode))
+    .and_then(|_| writeln!(
+        file,
+        "# Whether to store the files chosen in the config file (--save, -s)"
+    ))
+    .and_then(|_| writeln!(file, "storeFilesChosen: {}", config.store_files_chosen))
+    .and_then(|_| writeln!(
+        file,
+        "# Whether to include line numbers in the output (--line-numbers, -n)"
+    ))
+    .and_then(|_| writeln!(file, "lineNumbers: {}", config.line_numbers))
+    .and_then(|_| writeln!(
+        file,
+        "# Whether to show ignored files in the directory tree"
+    ))
+    .and_then(|_| writeln!(file, "showIgnoredInTree: {}", config.show_ignored_in_tree))
+    .and_then(|_| writeln!(file, "# Whether to show only default ignored files in the directory tree while still respecting gitignore"))
+    .and_then(|_| writeln!(
+        file,
+        "showDefaultIgnoredInTree: {}",
+        config.show_default_ignored_in_tree
+    ))?;
+
+    if !is_global {
+        writeln!(
+            file,
+            "# Previously selected files (when using the -s or --save flag previously)"
+        )?;
+        writeln!(file, "previousFiles: {}", config.previous_files.join(","))?;
+        writeln!(
+            file,
+            "# Excluded directories (when using the -s or --save flag previously or edited in UI)"
+        )?;
+        writeln!(file, "excludeDirs: {}", config.exclude_dirs.join(","))?;
+    }
+
+    // GUI-only settings go last
+    write_gui_settings(file, config)
+}
+
+/// Describe every config field from what save_config writes for each mode, so the
+/// descriptions and defaults can't drift from the file format
+pub fn config_schema() -> Vec<ConfigFieldSchema> {
+    let defaults = Config::default();
+    let default_fields = match serde_json::to_value(&defaults) {
+        Ok(serde_json::Value::Object(fields)) => fields,
+        _ => serde_json::Map::new(),
+    };
+
+    let mut schema: Vec<ConfigFieldSchema> = Vec::new();
+    for (mode, is_global) in [("global", true), ("local", false)] {
+        let mut rendered = Vec::new();
+        if write_config_entries(&mut rendered, &defaults, is_global).is_err() {
+            continue;
+        }
+
+        // Each key is preceded by the comment describing it
+        let mut description: Option<String> = None;
+        for line in String::from_utf8_lossy(&rendered).lines() {
+            let (entry, gui_only) = match line.strip_prefix(GUI_KEY_PREFIX) {
+                Some(entry) => (entry, true),
+                None => (line, false),
+            };
+            if !gui_only {
+                if let Some(comment) = line.strip_prefix('#') {
+                    description = Some(comment.trim().to_string());
+                    continue;
+                }
+            }
+            let Some((key, _)) = entry.split_once(':') else {
+                continue;
+            };
+            let key = key.trim();
+            let key_description = description.take();
+            if key == "version" {
+                continue;
+            }
+
+            if let Some(existing) = schema.iter_mut().find(|field| field.key == key) {
+                existing.modes.push(mode.to_string());
+                continue;
+            }
+            let field = field_name(key);
+            // Empty lists are left out when serializing
+            let default = default_fields
+                .get(&field)
+                .cloned()
+                .unwrap_or_else(|| serde_json::Value::Array(Vec::new()));
+            let value_type = match &default {
+                serde_json::Value::Bool(_) => "boolean",
+                serde_json::Value::Number(_) => "number",
+                serde_json::Value::String(_) => "string",
+                serde_json::Value::Array(_) => "array",
+                _ => "object",
+            };
+            schema.push(ConfigFieldSchema {
+                field,
+                key: key.to_string(),
+                value_type: value_type.to_string(),
+                default,
+                description: key_description,
+                modes: vec![mode.to_string()],
+                gui_only,
+            });
+        }
+    }
+    schema
+}
+
+/// Write the GUI-only settings as extension keys the CLI skips over
+fn write_gui_settings(file: &mut impl Write, config: &Config) -> std::io::Result<()> {
+    writeln!(
+        file,
+        "# GPTree GUI settings (the {} prefix keeps the gptree CLI from reading them)",
+        GUI_KEY_PREFIX
+    )?;
+    write_gui_key(
+        file,
+        "Heading placed above the directory tree in the output",
+        "treeHeader",
+        &config.tree_header,
+    )?;
+    write_gui_key(
+        file,
+        "Marker placed before the combined file contents",
+        "contentsHeader",
+        &config.contents_header,
+    )?;
+    write_gui_key(
+        file,
+        "Header placed above each file ({path}, {lines} and {tokens} are replaced)",
+        "fileHeaderTemplate",
+        &config.file_header_template,
+    )?;
+    write_gui_key(
+        file,
+        "Tokenizer for token counts: o200k_base, cl100k_base or approximate",
+        "tokenEncoding",
+        &config.token_encoding,
+    )?;
+    write_gui_key(
+        file,
+        "Model to size the output for: gpt-4o, claude, gemini or llama (empty for none)",
+        "targetModel",
+        &config.target_model,
+    )?;
+    write_gui_key(
+        file,
+        "Output format: text, markdown (fenced code blocks), xml (<document> tags per file) or json",
+        "outputFormat",
+        &config.output_format,
+    )?;
+    write_gui_key(
+        file,
+        "Whether markdown outputs start with YAML front matter (project, date, git ref, totals)",
+        "markdownFrontMatter",
+        config.markdown_front_matter,
+    )?;
+    write_gui_key(
+        file,
+        "Whether to add each file's size to its header",
+        "headerIncludeSize",
+        config.header_include_size,
+    )?;
+    write_gui_key(
+        file,
+        "Whether to add each file's last-modified time to its header",
+        "headerIncludeModified",
+        config.header_include_modified,
+    )?;
+    write_gui_key(
+        file,
+        "Whether to add the last git commit touching each file to its header",
+        "headerIncludeGitCommit",
+        config.header_include_git_commit,
+    )?;
+    write_gui_key(
+        file,
+        "Whether to compute a SHA-256 of each file and add it to its header",
+        "includeFileHash",
+        config.include_file_hash,
+    )?;
+    write_gui_key(
+        file,
+        "Whether to write a .manifest.json next to the saved output file",
+        "writeManifest",
+        config.write_manifest,
+    )?;
+    write_gui_key(
+        file,
+        "Whether to hide linguist-generated and linguist-vendored paths from .gitattributes",
+        "respectLinguistAttributes",
+        config.respect_linguist_attributes,
+    )?;
+    write_gui_key(
+        file,
+        "Whether to decode files using the charset and line endings from .editorconfig",
+        "respectEditorconfig",
+        config.respect_editorconfig,
+    )?;
+    write_gui_key(
+        file,
+        "Whether to save to a new numbered file instead of overwriting an existing output",
+        "neverOverwriteOutput",
+        config.never_overwrite_output,
+    )?;
+    write_gui_key(
+        file,
+        "Whether to append a line count summary table to the output",
+        "includeLocSummary",
+        config.include_loc_summary,
+    )?;
+    write_gui_key(
+        file,
+        "Whether to list the symbols defined in each included file before the contents",
+        "includeSymbolIndex",
+        config.include_symbol_index,
+    )?;
+    write_gui_key(
+        file,
+        "Whether to append a summary of direct dependencies in place of lockfiles",
+        "includeDependencySummary",
+        config.include_dependency_summary,
+    )?;
+    write_gui_key(
+        file,
+        "Whether to show excluded directories in the tree with their file count and size",
+        "stubExcludedDirs",
+        config.stub_excluded_dirs,
+    )?;
+    write_gui_key(
+        file,
+        "Whether to descend into symlinked directories (otherwise shown as name -> target)",
+        "followSymlinks",
+        config.follow_symlinks,
+    )?;
+    write_gui_key(
+        file,
+        "Steps run over each file in order: line_numbers, truncate:N, strip_comments, redact, replace, command",
+        "transforms",
+        &config.transforms,
+    )?;
+    write_gui_key(
+        file,
+        "Program the command step pipes each file through (stdin to stdout); {path} is the file",
+        "externalCommand",
+        &config.external_command,
+    )?;
+    write_gui_key(
+        file,
+        "Seconds the external command may run per file",
+        "externalCommandTimeoutSecs",
+        config.external_command_timeout_secs,
+    )?;
+    write_gui_key(
+        file,
+        "Largest file in KB passed to the external command, and largest output accepted",
+        "externalCommandMaxKb",
+        config.external_command_max_kb,
+    )?;
+    let replacement_rules = if config.replacement_rules.is_empty() {
+        String::new()
+    } else {
+        serde_json::to_string(&config.replacement_rules).unwrap_or_default()
+    };
+    write_gui_key(
+        file,
+        "Regex find/replace rules for the replace step, as a JSON list of {pattern, replacement}",
+        "replacementRules",
+        replacement_rules,
+    )?;
+    write_gui_key(
+        file,
+        "Stop generating once the output passes this many MB (0 = no limit)",
+        "maxOutputSizeMb",
+        config.max_output_size_mb,
+    )?;
+    write_gui_key(
+        file,
+        "Read at most this many MB of any one file, marking it truncated (0 = no limit)",
+        "maxFileReadMb",
+        config.max_file_read_mb,
+    )?;
+    write_gui_key(
+        file,
+        "Drop previously selected files that no longer exist when saving",
+        "pruneMissingPreviousFiles",
+        config.prune_missing_previous_files,
+    )?;
+    write_gui_key(
+        file,
+        "File and folder names hidden from the tree wherever they appear (comma-separated)",
+        "defaultIgnores",
+        config.default_ignores.join(","),
+    )
+}
+
+/// Write a single GUI-only setting preceded by its description
+fn write_gui_key(
+    file: &mut impl Write,
+    description: &str,
+    key: &str,
+    value: impl std::fmt::Display,
+) -> std::io::Result<()> {
+    writeln!(file, "# {}", description)?;
+    writeln!(file, "{}{}: {}", GUI_KEY_PREFIX, key, value)
+}
+
+/// Write back entries from the previous file that this version didn't recognise
+fn write_preserved_lines(file: &mut File, lines: &[String]) -> std::io::Result<()> {
+    if lines.is_empty() {
+        return Ok(());
+    }
+    writeln!(file, "# Settings from other gptree versions (kept as-is)")?;
+    for line in lines {
+        writeln!(file, "{}", line)?;
+    }
+    Ok(())
+}
+
 /// Update the previous files list in the configuration
 pub fn update_previous_files(
     config_path: &Path,
     selected_files: &[String],
     root_dir: &Path,
 ) -> Result<(), AppError> {
-    // Convert absolute paths to relative paths
+    // Convert absolute paths to relative paths; files outside the root go under external/
     let relative_paths: Vec<String> = selected_files
         .iter()
-        .filter_map(|path| {
-            let path_buf = PathBuf::from(path);
-            path_buf
-                .strip_prefix(root_dir)
-                .ok()
-                .map(|rel_path| rel_path.to_string_lossy().to_string())
+        .map(|path| {
+            project_relative_path(root_dir, Path::new(path))
+                .to_string_lossy()
+                .to_string()
         })
         .collect();
 
@@ -296,6 +1016,117 @@ pub fn update_previous_files(
     save_config(config_path, &config, false)
 }
 
+/// The `previous_files` entries that no longer exist under `root_dir`
+pub fn missing_previous_files(config: &Config, root_dir: &Path) -> Vec<String> {
+    config
+        .previous_files
+        .iter()
+        .filter(|relative| !resolve_project_path(root_dir, relative).is_file())
+        .cloned()
+        .collect()
+}
+
+/// Point the project's `previous_files` at the new paths of files renamed or moved within
+/// `root_dir`, so a refactor doesn't drop them from the saved selection.
+/// Returns whether any entry changed.
+pub fn remap_previous_files(
+    root_dir: &Path,
+    renames: &[(PathBuf, PathBuf)],
+) -> Result<bool, AppError> {
+    let config_path = project_config_path(root_dir);
+    if !config_path.exists() {
+        return Ok(false);
+    }
+
+    let mut config = load_config(&config_path)?;
+    let mut changed = false;
+    for entry in config.previous_files.iter_mut() {
+        if let Some(renamed) = renamed_path(&resolve_project_path(root_dir, entry), renames) {
+            *entry = project_relative_path(root_dir, &renamed)
+                .to_string_lossy()
+                .to_string();
+            changed = true;
+        }
+    }
+    if changed {
+        save_config(&config_path, &config, false)?;
+    }
+    Ok(changed)
+}
+
+/// Where each field of the active config was set, latest layer first: "session" for values the
+/// UI overrides for this session only, "env" for `GPTREE_*` variables, then `file_source`
+/// ("local" or "global") for fields the active config file sets, and "default" otherwise
+pub fn config_sources(
+    file_fields: &HashSet<String>,
+    file_source: &str,
+    env_fields: &HashSet<String>,
+    session_fields: &HashSet<String>,
+) -> HashMap<String, String> {
+    let fields = match serde_json::to_value(Config::default()) {
+        Ok(serde_json::Value::Object(fields)) => fields,
+        _ => serde_json::Map::new(),
+    };
+    fields
+        .keys()
+        .map(|field| {
+            let source = if session_fields.contains(field) {
+                "session"
+            } else if env_fields.contains(field) {
+                "env"
+            } else if file_fields.contains(field) {
+                file_source
+            } else {
+                "default"
+            };
+            (field.clone(), source.to_string())
+        })
+        .collect()
+}
+
+/// Compare the current selection with the files saved in `previous_files`
+pub fn selection_drift(
+    config: &Config,
+    selected_files: &[String],
+    root_dir: &Path,
+) -> SelectionDrift {
+    // previous_files is stored relative to the root, the same way update_previous_files writes it
+    let current: Vec<String> = selected_files
+        .iter()
+        .map(|path| {
+            project_relative_path(root_dir, Path::new(path))
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect();
+    let current_set: HashSet<&String> = current.iter().collect();
+    let previous_set: HashSet<&String> = config.previous_files.iter().collect();
+
+    let mut drift = SelectionDrift {
+        added: current
+            .iter()
+            .filter(|path| !previous_set.contains(path))
+            .cloned()
+            .collect(),
+        ..Default::default()
+    };
+    for path in config
+        .previous_files
+        .iter()
+        .filter(|path| !current_set.contains(path))
+    {
+        if resolve_project_path(root_dir, path).is_file() {
+            drift.removed.push(path.clone());
+        } else {
+            drift.missing.push(path.clone());
+        }
+    }
+    drift.added.sort();
+    drift.removed.sort();
+    drift.missing.sort();
+    drift
+}
+
 /// Migrate a config to the current version
 fn migrate_config(mut config: Config, is_global: bool) -> Config {
     if config.version < CONFIG_VERSION {
@@ -358,6 +1189,129 @@ pub fn save_session_state(
     fs::write(&path, content).map_err(AppError::Io) // Use shorthand
 }
 
+/// Get the path to the favorites file in the app config directory
+fn get_favorites_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let config_dir = app_handle.path().app_config_dir().map_err(|e| {
+        AppError::Config(format!("Could not determine app config directory: {}", e))
+    })?;
+    fs::create_dir_all(&config_dir)
+        .map_err(|e| AppError::Config(format!("Could not create config directory: {}", e)))?;
+    Ok(config_dir.join(FAVORITES_FILE))
+}
+
+fn save_favorites(
+    app_handle: &tauri::AppHandle,
+    favorites: &[FavoriteProject],
+) -> Result<(), AppError> {
+    let path = get_favorites_path(app_handle)?;
+    let content =
+        serde_json::to_string_pretty(favorites).map_err(|e| AppError::Json(e.to_string()))?;
+    fs::write(&path, content).map_err(AppError::Io)
+}
+
+/// List the favorite projects in the order they were added
+pub fn list_favorites(app_handle: &tauri::AppHandle) -> Result<Vec<FavoriteProject>, AppError> {
+    let path = get_favorites_path(app_handle)?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str(&content).map_err(|e| AppError::Json(e.to_string()))
+}
+
+/// Pin `path` as a favorite, or rename it if it already is one
+pub fn add_favorite(
+    app_handle: &tauri::AppHandle,
+    path: &Path,
+    name: Option<String>,
+) -> Result<Vec<FavoriteProject>, AppError> {
+    if !path.is_dir() {
+        return Err(AppError::PathNotFound(path.to_string_lossy().to_string()));
+    }
+    let path_str = path.to_string_lossy().to_string();
+    let name = name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path_str.clone())
+        });
+
+    let mut favorites = list_favorites(app_handle)?;
+    match favorites
+        .iter_mut()
+        .find(|favorite| favorite.path == path_str)
+    {
+        Some(existing) => existing.name = name,
+        None => favorites.push(FavoriteProject {
+            path: path_str,
+            name,
+            added_at: chrono::Local::now().to_rfc3339(),
+        }),
+    }
+    save_favorites(app_handle, &favorites)?;
+    Ok(favorites)
+}
+
+/// Unpin `path`, returning the favorites that are left
+pub fn remove_favorite(
+    app_handle: &tauri::AppHandle,
+    path: &str,
+) -> Result<Vec<FavoriteProject>, AppError> {
+    let mut favorites = list_favorites(app_handle)?;
+    favorites.retain(|favorite| favorite.path != path);
+    save_favorites(app_handle, &favorites)?;
+    Ok(favorites)
+}
+
+/// Project path to the external commands approved for it
+type ApprovedCommands = BTreeMap<String, Vec<String>>;
+
+fn get_approved_commands_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let config_dir = app_handle.path().app_config_dir().map_err(|e| {
+        AppError::Config(format!("Could not determine app config directory: {}", e))
+    })?;
+    fs::create_dir_all(&config_dir)
+        .map_err(|e| AppError::Config(format!("Could not create config directory: {}", e)))?;
+    Ok(config_dir.join(APPROVED_COMMANDS_FILE))
+}
+
+fn load_approved_commands(app_handle: &tauri::AppHandle) -> ApprovedCommands {
+    get_approved_commands_path(app_handle)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// The external commands the user has approved for the project at `root_dir`. Approval is
+/// kept outside the project, so a repository can't approve its own command.
+pub fn approved_commands(app_handle: &tauri::AppHandle, root_dir: &Path) -> Vec<String> {
+    load_approved_commands(app_handle)
+        .remove(&*root_dir.to_string_lossy())
+        .unwrap_or_default()
+}
+
+/// Allow the `command` transform to run `command` in the project at `root_dir`.
+/// Changing the command in the config needs a fresh approval.
+pub fn approve_command(
+    app_handle: &tauri::AppHandle,
+    root_dir: &Path,
+    command: &str,
+) -> Result<(), AppError> {
+    let mut approved = load_approved_commands(app_handle);
+    let commands = approved
+        .entry(root_dir.to_string_lossy().to_string())
+        .or_default();
+    if !commands.iter().any(|approved| approved == command) {
+        commands.push(command.to_string());
+    }
+    let content =
+        serde_json::to_string_pretty(&approved).map_err(|e| AppError::Json(e.to_string()))?;
+    fs::write(get_approved_commands_path(app_handle)?, content).map_err(AppError::Io)
+}
+
 /// Helper function to ensure a config is properly saved
 /// Returns the path where the config was saved if successful
 pub fn ensure_config_saved(
@@ -371,7 +1325,7 @@ pub fn ensure_config_saved(
         home_dir.join(GLOBAL_CONFIG_FILE)
     } else {
         if let Some(dir) = current_dir {
-            dir.join(PROJECT_CONFIG_FILE)
+            project_config_path(dir)
         } else {
             return Err(AppError::Config(
                 "Current directory required for local config".to_string(),
@@ -481,3 +1435,118 @@ pub fn diagnose_config_file_access(file_path: &Path) -> String {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keys the gptree CLI reads from `.gptree_config`
+    const CLI_KEYS: &[&str] = &[
+        "version",
+        "useGitIgnore",
+        "includeFileTypes",
+        "excludeFileTypes",
+        "outputFile",
+        "saveOutputFile",
+        "outputFileLocally",
+        "copyToClipboard",
+        "safeMode",
+        "storeFilesChosen",
+        "lineNumbers",
+        "showIgnoredInTree",
+        "showDefaultIgnoredInTree",
+        "previousFiles",
+        "excludeDirs",
+    ];
+
+    fn written(config: &Config, is_global: bool) -> String {
+        let mut buffer = Vec::new();
+        write_config_entries(&mut buffer, config, is_global).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    fn parsed(contents: &str) -> Config {
+        let mut config = Config::default();
+        for (key, value) in contents.lines().filter_map(parse_config_line) {
+            apply_config_entry(&mut config, key, value);
+        }
+        config
+    }
+
+    fn scratch_file(name: &str, contents: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("gptree-config-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(".gptree_config");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn cli_only_sees_its_own_keys() {
+        // The CLI skips lines starting with '#', so whatever is left must be a key it knows
+        let contents = written(&Config::default(), false);
+        for line in contents.lines().filter(|line| !line.starts_with('#')) {
+            let (key, _) = line.split_once(':').unwrap();
+            assert!(
+                CLI_KEYS.contains(&key),
+                "CLI would read GUI-only key {}",
+                key
+            );
+        }
+        assert!(contents
+            .lines()
+            .any(|line| line.starts_with(&format!("{}treeHeader:", GUI_KEY_PREFIX))));
+    }
+
+    #[test]
+    fn gui_settings_round_trip() {
+        let config = Config {
+            tree_header: "Layout".to_string(),
+            include_file_hash: true,
+            previous_files: vec!["src/main.rs".to_string(), "README.md".to_string()],
+            exclude_dirs: vec!["target".to_string()],
+            ..Config::default()
+        };
+        assert_eq!(parsed(&written(&config, false)), config);
+    }
+
+    #[test]
+    fn legacy_file_header_prefix_becomes_template() {
+        let config = parsed("#gui:fileHeaderPrefix: ###");
+        assert_eq!(config.file_header_template, "### {path}");
+    }
+
+    #[test]
+    fn comments_are_not_entries() {
+        assert_eq!(parse_config_line("# Whether to use .gitignore"), None);
+        assert_eq!(parse_config_line("   "), None);
+        assert_eq!(
+            parse_config_line("#gui:treeHeader: Layout"),
+            Some(("treeHeader", "Layout"))
+        );
+    }
+
+    #[test]
+    fn saving_keeps_entries_from_other_versions() {
+        let path = scratch_file(
+            "preserve",
+            "version: 1\nuseGitIgnore: false\ncliOnlyOption: 3\n#gui:fromNewerGui: yes\n",
+        );
+        let mut config = load_config(&path).unwrap();
+        assert!(!config.use_git_ignore);
+
+        config.safe_mode = false;
+        save_config(&path, &config, false).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.lines().any(|line| line == "cliOnlyOption: 3"));
+        assert!(saved.lines().any(|line| line == "#gui:fromNewerGui: yes"));
+        assert_eq!(load_config(&path).unwrap(), config);
+
+        // Saving again doesn't pile up copies of them
+        save_config(&path, &config, false).unwrap();
+        let resaved = fs::read_to_string(&path).unwrap();
+        assert_eq!(resaved.matches("cliOnlyOption").count(), 1);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+use crate::fs::matches_ignored_name;
+use std::fs;
+use std::path::Path;
+
+/// Lockfiles made redundant by the dependency summary
+pub const LOCKFILE_NAMES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "poetry.lock",
+    "uv.lock",
+    "Pipfile.lock",
+];
+
+/// Direct dependencies declared in one manifest, grouped by section
+struct ManifestSummary {
+    rel_path: String,
+    sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+/// Summarize the direct dependencies declared in the project's Cargo.toml, package.json and
+/// pyproject.toml files, looking in the root and its immediate subdirectories.
+/// Returns None if there are no manifests with dependencies.
+pub fn dependency_summary(root_dir: &Path, default_ignores: &[String]) -> Option<String> {
+    let mut dirs = vec![root_dir.to_path_buf()];
+    if let Ok(entries) = fs::read_dir(root_dir) {
+        let mut children: Vec<_> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir() && !matches_ignored_name(root_dir, path, default_ignores))
+            .collect();
+        children.sort();
+        dirs.extend(children);
+    }
+
+    let mut summaries = Vec::new();
+    for dir in &dirs {
+        for (file_name, parse) in MANIFEST_PARSERS {
+            let path = dir.join(file_name);
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let sections: Vec<_> = parse(&content)
+                .into_iter()
+                .filter(|(_, deps)| !deps.is_empty())
+                .collect();
+            if sections.is_empty() {
+                continue;
+            }
+            let rel_path = path.strip_prefix(root_dir).unwrap_or(&path);
+            summaries.push(ManifestSummary {
+                rel_path: rel_path.to_string_lossy().replace('\\', "/"),
+                sections,
+            });
+        }
+    }
+
+    if summaries.is_empty() {
+        return None;
+    }
+    Some(render_summaries(&summaries))
+}
+
+type SectionParser = fn(&str) -> Vec<(String, Vec<(String, String)>)>;
+
+const MANIFEST_PARSERS: &[(&str, SectionParser)] = &[
+    ("Cargo.toml", parse_cargo_toml),
+    ("package.json", parse_package_json),
+    ("pyproject.toml", parse_pyproject_toml),
+];
+
+fn render_summaries(summaries: &[ManifestSummary]) -> String {
+    let mut lines = Vec::new();
+    for summary in summaries {
+        lines.push(format!("## {}", summary.rel_path));
+        for (section, deps) in &summary.sections {
+            lines.push(format!("{}:", section));
+            for (name, version) in deps {
+                if version.is_empty() {
+                    lines.push(format!("  {}", name));
+                } else {
+                    lines.push(format!("  {} {}", name, version));
+                }
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+fn parse_cargo_toml(content: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let Ok(manifest) = content.parse::<toml::Table>() else {
+        return Vec::new();
+    };
+
+    ["dependencies", "dev-dependencies", "build-dependencies"]
+        .iter()
+        .map(|section| {
+            // Workspace roots declare shared versions under [workspace.dependencies]
+            let table = manifest.get(*section).or_else(|| {
+                (*section == "dependencies")
+                    .then(|| manifest.get("workspace")?.get("dependencies"))
+                    .flatten()
+            });
+            let deps = table
+                .and_then(toml::Value::as_table)
+                .map(|deps| {
+                    deps.iter()
+                        .map(|(name, spec)| (name.clone(), cargo_version(spec)))
+                        .collect()
+                })
+                .unwrap_or_default();
+            (section.to_string(), deps)
+        })
+        .collect()
+}
+
+/// The version requirement of a Cargo dependency, or where it comes from if it has none
+fn cargo_version(spec: &toml::Value) -> String {
+    if let Some(version) = spec.as_str() {
+        return version.to_string();
+    }
+    let Some(table) = spec.as_table() else {
+        return String::new();
+    };
+    if let Some(version) = table.get("version").and_then(toml::Value::as_str) {
+        version.to_string()
+    } else if table.contains_key("workspace") {
+        "(workspace)".to_string()
+    } else if table.contains_key("path") {
+        "(path)".to_string()
+    } else if table.contains_key("git") {
+        "(git)".to_string()
+    } else {
+        String::new()
+    }
+}
+
+fn parse_package_json(content: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let Ok(manifest) = serde_json::from_str::<serde_json::Value>(content) else {
+        return Vec::new();
+    };
+
+    ["dependencies", "devDependencies", "peerDependencies"]
+        .iter()
+        .map(|section| {
+            let deps = manifest
+                .get(*section)
+                .and_then(serde_json::Value::as_object)
+                .map(|deps| {
+                    deps.iter()
+                        .map(|(name, version)| {
+                            (
+                                name.clone(),
+                                version.as_str().unwrap_or_default().to_string(),
+                            )
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            (section.to_string(), deps)
+        })
+        .collect()
+}
+
+fn parse_pyproject_toml(content: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let Ok(manifest) = content.parse::<toml::Table>() else {
+        return Vec::new();
+    };
+    let mut sections = Vec::new();
+
+    // PEP 621: requirement strings like "requests>=2.31"
+    if let Some(project) = manifest.get("project") {
+        let requirements = project
+            .get("dependencies")
+            .and_then(toml::Value::as_array)
+            .map(|deps| parse_requirements(deps))
+            .unwrap_or_default();
+        sections.push(("dependencies".to_string(), requirements));
+
+        if let Some(extras) = project
+            .get("optional-dependencies")
+            .and_then(toml::Value::as_table)
+        {
+            for (extra, deps) in extras {
+                let requirements = deps
+                    .as_array()
+                    .map(|deps| parse_requirements(deps))
+                    .unwrap_or_default();
+                sections.push((format!("optional-dependencies.{}", extra), requirements));
+            }
+        }
+    }
+
+    // Poetry: a table of name = version or name = { version = ... }
+    if let Some(deps) = manifest
+        .get("tool")
+        .and_then(|tool| tool.get("poetry"))
+        .and_then(|poetry| poetry.get("dependencies"))
+        .and_then(toml::Value::as_table)
+    {
+        let deps = deps
+            .iter()
+            .map(|(name, spec)| (name.clone(), cargo_version(spec)))
+            .collect();
+        sections.push(("tool.poetry.dependencies".to_string(), deps));
+    }
+
+    sections
+}
+
+/// Split PEP 508 requirement strings into a name and the rest of the specifier
+fn parse_requirements(requirements: &[toml::Value]) -> Vec<(String, String)> {
+    requirements
+        .iter()
+        .filter_map(toml::Value::as_str)
+        .map(|requirement| {
+            let split_at = requirement
+                .find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | '.')))
+                .unwrap_or(requirement.len());
+            let (name, version) = requirement.split_at(split_at);
+            (name.to_string(), version.trim().to_string())
+        })
+        .collect()
+}
@@ -0,0 +1,96 @@
+use crate::fs::detect_project_filters;
+use crate::models::{AppError, ExclusionSuggestion};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+/// File in the app data directory holding the exclusions learned from over-budget generations
+const LEARNED_EXCLUSIONS_FILE: &str = "learned_exclusions.json";
+
+/// Group for projects whose stack isn't recognised
+const OTHER_PROJECT_TYPE: &str = "other";
+
+/// How often each directory was excluded to bring an output back under budget,
+/// keyed by project type and then by project-relative directory
+type LearnedExclusions = BTreeMap<String, BTreeMap<String, u32>>;
+
+fn learned_exclusions_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Config(format!("Could not determine app data directory: {}", e)))?;
+    fs::create_dir_all(&data_dir)?;
+    Ok(data_dir.join(LEARNED_EXCLUSIONS_FILE))
+}
+
+fn load_learned(app_handle: &tauri::AppHandle) -> LearnedExclusions {
+    learned_exclusions_path(app_handle)
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// The stacks detected in `root_dir`, which decide what counts as a similar project
+fn project_types(root_dir: &Path) -> Vec<String> {
+    detect_project_filters(root_dir)
+        .map(|suggestion| suggestion.project_types)
+        .unwrap_or_else(|| vec![OTHER_PROJECT_TYPE.to_string()])
+}
+
+/// Remember that `dirs` were excluded in `root_dir` to get an over-budget output under budget
+pub fn record(
+    app_handle: &tauri::AppHandle,
+    root_dir: &Path,
+    dirs: &[String],
+) -> Result<(), AppError> {
+    let mut learned = load_learned(app_handle);
+    for project_type in project_types(root_dir) {
+        let counts = learned.entry(project_type).or_default();
+        for dir in dirs {
+            *counts.entry(dir.clone()).or_default() += 1;
+        }
+    }
+
+    let content =
+        serde_json::to_string_pretty(&learned).map_err(|e| AppError::Json(e.to_string()))?;
+    fs::write(learned_exclusions_path(app_handle)?, content)?;
+    Ok(())
+}
+
+/// Exclusions learned from projects of the same type that would apply to `root_dir`: directories
+/// it has that aren't excluded yet, most often excluded first
+pub fn suggest(
+    app_handle: &tauri::AppHandle,
+    root_dir: &Path,
+    already_excluded: &HashSet<String>,
+) -> Vec<ExclusionSuggestion> {
+    let learned = load_learned(app_handle);
+    let mut suggestions: Vec<ExclusionSuggestion> = Vec::new();
+    for project_type in project_types(root_dir) {
+        let Some(counts) = learned.get(&project_type) else {
+            continue;
+        };
+        for (dir, count) in counts {
+            if already_excluded.contains(dir) || !root_dir.join(dir).is_dir() {
+                continue;
+            }
+            // A project with several stacks can learn the same directory more than once
+            match suggestions.iter_mut().find(|s| &s.dir == dir) {
+                Some(existing) => existing.times_excluded = existing.times_excluded.max(*count),
+                None => suggestions.push(ExclusionSuggestion {
+                    dir: dir.clone(),
+                    project_type: project_type.clone(),
+                    times_excluded: *count,
+                }),
+            }
+        }
+    }
+    suggestions.sort_by(|a, b| {
+        b.times_excluded
+            .cmp(&a.times_excluded)
+            .then_with(|| a.dir.cmp(&b.dir))
+    });
+    suggestions
+}
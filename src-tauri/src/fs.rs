@@ -1,25 +1,32 @@
-use crate::models::{AppError, DirectoryItem, TreeStructure, DEFAULT_IGNORES};
+use crate::git;
+use crate::models::{
+    AppError, Config, DirSize, DirectoryItem, FileListInput, FilterSuggestion, FlatTree,
+    IgnoreReason, InfraFile, KeyFileSuggestion, SizeBucket, SizeHistogram, TreeStructure,
+    FLAT_NODE_CHILDREN_PENDING, FLAT_NODE_DEFAULT_IGNORED, FLAT_NODE_DIR,
+    FLAT_NODE_EXCLUDED_BY_CONFIG,
+};
+use ec4rs::property::{Charset, EndOfLine};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
 use ignore::{Walk, WalkBuilder};
-use std::collections::HashSet;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::{Read, Write};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Arc;
 use walkdir::{DirEntry, WalkDir};
 
-/// Checks if a file or directory should be ignored based on default ignores
-pub fn is_default_ignored(path: &Path) -> bool {
-    let file_name = path
-        .file_name()
-        .and_then(|name| name.to_str())
-        .unwrap_or("");
-
-    DEFAULT_IGNORES.contains(&file_name)
-        || path.components().any(|comp| {
-            comp.as_os_str()
-                .to_str()
-                .map(|s| DEFAULT_IGNORES.contains(&s))
-                .unwrap_or(false)
-        })
+/// Whether `path` or any folder it's in below `root_dir` is named in `names`. Folders above
+/// the root don't count, so a project kept under something like `~/build/` still shows.
+pub fn matches_ignored_name(root_dir: &Path, path: &Path, names: &[impl AsRef<str>]) -> bool {
+    let relative = path.strip_prefix(root_dir).unwrap_or(path);
+    relative.components().any(|comp| {
+        comp.as_os_str()
+            .to_str()
+            .is_some_and(|s| names.iter().any(|name| name.as_ref() == s))
+    })
 }
 
 /// Load gitignore patterns from the root directory or its parents
@@ -45,60 +52,409 @@ pub fn load_gitignore(
     Ok(None)
 }
 
-/// Generate a tree structure representation of a directory
-pub fn generate_tree_structure(
-    root_dir: &Path,
-    use_gitignore: bool,
+/// Tracked files a sparse checkout leaves out of the working tree. Listing them shells out to
+/// git, so it's done once per load and shared by the filters built during it.
+pub type SkipWorktree = Arc<HashSet<PathBuf>>;
+
+/// Look up the skip-worktree files of the repository `root_dir` is in
+pub fn load_skip_worktree(root_dir: &Path) -> SkipWorktree {
+    Arc::new(
+        git::find_repo_root(root_dir)
+            .map(|repo_root| git::skip_worktree_paths(&repo_root))
+            .unwrap_or_default(),
+    )
+}
+
+/// Decides which entries appear in the tree, combining gitignore, default ignores,
+/// .gitattributes linguist annotations and the include/exclude file type filters
+pub struct PathFilter {
+    root_dir: PathBuf,
+    gitignore: Option<Gitignore>,
+    git_exclude: Option<Gitignore>,
+    linguist: Option<Gitignore>,
+    skip_worktree: SkipWorktree,
     show_ignored: bool,
     show_default_ignored: bool,
-    include_file_types: &str,
-    exclude_file_types: &str,
-    excluded_dirs: &HashSet<String>,
-) -> Result<TreeStructure, AppError> {
-    // Load gitignore if requested
-    let gitignore = if use_gitignore {
-        load_gitignore(root_dir)?.and_then(|builder| builder.build().ok())
-    } else {
-        None
-    };
+    default_ignores: Vec<String>,
+    include_all: bool,
+    included_extensions: HashSet<String>,
+    excluded_extensions: HashSet<String>,
+    /// Keep directories that are filtered out in the tree as a one-line stub
+    pub stub_excluded_dirs: bool,
+    pub follow_symlinks: bool,
+}
 
-    // Parse include_file_types into a set of extensions
-    let include_all = include_file_types == "*";
-    let included_extensions: HashSet<String> = if !include_all {
-        include_file_types
-            .split(',')
-            .map(|ext| ext.trim().to_lowercase())
-            .filter(|ext| !ext.is_empty())
-            .collect()
-    } else {
-        HashSet::new() // Empty set when including all file types
-    };
+impl PathFilter {
+    /// Build the filter for `root_dir` from the display settings in `config`
+    pub fn new(
+        root_dir: &Path,
+        config: &Config,
+        skip_worktree: &SkipWorktree,
+    ) -> Result<Self, AppError> {
+        // Load gitignore if requested
+        let gitignore = if config.use_git_ignore {
+            load_gitignore(root_dir)?.and_then(|builder| builder.build().ok())
+        } else {
+            None
+        };
 
-    // Convert exclude_file_types to a set for efficient lookups
-    let excluded_extensions: HashSet<String> = exclude_file_types
-        .split(',')
-        .map(|ext| ext.trim().to_lowercase())
-        .filter(|ext| !ext.is_empty())
-        .collect();
+        // `.git` is a file in linked worktrees, so find the shared git dir through it
+        let repo_root = git::find_repo_root(root_dir);
+        let git_exclude = if config.use_git_ignore {
+            repo_root.as_deref().and_then(load_git_exclude)
+        } else {
+            None
+        };
+
+        let linguist = if config.respect_linguist_attributes {
+            load_linguist_attributes(root_dir)
+        } else {
+            None
+        };
+
+        // Parse include_file_types into a set of extensions
+        let include_all = config.include_file_types == "*";
+        let included_extensions: HashSet<String> = if !include_all {
+            parse_extensions(&config.include_file_types)
+        } else {
+            HashSet::new() // Empty set when including all file types
+        };
+
+        Ok(Self {
+            root_dir: root_dir.to_path_buf(),
+            gitignore,
+            git_exclude,
+            linguist,
+            // Files left out of a sparse checkout aren't materialized, so they never belong in the tree
+            skip_worktree: skip_worktree.clone(),
+            show_ignored: config.show_ignored_in_tree,
+            show_default_ignored: config.show_default_ignored_in_tree,
+            default_ignores: config.default_ignores.clone(),
+            include_all,
+            included_extensions,
+            excluded_extensions: parse_extensions(&config.exclude_file_types),
+            stub_excluded_dirs: config.stub_excluded_dirs,
+            follow_symlinks: config.follow_symlinks,
+        })
+    }
+
+    /// Check if an entry passes the ignore rules (gitignore, linguist attributes, default ignores)
+    pub fn passes_ignores(&self, path: &Path, is_dir: bool) -> bool {
+        if self.show_ignored {
+            return true;
+        }
 
-    // Helper function to check if a file should be included based on its extension
-    let should_include_file = |path: &Path| -> bool {
-        if path.is_dir() {
-            return true; // Always include directories in the tree
+        let matches = |matcher: &Option<Gitignore>| {
+            matcher
+                .as_ref()
+                .is_some_and(|m| m.matched(path, is_dir).is_ignore())
+        };
+        let should_ignore =
+            matches(&self.gitignore) || matches(&self.git_exclude) || matches(&self.linguist);
+
+        if self.show_default_ignored {
+            !should_ignore
+        } else {
+            !should_ignore && !self.is_default_ignored(path)
         }
+    }
+
+    /// Whether `path` is hidden by the config's default ignores
+    pub fn is_default_ignored(&self, path: &Path) -> bool {
+        matches_ignored_name(&self.root_dir, path, &self.default_ignores)
+    }
 
+    /// Check if a file should be included based on its extension
+    pub fn passes_extension(&self, path: &Path) -> bool {
         if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
             let ext = format!(".{}", extension.to_lowercase());
-            if include_all {
-                !excluded_extensions.contains(&ext)
+            if self.include_all {
+                !self.excluded_extensions.contains(&ext)
             } else {
-                included_extensions.contains(&ext)
+                self.included_extensions.contains(&ext)
             }
         } else {
             // Files without extensions
-            include_all // Include only if we're including all files
+            self.include_all // Include only if we're including all files
         }
-    };
+    }
+
+    /// Where `path` points if it's a symlink the config doesn't follow.
+    /// Such entries are shown as leaves, never descended into.
+    pub fn unfollowed_symlink(&self, path: &Path) -> Option<String> {
+        if self.follow_symlinks {
+            return None;
+        }
+        let is_symlink = fs::symlink_metadata(path)
+            .map(|metadata| metadata.file_type().is_symlink())
+            .unwrap_or(false);
+        if !is_symlink {
+            return None;
+        }
+        let target = fs::read_link(path).ok()?;
+        Some(target.to_string_lossy().to_string())
+    }
+
+    /// Every rule that keeps `path` out of the tree under the current settings, checking its
+    /// parent directories too since nothing inside a hidden directory is listed. Empty when
+    /// the path is shown.
+    pub fn explain(
+        &self,
+        root_dir: &Path,
+        path: &Path,
+        excluded_dirs_config: &HashSet<String>,
+    ) -> Vec<IgnoreReason> {
+        let mut reasons = Vec::new();
+        let Ok(relative) = path.strip_prefix(root_dir) else {
+            return reasons;
+        };
+
+        let mut current = root_dir.to_path_buf();
+        let mut chain = vec![current.clone()];
+        for component in relative.components() {
+            current.push(component);
+            chain.push(current.clone());
+        }
+
+        for (depth, entry) in chain.iter().enumerate() {
+            let is_last = depth + 1 == chain.len();
+            let is_dir = !is_last || path.is_dir();
+            let matched_path = project_relative_path(root_dir, entry)
+                .to_string_lossy()
+                .to_string();
+            let mut reason =
+                |kind: &str, source: Option<&Path>, pattern: Option<&str>, message: String| {
+                    let line = source
+                        .zip(pattern)
+                        .and_then(|(source, pattern)| pattern_line(source, pattern));
+                    reasons.push(IgnoreReason {
+                        kind: kind.to_string(),
+                        matched_path: matched_path.clone(),
+                        source: source.map(|source| source.to_string_lossy().to_string()),
+                        line,
+                        pattern: pattern.map(str::to_string),
+                        message,
+                    });
+                };
+
+            if !self.show_ignored {
+                let matchers = [
+                    ("gitignore", &self.gitignore, None),
+                    ("git_exclude", &self.git_exclude, None),
+                    (
+                        "gitattributes",
+                        &self.linguist,
+                        Some(root_dir.join(".gitattributes")),
+                    ),
+                ];
+                for (kind, matcher, fallback_source) in matchers {
+                    let Some(matcher) = matcher else {
+                        continue;
+                    };
+                    if let Match::Ignore(glob) = matcher.matched(entry, is_dir) {
+                        let source = glob.from().map(Path::to_path_buf).or(fallback_source);
+                        let message = match &source {
+                            Some(source) => format!(
+                                "{} matches \"{}\" in {}",
+                                matched_path,
+                                glob.original(),
+                                source.to_string_lossy()
+                            ),
+                            None => format!("{} matches \"{}\"", matched_path, glob.original()),
+                        };
+                        reason(kind, source.as_deref(), Some(glob.original()), message);
+                    }
+                }
+
+                let name = entry.file_name().and_then(|name| name.to_str());
+                let default_ignored =
+                    name.filter(|name| self.default_ignores.iter().any(|ignored| ignored == name));
+                if let (Some(name), false) = (default_ignored, self.show_default_ignored) {
+                    reason(
+                        "default_ignore",
+                        None,
+                        Some(name),
+                        format!(
+                            "{} is ignored by default; turn on \"Show default ignored files\" to see it",
+                            name
+                        ),
+                    );
+                }
+            }
+
+            if is_dir && depth > 0 && excluded_dirs_config.contains(&matched_path) {
+                reason(
+                    "config_exclude",
+                    None,
+                    None,
+                    format!("{} is in the excluded directories", matched_path),
+                );
+            }
+            if !is_last {
+                if let Some(target) = self.unfollowed_symlink(entry) {
+                    reason(
+                        "unfollowed_symlink",
+                        None,
+                        None,
+                        format!(
+                            "{} is a symlink to {} and symlinks aren't followed",
+                            matched_path, target
+                        ),
+                    );
+                }
+            }
+        }
+
+        if !path.is_dir() {
+            let matched_path = project_relative_path(root_dir, path)
+                .to_string_lossy()
+                .to_string();
+            if self.skip_worktree.contains(path) {
+                reasons.push(IgnoreReason {
+                    kind: "sparse_checkout".to_string(),
+                    matched_path: matched_path.clone(),
+                    source: None,
+                    line: None,
+                    pattern: None,
+                    message: format!("{} is outside the sparse checkout", matched_path),
+                });
+            }
+            if !self.passes_extension(path) {
+                let message = if self.include_all {
+                    format!("{} has an excluded file type", matched_path)
+                } else {
+                    format!("{} isn't one of the included file types", matched_path)
+                };
+                reasons.push(IgnoreReason {
+                    kind: "extension_filter".to_string(),
+                    matched_path,
+                    source: None,
+                    line: None,
+                    pattern: None,
+                    message,
+                });
+            }
+        }
+        reasons
+    }
+
+    /// Check if an entry should be shown; directories only need to pass the ignore rules
+    pub fn should_show(&self, path: &Path, is_dir: bool) -> bool {
+        if is_dir {
+            self.passes_ignores(path, true)
+        } else {
+            !self.skip_worktree.contains(path)
+                && self.passes_ignores(path, false)
+                && self.passes_extension(path)
+        }
+    }
+}
+
+/// The 1-based line of `source` holding `pattern`, the last one if it appears more than once
+/// since later rules win. Gitattributes lines hold the pattern followed by attributes.
+fn pattern_line(source: &Path, pattern: &str) -> Option<usize> {
+    let content = fs::read_to_string(source).ok()?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            line == pattern || line.split_whitespace().next() == Some(pattern)
+        })
+        .last()
+        .map(|(index, _)| index + 1)
+}
+
+/// Load the repository's `info/exclude` patterns, shared by every worktree
+fn load_git_exclude(repo_root: &Path) -> Option<Gitignore> {
+    let git_dir = git::resolve_git_dir(repo_root)?;
+    let exclude_path = git::common_git_dir(&git_dir).join("info").join("exclude");
+    if !exclude_path.is_file() {
+        return None;
+    }
+
+    let mut builder = GitignoreBuilder::new(repo_root);
+    builder.add(&exclude_path);
+    builder.build().ok()
+}
+
+/// Parse a comma-separated list of file types into a set of lowercase extensions
+fn parse_extensions(file_types: &str) -> HashSet<String> {
+    file_types
+        .split(',')
+        .map(|ext| ext.trim().to_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
+
+/// Build a matcher for paths marked `linguist-generated` or `linguist-vendored` in the
+/// root .gitattributes, so generated and vendored code is hidden the same way GitHub hides it
+pub fn load_linguist_attributes(root_dir: &Path) -> Option<Gitignore> {
+    let contents = fs::read_to_string(root_dir.join(".gitattributes")).ok()?;
+    let mut builder = GitignoreBuilder::new(root_dir);
+    let mut has_patterns = false;
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut parts = line.split_whitespace();
+        let Some(pattern) = parts.next() else {
+            continue;
+        };
+
+        for attr in parts {
+            // Gitattributes patterns follow gitignore rules, so a later unset can whitelist
+            let glob = match attr {
+                "linguist-generated"
+                | "linguist-generated=true"
+                | "linguist-vendored"
+                | "linguist-vendored=true" => pattern.to_string(),
+                "-linguist-generated"
+                | "linguist-generated=false"
+                | "-linguist-vendored"
+                | "linguist-vendored=false" => format!("!{}", pattern),
+                _ => continue,
+            };
+            if builder.add_line(None, &glob).is_ok() {
+                has_patterns = true;
+            }
+        }
+    }
+
+    if has_patterns {
+        builder.build().ok()
+    } else {
+        None
+    }
+}
+
+/// Sort entries with directories first, then alphabetically
+fn sort_entries(items: &mut [PathBuf]) {
+    items.sort_by(|a, b| {
+        let a_is_dir = a.is_dir();
+        let b_is_dir = b.is_dir();
+        if a_is_dir && !b_is_dir {
+            std::cmp::Ordering::Less
+        } else if !a_is_dir && b_is_dir {
+            std::cmp::Ordering::Greater
+        } else {
+            a.file_name()
+                .unwrap_or_default()
+                .cmp(b.file_name().unwrap_or_default())
+        }
+    });
+}
+
+/// Generate a tree structure representation of a directory
+pub fn generate_tree_structure(
+    root_dir: &Path,
+    config: &Config,
+    excluded_dirs: &HashSet<String>,
+) -> Result<TreeStructure, AppError> {
+    let filter = PathFilter::new(root_dir, config, &load_skip_worktree(root_dir))?;
 
     let mut tree_lines = vec![".".to_string()];
     let mut file_list = Vec::new();
@@ -110,10 +466,7 @@ pub fn generate_tree_structure(
         indent_prefix: &str,
         tree_lines: &mut Vec<String>,
         file_list: &mut Vec<String>,
-        gitignore: &Option<ignore::gitignore::Gitignore>,
-        show_ignored: bool,
-        show_default_ignored: bool,
-        should_include_file: &dyn Fn(&Path) -> bool,
+        filter: &PathFilter,
         excluded_dirs: &HashSet<String>,
     ) -> Result<(), AppError> {
         // Get directory entries
@@ -122,85 +475,47 @@ pub fn generate_tree_structure(
             .map(|e| e.path())
             .collect::<Vec<PathBuf>>();
 
+        // Excluded directories kept in the tree as a one-line stub
+        let mut stubbed = HashSet::new();
+
         // Filter and sort entries
         let mut items: Vec<PathBuf> = entries
             .into_iter()
             .filter(|entry| {
-                // Always include directories for tree structure
-                if entry.is_dir() {
+                let is_dir = entry.is_dir();
+                if is_dir {
                     // Check if directory is in excluded_dirs
                     let relative_path_to_check = entry
                         .strip_prefix(root_dir)
                         .unwrap_or(entry)
                         .to_string_lossy()
                         .into_owned();
-                    if excluded_dirs.contains(&relative_path_to_check) {
-                        return false; // Skip this directory and its children
-                    }
-
-                    if show_ignored {
-                        return true;
-                    } else {
-                        let should_ignore = if let Some(gitignore) = gitignore {
-                            gitignore.matched(entry, true).is_ignore()
-                        } else {
-                            false
-                        };
-
-                        let is_default_ignored = is_default_ignored(entry);
-
-                        if show_default_ignored {
-                            return !should_ignore;
-                        } else {
-                            return !should_ignore && !is_default_ignored;
+                    let excluded = excluded_dirs.contains(&relative_path_to_check)
+                        || !filter.should_show(entry, true);
+                    if excluded {
+                        // .git holds repository internals, never project content
+                        let is_git_dir = entry.file_name().is_some_and(|name| name == ".git");
+                        if filter.stub_excluded_dirs && !is_git_dir {
+                            stubbed.insert(entry.clone());
+                            return true;
                         }
+                        return false; // Skip this directory and its children
                     }
+                    return true;
                 }
-
-                // For files, apply both gitignore and extension filters
-                let passes_gitignore = if show_ignored {
-                    true
-                } else {
-                    let should_ignore = if let Some(gitignore) = gitignore {
-                        gitignore.matched(entry, false).is_ignore()
-                    } else {
-                        false
-                    };
-
-                    let is_default_ignored = is_default_ignored(entry);
-
-                    if show_default_ignored {
-                        !should_ignore
-                    } else {
-                        !should_ignore && !is_default_ignored
-                    }
-                };
-
-                // Only apply file type filtering if the file passes the gitignore filter
-                passes_gitignore && should_include_file(entry)
+                filter.should_show(entry, is_dir)
             })
             .collect();
 
         // Sort items (directories first, then alphabetically)
-        items.sort_by(|a, b| {
-            let a_is_dir = a.is_dir();
-            let b_is_dir = b.is_dir();
-            if a_is_dir && !b_is_dir {
-                std::cmp::Ordering::Less
-            } else if !a_is_dir && b_is_dir {
-                std::cmp::Ordering::Greater
-            } else {
-                a.file_name()
-                    .unwrap_or_default()
-                    .cmp(b.file_name().unwrap_or_default())
-            }
-        });
+        sort_entries(&mut items);
 
         let num_items = items.len();
 
         for (index, item_path) in items.into_iter().enumerate() {
             let is_last_item = index == num_items - 1;
-            let is_directory = item_path.is_dir();
+            let symlink_target = filter.unfollowed_symlink(&item_path);
+            let is_directory = symlink_target.is_none() && item_path.is_dir();
 
             // Get item name for display
             let item_display_name = item_path
@@ -209,8 +524,10 @@ pub fn generate_tree_structure(
                 .unwrap_or("")
                 .to_string();
 
-            // Append "/" for directories
-            let display_name = if is_directory {
+            // Append "/" for directories, and show where unfollowed symlinks point
+            let display_name = if let Some(target) = &symlink_target {
+                format!("{} -> {}", item_display_name, target)
+            } else if is_directory {
                 format!("{}/", item_display_name)
             } else {
                 item_display_name
@@ -224,8 +541,21 @@ pub fn generate_tree_structure(
             };
             let line_prefix = format!("{}{}", indent_prefix, connector);
 
+            if stubbed.contains(&item_path) {
+                tree_lines.push(format!(
+                    "{}{} {}",
+                    line_prefix,
+                    display_name,
+                    excluded_dir_stub(&item_path)
+                ));
+                continue;
+            }
+
             tree_lines.push(format!("{}{}", line_prefix, display_name));
 
+            if symlink_target.is_some() {
+                continue;
+            }
             if is_directory {
                 // Indentation for subdirectories: '    ' if last item, '│   ' otherwise
                 let new_indent_prefix = format!(
@@ -239,10 +569,7 @@ pub fn generate_tree_structure(
                     &new_indent_prefix,
                     tree_lines,
                     file_list,
-                    gitignore,
-                    show_ignored,
-                    show_default_ignored,
-                    should_include_file,
+                    filter,
                     excluded_dirs,
                 )?;
             } else if item_path.is_file() {
@@ -259,10 +586,7 @@ pub fn generate_tree_structure(
         "",
         &mut tree_lines,
         &mut file_list,
-        &gitignore,
-        show_ignored,
-        show_default_ignored,
-        &should_include_file,
+        &filter,
         excluded_dirs,
     )?;
 
@@ -272,15 +596,391 @@ pub fn generate_tree_structure(
     })
 }
 
-/// Read the content of a file
-pub fn read_file_content(file_path: &Path) -> Result<String, AppError> {
-    let mut file = File::open(file_path)
+/// Top-level folder that selected files from outside the project root are listed under
+pub const EXTERNAL_ROOT: &str = "external";
+
+/// `path` as shown in outputs and stored in previous_files: relative to `root_dir`, or for a
+/// file outside the root (another workspace folder, a symlinked package) its absolute path
+/// under `external/`, e.g. `external/home/me/lib/a.rs` or `external/C/lib/a.rs`
+pub fn project_relative_path(root_dir: &Path, path: &Path) -> PathBuf {
+    if let Ok(relative) = path.strip_prefix(root_dir) {
+        return relative.to_path_buf();
+    }
+    let mut external = PathBuf::from(EXTERNAL_ROOT);
+    for component in path.components() {
+        match component {
+            // Pushing a prefix like `C:` would replace the whole path, so keep just the drive
+            Component::Prefix(prefix) => external.push(
+                prefix
+                    .as_os_str()
+                    .to_string_lossy()
+                    .trim_start_matches(['\\', '?'])
+                    .trim_end_matches(':')
+                    .replace(['\\', ':'], "_"),
+            ),
+            Component::RootDir => {}
+            other => external.push(other),
+        }
+    }
+    external
+}
+
+/// The absolute path for an entry written by project_relative_path.
+/// A real `external` folder in the project takes precedence over the virtual one.
+pub fn resolve_project_path(root_dir: &Path, relative: &str) -> PathBuf {
+    let inside = root_dir.join(relative);
+    let Ok(external) = Path::new(relative).strip_prefix(EXTERNAL_ROOT) else {
+        return inside;
+    };
+    if inside.exists() {
+        return inside;
+    }
+    #[cfg(windows)]
+    {
+        let mut components = external.components();
+        if let Some(drive) = components.next() {
+            return PathBuf::from(format!("{}:\\", drive.as_os_str().to_string_lossy()))
+                .join(components.as_path());
+        }
+    }
+    Path::new("/").join(external)
+}
+
+/// Where `path` ended up after `renames` (from, to), applied in order. A renamed directory
+/// carries everything under it along. None if none of the renames touch it.
+pub fn renamed_path(path: &Path, renames: &[(PathBuf, PathBuf)]) -> Option<PathBuf> {
+    let mut current = path.to_path_buf();
+    let mut moved = false;
+    for (from, to) in renames {
+        if let Ok(rest) = current.strip_prefix(from) {
+            current = if rest.as_os_str().is_empty() {
+                to.clone()
+            } else {
+                to.join(rest)
+            };
+            moved = true;
+        }
+    }
+    moved.then_some(current)
+}
+
+/// Render the tree for exactly these files, without walking the disk or applying any filters.
+/// Files outside `root_dir` are placed under an `external/` folder.
+pub fn tree_from_file_list(root_dir: &Path, files: &[String]) -> TreeStructure {
+    #[derive(Default)]
+    struct TreeNode {
+        dirs: BTreeMap<String, TreeNode>,
+        files: BTreeSet<String>,
+    }
+
+    let mut root = TreeNode::default();
+    for file in files {
+        let rel_path = project_relative_path(root_dir, Path::new(file));
+        let components: Vec<String> = rel_path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        let Some((file_name, dirs)) = components.split_last() else {
+            continue;
+        };
+        let mut node = &mut root;
+        for dir in dirs {
+            node = node.dirs.entry(dir.clone()).or_default();
+        }
+        node.files.insert(file_name.clone());
+    }
+
+    // Same layout as generate_tree_structure: directories first, then files
+    fn render(node: &TreeNode, indent_prefix: &str, tree_lines: &mut Vec<String>) {
+        let entries: Vec<(&String, Option<&TreeNode>)> = node
+            .dirs
+            .iter()
+            .map(|(name, child)| (name, Some(child)))
+            .chain(node.files.iter().map(|name| (name, None)))
+            .collect();
+        let num_items = entries.len();
+
+        for (index, (name, child)) in entries.into_iter().enumerate() {
+            let is_last_item = index == num_items - 1;
+            let connector = if is_last_item {
+                "└── "
+            } else {
+                "├── "
+            };
+            match child {
+                Some(child) => {
+                    tree_lines.push(format!("{}{}{}/", indent_prefix, connector, name));
+                    let new_indent_prefix = format!(
+                        "{}{}",
+                        indent_prefix,
+                        if is_last_item { "    " } else { "│   " }
+                    );
+                    render(child, &new_indent_prefix, tree_lines);
+                }
+                None => tree_lines.push(format!("{}{}{}", indent_prefix, connector, name)),
+            }
+        }
+    }
+
+    let mut tree_lines = vec![".".to_string()];
+    render(&root, "", &mut tree_lines);
+
+    TreeStructure {
+        tree_text: tree_lines.join("\n"),
+        file_list: files.to_vec(),
+    }
+}
+
+/// Read a list of files, one path per line, absolute or relative to `root_dir`.
+/// Blank lines and lines starting with `#` are skipped, and duplicates are listed once.
+pub fn read_file_list(list_path: &Path, root_dir: &Path) -> Result<FileListInput, AppError> {
+    let content = fs::read_to_string(list_path)
+        .map_err(|_| AppError::PathNotFound(list_path.to_string_lossy().to_string()))?;
+
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    let mut missing = Vec::new();
+    for line in content.lines() {
+        let entry = line.trim();
+        if entry.is_empty() || entry.starts_with('#') || !seen.insert(entry) {
+            continue;
+        }
+        let path = root_dir.join(entry);
+        if path.is_file() {
+            files.push(path.to_string_lossy().to_string());
+        } else {
+            missing.push(entry.to_string());
+        }
+    }
+    Ok(FileListInput { files, missing })
+}
+
+/// Format a byte count as a short human-readable size
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Format a count with thousands separators, e.g. 41,230
+fn format_count(count: u64) -> String {
+    let digits = count.to_string();
+    let mut formatted = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    formatted
+}
+
+/// The stub shown in place of an excluded directory's contents, e.g. `[excluded: 41,230 files, 312.4 MB]`
+fn excluded_dir_stub(dir_path: &Path) -> String {
+    let (files, bytes) = WalkDir::new(dir_path)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .fold((0u64, 0u64), |(files, bytes), entry| {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            (files + 1, bytes + size)
+        });
+    let noun = if files == 1 { "file" } else { "files" };
+    format!(
+        "[excluded: {} {}, {}]",
+        format_count(files),
+        noun,
+        format_size(bytes)
+    )
+}
+
+/// A file's content, cut short if the file was over the read limit
+pub struct FileRead {
+    pub content: String,
+    pub truncated: bool,
+}
+
+/// Read the content of a file, stopping after `max_bytes` (0 reads it all) so a stray core
+/// dump or database file can't stall a generation
+pub fn read_file_content(
+    file_path: &Path,
+    use_editorconfig: bool,
+    max_bytes: u64,
+) -> Result<FileRead, AppError> {
+    let file = File::open(file_path)
         .map_err(|_| AppError::PathNotFound(file_path.to_string_lossy().to_string()))?;
 
-    let mut content = String::new();
-    file.read_to_string(&mut content)?;
+    // One byte past the limit tells a file that's exactly at the limit from a longer one
+    let read_limit = if max_bytes == 0 {
+        u64::MAX
+    } else {
+        max_bytes.saturating_add(1)
+    };
+    let mut bytes = Vec::new();
+    file.take(read_limit).read_to_end(&mut bytes)?;
+    let truncated = max_bytes > 0 && bytes.len() as u64 > max_bytes;
+    if truncated {
+        bytes.truncate(max_bytes as usize);
+    }
+
+    let content = if use_editorconfig {
+        decode_with_editorconfig(file_path, bytes, truncated)?
+    } else {
+        decode_utf8(bytes, truncated)?
+    };
+    Ok(FileRead { content, truncated })
+}
+
+/// Decode UTF-8, dropping a character cut in half at the end of a truncated read
+fn decode_utf8(mut bytes: Vec<u8>, truncated: bool) -> Result<String, AppError> {
+    if truncated {
+        if let Err(e) = std::str::from_utf8(&bytes) {
+            if e.error_len().is_none() {
+                bytes.truncate(e.valid_up_to());
+            }
+        }
+    }
+    String::from_utf8(bytes)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e).into())
+}
+
+/// Decode a file using the charset and line endings its .editorconfig declares for it,
+/// normalizing declared CRLF or CR line endings to LF. Files with no hints are read as UTF-8.
+fn decode_with_editorconfig(
+    file_path: &Path,
+    bytes: Vec<u8>,
+    truncated: bool,
+) -> Result<String, AppError> {
+    let properties = ec4rs::properties_of(file_path).unwrap_or_default();
+
+    let content = match properties.get::<Charset>() {
+        Ok(Charset::Latin1) => bytes.iter().map(|&byte| byte as char).collect(),
+        Ok(Charset::Utf16Le) => decode_utf16(&bytes, u16::from_le_bytes)?,
+        Ok(Charset::Utf16Be) => decode_utf16(&bytes, u16::from_be_bytes)?,
+        _ => decode_utf8(bytes, truncated)?,
+    };
+    let content = content
+        .strip_prefix('\u{feff}')
+        .map(str::to_string)
+        .unwrap_or(content);
+
+    Ok(match properties.get::<EndOfLine>() {
+        Ok(EndOfLine::CrLf) => content.replace("\r\n", "\n"),
+        Ok(EndOfLine::Cr) => content.replace('\r', "\n"),
+        _ => content,
+    })
+}
+
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> Result<String, AppError> {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| to_unit([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e).into())
+}
+
+/// Read several files, up to `concurrency` at a time. Results are in the same order as `paths`.
+pub fn read_files(
+    paths: &[String],
+    concurrency: usize,
+    use_editorconfig: bool,
+    max_bytes: u64,
+) -> Vec<Result<FileRead, AppError>> {
+    parallel_map(paths, concurrency, |path| {
+        read_file_content(Path::new(path), use_editorconfig, max_bytes)
+    })
+}
+
+/// Hash several files, up to `concurrency` at a time. Results are in the same order as
+/// `paths`, with None for files that couldn't be read.
+pub fn hash_files(paths: &[PathBuf], concurrency: usize) -> Vec<Option<String>> {
+    parallel_map(paths, concurrency, |path| hash_file(path).ok())
+}
+
+/// Apply `f` to every item on up to `concurrency` threads, keeping the results in order
+fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    concurrency: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    if concurrency <= 1 || items.len() <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    // Each worker takes the next unclaimed item until none are left
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, R)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..concurrency.min(items.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, AtomicOrdering::Relaxed);
+                        let Some(item) = items.get(index) else {
+                            break;
+                        };
+                        done.push((index, f(item)));
+                    }
+                    done
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_default())
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// What identifies the file behind a path: device and inode on Unix, so hard links and bind
+/// mounts compare equal; elsewhere the canonical path, which resolves junctions and symlinks
+#[cfg(unix)]
+pub type FileIdentity = (u64, u64);
+#[cfg(not(unix))]
+pub type FileIdentity = PathBuf;
+
+#[cfg(unix)]
+pub fn file_identity(path: &Path) -> Option<FileIdentity> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn file_identity(path: &Path) -> Option<FileIdentity> {
+    fs::canonicalize(path).ok()
+}
 
-    Ok(content)
+/// Compute the hex-encoded SHA-256 of a file's content
+pub fn hash_content(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Hash a file on disk without loading it whole. Matches hash_content for UTF-8 files.
+pub fn hash_file(path: &Path) -> Result<String, AppError> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
 }
 
 /// Add line numbers to content
@@ -321,215 +1021,1030 @@ pub fn save_to_file(file_path: &Path, content: &str) -> Result<(), AppError> {
     Ok(())
 }
 
-/// Convert directory to a hierarchical tree of DirectoryItem structs for the UI
-pub fn get_directory_tree(
-    root_dir: &Path,
-    use_gitignore: bool,
-    show_ignored: bool,
-    show_default_ignored: bool,
-    include_file_types: &str,
-    exclude_file_types: &str,
-    excluded_dirs_config: &HashSet<String>,
-) -> Result<DirectoryItem, AppError> {
-    // Load gitignore if requested
-    let gitignore = if use_gitignore {
-        load_gitignore(root_dir)?.and_then(|builder| builder.build().ok())
-    } else {
-        None
-    };
-
-    // Parse include_file_types into a set of extensions
-    let include_all = include_file_types == "*";
-    let included_extensions: HashSet<String> = if !include_all {
-        include_file_types
-            .split(',')
-            .map(|ext| ext.trim().to_lowercase())
-            .filter(|ext| !ext.is_empty())
-            .collect()
-    } else {
-        HashSet::new() // Empty set when including all file types
-    };
-
-    // Convert exclude_file_types to a set for efficient lookups
-    let excluded_extensions: HashSet<String> = exclude_file_types
-        .split(',')
-        .map(|ext| ext.trim().to_lowercase())
-        .filter(|ext| !ext.is_empty())
-        .collect();
+/// Hands out one shared allocation per distinct file name during a walk
+#[derive(Default)]
+struct NameInterner {
+    names: HashSet<Arc<str>>,
+}
 
-    // Helper function to check if a file should be included based on its extension
-    let should_include_file_for_ui = |path: &Path| -> bool {
-        if path.is_dir() {
-            // For directories, we generally include them if they pass gitignore/default ignore checks.
-            // The `is_excluded_by_config` flag will handle the visual cue for excluded dirs.
-            return true;
-        }
-        if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
-            let ext = format!(".{}", extension.to_lowercase());
-            if include_all {
-                !excluded_extensions.contains(&ext)
-            } else {
-                included_extensions.contains(&ext)
-            }
-        } else {
-            include_all
+impl NameInterner {
+    fn intern(&mut self, name: &str) -> Arc<str> {
+        if let Some(existing) = self.names.get(name) {
+            return existing.clone();
         }
+        let name: Arc<str> = name.into();
+        self.names.insert(name.clone());
+        name
+    }
+}
+
+/// Convert directory to a hierarchical tree of DirectoryItem structs for the UI
+/// `is_cancelled` is polled once per directory; the walk stops with `AppError::Cancelled`.
+/// `files_scanned` counts the files seen so far, for reporting progress while it runs.
+/// With `threads` > 1 the top-level directories are walked in parallel.
+pub fn get_directory_tree(
+    root_dir: &Path,
+    config: &Config,
+    excluded_dirs_config: &HashSet<String>,
+    skip_worktree: &SkipWorktree,
+    is_cancelled: &(dyn Fn() -> bool + Sync),
+    files_scanned: &AtomicUsize,
+    threads: usize,
+) -> Result<DirectoryItem, AppError> {
+    let filter = PathFilter::new(root_dir, config, skip_worktree)?;
+    let walk = TreeWalk {
+        root_dir,
+        filter: &filter,
+        excluded_dirs_config,
+        is_cancelled,
+        files_scanned,
     };
 
     let root_name = root_dir
         .file_name()
         .and_then(|name| name.to_str())
-        .unwrap_or(".")
-        .to_string();
+        .unwrap_or(".");
 
+    let mut names = NameInterner::default();
     let mut root_item = DirectoryItem {
-        name: root_name.clone(), // Clone root_name
-        path: root_dir.to_string_lossy().to_string(),
+        name: names.intern(root_name),
+        path: root_dir.to_string_lossy().into(),
         is_dir: true,
         is_selected: false,
         children: Vec::new(),
         is_excluded_by_config: excluded_dirs_config.contains(&"".to_string()), // Root cannot be excluded this way
+        is_default_ignored: false,
+        symlink_target: None,
+        children_pending: false,
     };
 
-    // Build the tree recursively
-    fn build_dir_tree(
-        dir_path: &Path,
-        root_dir: &Path,
-        parent_item: &mut DirectoryItem,
-        gitignore: &Option<ignore::gitignore::Gitignore>,
-        show_ignored: bool,
-        show_default_ignored: bool,
-        should_include_file_for_ui: &dyn Fn(&Path) -> bool,
-        excluded_dirs_config: &HashSet<String>,
-    ) -> Result<(), AppError> {
-        let entries = fs::read_dir(dir_path)?
-            .filter_map(Result::ok)
-            .map(|e| e.path())
-            .collect::<Vec<PathBuf>>();
+    if threads <= 1 {
+        walk.build_dir_tree(root_dir, &mut root_item, &mut names)?;
+    } else {
+        walk.build_root_parallel(&mut root_item, threads)?;
+    }
 
-        // Filter and sort entries
-        let mut items: Vec<PathBuf> = entries
-            .into_iter()
-            .filter(|entry| {
-                // For get_directory_tree (UI): DO NOT filter out dirs from excluded_dirs_config here.
-                // We mark them with is_excluded_by_config instead.
-                // Original logic for gitignore and default ignores still applies.
-                if entry.is_dir() {
-                    if show_ignored {
-                        return true;
-                    }
-                    let should_ignore_git = if let Some(gi) = gitignore {
-                        gi.matched(entry, true).is_ignore()
-                    } else {
-                        false
-                    };
-                    let is_default = is_default_ignored(entry);
-                    if show_default_ignored {
-                        return !should_ignore_git;
-                    }
-                    return !should_ignore_git && !is_default;
-                }
+    Ok(root_item)
+}
 
-                // For files, apply gitignore and extension filters
-                let passes_gitignore = if show_ignored {
-                    true
-                } else {
-                    let should_ignore_git = if let Some(gi) = gitignore {
-                        gi.matched(entry, false).is_ignore()
-                    } else {
-                        false
-                    };
-                    let is_default = is_default_ignored(entry);
-                    if show_default_ignored {
-                        !should_ignore_git
-                    } else {
-                        !should_ignore_git && !is_default
-                    }
-                };
-                passes_gitignore && should_include_file_for_ui(entry)
-            })
+/// List a single level of the UI tree: `dir_path` with its direct children.
+/// Child directories come back with `children_pending` set and are loaded when expanded,
+/// so opening a huge directory never waits on a full walk.
+pub fn get_directory_level(
+    root_dir: &Path,
+    dir_path: &Path,
+    config: &Config,
+    excluded_dirs_config: &HashSet<String>,
+    skip_worktree: &SkipWorktree,
+) -> Result<DirectoryItem, AppError> {
+    let filter = PathFilter::new(root_dir, config, skip_worktree)?;
+    let files_scanned = AtomicUsize::new(0);
+    let walk = TreeWalk {
+        root_dir,
+        filter: &filter,
+        excluded_dirs_config,
+        is_cancelled: &|| false,
+        files_scanned: &files_scanned,
+    };
+
+    let mut names = NameInterner::default();
+    let mut dir_item = walk.new_item(dir_path, &mut names);
+    if !dir_item.is_dir {
+        return Ok(dir_item);
+    }
+    for item_path in walk.list_entries(dir_path)? {
+        let mut item = walk.new_item(&item_path, &mut names);
+        item.children_pending = item.is_dir;
+        dir_item.children.push(item);
+    }
+    Ok(dir_item)
+}
+
+/// `dir_path` with everything below it loaded, for selecting a folder whose children haven't
+/// been expanded yet. Unlike a full walk, empty folders are kept.
+pub fn get_directory_subtree(
+    root_dir: &Path,
+    dir_path: &Path,
+    config: &Config,
+    excluded_dirs_config: &HashSet<String>,
+    skip_worktree: &SkipWorktree,
+) -> Result<DirectoryItem, AppError> {
+    let filter = PathFilter::new(root_dir, config, skip_worktree)?;
+    let files_scanned = AtomicUsize::new(0);
+    let walk = TreeWalk {
+        root_dir,
+        filter: &filter,
+        excluded_dirs_config,
+        is_cancelled: &|| false,
+        files_scanned: &files_scanned,
+    };
+
+    let mut names = NameInterner::default();
+    let mut dir_item = walk.new_item(dir_path, &mut names);
+    if dir_item.is_dir {
+        walk.build_dir_tree(dir_path, &mut dir_item, &mut names)?;
+    }
+    Ok(dir_item)
+}
+
+/// Whether every directory between `root_dir` and `path` is shown in the tree, so an entry
+/// at `path` could appear in it
+pub fn within_visible_dirs(root_dir: &Path, path: &Path, filter: &PathFilter) -> bool {
+    let Some(parent) = path.parent() else {
+        return false;
+    };
+    let Ok(relative) = parent.strip_prefix(root_dir) else {
+        return false;
+    };
+    let mut dir = root_dir.to_path_buf();
+    relative.components().all(|component| {
+        dir.push(component);
+        filter.unfollowed_symlink(&dir).is_none() && filter.should_show(&dir, true)
+    })
+}
+
+/// The tree item for a single entry under `root_dir`, with its subtree if it's a directory.
+/// None when the entry wouldn't appear in a full walk, e.g. it's filtered out or an empty folder.
+pub fn tree_entry(
+    root_dir: &Path,
+    path: &Path,
+    filter: &PathFilter,
+    excluded_dirs_config: &HashSet<String>,
+) -> Option<DirectoryItem> {
+    if !within_visible_dirs(root_dir, path, filter) || !filter.should_show(path, path.is_dir()) {
+        return None;
+    }
+    let walk = TreeWalk {
+        root_dir,
+        filter,
+        excluded_dirs_config,
+        is_cancelled: &|| false,
+        files_scanned: &AtomicUsize::new(0),
+    };
+    let mut names = NameInterner::default();
+    let mut item = walk.new_item(path, &mut names);
+    if item.is_dir {
+        walk.build_dir_tree(path, &mut item, &mut names).ok()?;
+        if !TreeWalk::keep_dir(&item) {
+            return None;
+        }
+    }
+    Some(item)
+}
+
+/// Shared state for building the UI tree
+struct TreeWalk<'a> {
+    root_dir: &'a Path,
+    filter: &'a PathFilter,
+    excluded_dirs_config: &'a HashSet<String>,
+    is_cancelled: &'a (dyn Fn() -> bool + Sync),
+    files_scanned: &'a AtomicUsize,
+}
+
+impl TreeWalk<'_> {
+    /// The visible entries of a directory, directories first
+    fn list_entries(&self, dir_path: &Path) -> Result<Vec<PathBuf>, AppError> {
+        if (self.is_cancelled)() {
+            return Err(AppError::Cancelled);
+        }
+
+        // For get_directory_tree (UI): DO NOT filter out dirs from excluded_dirs_config here.
+        // We mark them with is_excluded_by_config instead.
+        let mut items: Vec<PathBuf> = fs::read_dir(dir_path)?
+            .filter_map(Result::ok)
+            .map(|e| e.path())
+            .filter(|entry| self.filter.should_show(entry, entry.is_dir()))
             .collect();
 
         // Sort items (directories first, then alphabetically)
-        items.sort_by(|a, b| {
-            let a_is_dir = a.is_dir();
-            let b_is_dir = b.is_dir();
-            if a_is_dir && !b_is_dir {
-                std::cmp::Ordering::Less
-            } else if !a_is_dir && b_is_dir {
-                std::cmp::Ordering::Greater
-            } else {
-                a.file_name()
-                    .unwrap_or_default()
-                    .cmp(b.file_name().unwrap_or_default())
-            }
-        });
+        sort_entries(&mut items);
+        Ok(items)
+    }
 
-        for item_path in items {
-            let is_directory = item_path.is_dir();
-            let item_name = item_path
-                .file_name()
-                .and_then(|name| name.to_str())
-                .unwrap_or("")
-                .to_string();
+    fn new_item(&self, item_path: &Path, names: &mut NameInterner) -> DirectoryItem {
+        let symlink_target = self.filter.unfollowed_symlink(item_path);
+        let is_directory = symlink_target.is_none() && item_path.is_dir();
+        if !is_directory {
+            self.files_scanned.fetch_add(1, AtomicOrdering::Relaxed);
+        }
+        let item_name = item_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
 
-            let relative_path_str = item_path
-                .strip_prefix(root_dir) // Calculate relative path
-                .unwrap_or(&item_path) // Fallback to absolute if strip fails (should not happen for children)
-                .to_string_lossy()
-                .into_owned();
-
-            let mut item = DirectoryItem {
-                name: item_name,
-                path: item_path.to_string_lossy().to_string(),
-                is_dir: is_directory,
-                is_selected: false,
-                children: Vec::new(),
-                is_excluded_by_config: if is_directory {
-                    excluded_dirs_config.contains(&relative_path_str)
-                } else {
-                    false
-                },
-            };
+        let relative_path_str = item_path
+            .strip_prefix(self.root_dir) // Calculate relative path
+            .unwrap_or(item_path) // Fallback to absolute if strip fails (should not happen for children)
+            .to_string_lossy()
+            .into_owned();
 
-            if is_directory {
-                build_dir_tree(
-                    &item_path,
-                    root_dir,
-                    &mut item,
-                    gitignore,
-                    show_ignored,
-                    show_default_ignored,
-                    should_include_file_for_ui,
-                    excluded_dirs_config,
-                )?;
-                // Add directory to parent's children if it's not empty OR it's explicitly excluded by config (so user can see and potentially un-exclude it)
-                if !item.children.is_empty() || item.is_excluded_by_config {
+        DirectoryItem {
+            name: names.intern(item_name),
+            path: item_path.to_string_lossy().into(),
+            is_dir: is_directory,
+            is_selected: false,
+            children: Vec::new(),
+            is_excluded_by_config: if is_directory {
+                self.excluded_dirs_config.contains(&relative_path_str)
+            } else {
+                false
+            },
+            is_default_ignored: self.filter.is_default_ignored(item_path),
+            symlink_target,
+            children_pending: false,
+        }
+    }
+
+    /// Whether a finished directory belongs in its parent: it has visible children, or it's
+    /// explicitly excluded by config (so the user can see and potentially un-exclude it)
+    fn keep_dir(item: &DirectoryItem) -> bool {
+        !item.children.is_empty() || item.is_excluded_by_config
+    }
+
+    fn build_dir_tree(
+        &self,
+        dir_path: &Path,
+        parent_item: &mut DirectoryItem,
+        names: &mut NameInterner,
+    ) -> Result<(), AppError> {
+        for item_path in self.list_entries(dir_path)? {
+            let mut item = self.new_item(&item_path, names);
+            if item.is_dir {
+                self.build_dir_tree(&item_path, &mut item, names)?;
+                if Self::keep_dir(&item) {
                     parent_item.children.push(item);
                 }
             } else {
                 parent_item.children.push(item);
             }
         }
+        parent_item.children.shrink_to_fit();
         Ok(())
     }
 
-    build_dir_tree(
-        root_dir,
-        root_dir,
-        &mut root_item,
-        &gitignore,
-        show_ignored,
-        show_default_ignored,
-        &should_include_file_for_ui,
-        excluded_dirs_config,
-    )?;
+    /// Build the root's children with its subdirectories spread over `threads` workers
+    fn build_root_parallel(
+        &self,
+        root_item: &mut DirectoryItem,
+        threads: usize,
+    ) -> Result<(), AppError> {
+        let entries = self.list_entries(self.root_dir)?;
+        let next = AtomicUsize::new(0);
 
-    Ok(root_item)
+        // Each worker takes the next unclaimed entry until none are left
+        let mut built: Vec<(usize, Result<DirectoryItem, AppError>)> =
+            std::thread::scope(|scope| {
+                let workers: Vec<_> = (0..threads.min(entries.len()).max(1))
+                    .map(|_| {
+                        scope.spawn(|| {
+                            let mut names = NameInterner::default();
+                            let mut results = Vec::new();
+                            loop {
+                                let index = next.fetch_add(1, AtomicOrdering::Relaxed);
+                                let Some(item_path) = entries.get(index) else {
+                                    break;
+                                };
+                                let mut item = self.new_item(item_path, &mut names);
+                                let result = if item.is_dir {
+                                    self.build_dir_tree(item_path, &mut item, &mut names)
+                                        .map(|_| item)
+                                } else {
+                                    Ok(item)
+                                };
+                                results.push((index, result));
+                            }
+                            results
+                        })
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|worker| worker.join().unwrap_or_default())
+                    .collect()
+            });
+
+        // Put the entries back in sorted order
+        built.sort_by_key(|(index, _)| *index);
+        for (_, result) in built {
+            let item = result?;
+            if !item.is_dir || Self::keep_dir(&item) {
+                root_item.children.push(item);
+            }
+        }
+        root_item.children.shrink_to_fit();
+        Ok(())
+    }
+}
+
+/// Convert a nested tree into the compact `FlatTree` form
+pub fn flatten_tree(root: &DirectoryItem) -> FlatTree {
+    let mut flat = FlatTree {
+        root_path: root.path.clone(),
+        separator: std::path::MAIN_SEPARATOR.to_string(),
+        prefixes: Vec::new(),
+        names: Vec::new(),
+        parents: Vec::new(),
+        prefix: Vec::new(),
+        flags: Vec::new(),
+        symlink_targets: BTreeMap::new(),
+    };
+
+    fn node_flags(item: &DirectoryItem) -> u8 {
+        let mut flags = 0;
+        if item.is_dir {
+            flags |= FLAT_NODE_DIR;
+        }
+        if item.is_excluded_by_config {
+            flags |= FLAT_NODE_EXCLUDED_BY_CONFIG;
+        }
+        if item.children_pending {
+            flags |= FLAT_NODE_CHILDREN_PENDING;
+        }
+        if item.is_default_ignored {
+            flags |= FLAT_NODE_DEFAULT_IGNORED;
+        }
+        flags
+    }
+
+    fn push_children(item: &DirectoryItem, index: i32, flat: &mut FlatTree) {
+        if item.children.is_empty() {
+            return;
+        }
+        // Every child shares the same parent directory, so intern its path once
+        let prefix_index = flat.prefixes.len() as u32;
+        flat.prefixes.push(item.path.clone());
+
+        for child in &item.children {
+            let child_index = flat.names.len() as i32;
+            flat.names.push(child.name.clone());
+            flat.parents.push(index);
+            flat.prefix.push(prefix_index);
+            flat.flags.push(node_flags(child));
+            if let Some(target) = &child.symlink_target {
+                flat.symlink_targets
+                    .insert(child_index as u32, target.clone());
+            }
+            push_children(child, child_index, flat);
+        }
+    }
+
+    // The root's path is stored separately, so its prefix slot is unused
+    flat.names.push(root.name.clone());
+    flat.parents.push(-1);
+    flat.prefix.push(0);
+    flat.flags.push(node_flags(root));
+    push_children(root, 0, &mut flat);
+
+    flat
+}
+
+/// Known project manifests and the filters that suit each stack:
+/// (manifest file, project type, file types to include, build/dependency dirs to exclude)
+const PROJECT_MARKERS: [(&str, &str, &[&str], &[&str]); 4] = [
+    ("Cargo.toml", "rust", &[".rs", ".toml", ".md"], &["target"]),
+    (
+        "package.json",
+        "node",
+        &[
+            ".js", ".jsx", ".ts", ".tsx", ".json", ".css", ".html", ".md",
+        ],
+        &["node_modules", "dist", "build", ".next", "coverage"],
+    ),
+    (
+        "pyproject.toml",
+        "python",
+        &[".py", ".toml", ".cfg", ".ini", ".md"],
+        &[
+            ".venv",
+            "venv",
+            "build",
+            "dist",
+            ".mypy_cache",
+            ".pytest_cache",
+        ],
+    ),
+    ("go.mod", "go", &[".go", ".mod", ".md"], &["vendor", "bin"]),
+];
+
+/// Files or directories whose presence marks a directory as a project root
+const PROJECT_ROOT_MARKERS: [&str; 16] = [
+    ".git",
+    ".hg",
+    ".svn",
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "setup.py",
+    "requirements.txt",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "build.gradle.kts",
+    "Gemfile",
+    "composer.json",
+    "CMakeLists.txt",
+    "Makefile",
+];
+
+/// Size ranges used by quick_size_histogram, as (label, lower bound in bytes)
+const SIZE_BUCKETS: &[(&str, u64)] = &[
+    ("< 1 KB", 0),
+    ("1-10 KB", 1024),
+    ("10-100 KB", 10 * 1024),
+    ("100 KB-1 MB", 100 * 1024),
+    ("1-10 MB", 1024 * 1024),
+    ("> 10 MB", 10 * 1024 * 1024),
+];
+
+/// Directories deeper than this aren't listed separately in the histogram's largest_dirs
+const HISTOGRAM_DIR_DEPTH: usize = 3;
+const HISTOGRAM_TOP_DIRS: usize = 10;
+
+/// Bucket file sizes and find the heaviest directories, looking at no more than `file_limit`
+/// files. Gitignore rules aren't applied, so big ignored folders like node_modules show up
+/// as exclude candidates.
+pub fn quick_size_histogram(
+    root_dir: &Path,
+    default_ignores: &[String],
+    file_limit: usize,
+) -> SizeHistogram {
+    let mut buckets: Vec<SizeBucket> = SIZE_BUCKETS
+        .iter()
+        .enumerate()
+        .map(|(i, (label, min_bytes))| SizeBucket {
+            label: label.to_string(),
+            min_bytes: *min_bytes,
+            max_bytes: SIZE_BUCKETS.get(i + 1).map(|(_, next)| *next),
+            files: 0,
+            bytes: 0,
+        })
+        .collect();
+    let mut dirs: HashMap<PathBuf, (usize, u64)> = HashMap::new();
+    let mut files_sampled = 0;
+    let mut bytes_sampled = 0;
+    let mut complete = true;
+
+    let entries = WalkDir::new(root_dir)
+        .into_iter()
+        .filter_entry(|entry: &DirEntry| {
+            !matches_ignored_name(root_dir, entry.path(), default_ignores)
+        })
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file());
+    for entry in entries {
+        if files_sampled >= file_limit {
+            complete = false;
+            break;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let size = metadata.len();
+        files_sampled += 1;
+        bytes_sampled += size;
+
+        if let Some(bucket) = buckets.iter_mut().rev().find(|b| size >= b.min_bytes) {
+            bucket.files += 1;
+            bucket.bytes += size;
+        }
+
+        let rel_dir = entry
+            .path()
+            .parent()
+            .and_then(|parent| parent.strip_prefix(root_dir).ok())
+            .unwrap_or(Path::new(""));
+        let mut ancestor = PathBuf::new();
+        for component in rel_dir.components().take(HISTOGRAM_DIR_DEPTH) {
+            ancestor.push(component);
+            let totals = dirs.entry(ancestor.clone()).or_default();
+            totals.0 += 1;
+            totals.1 += size;
+        }
+    }
+
+    let mut largest_dirs: Vec<DirSize> = dirs
+        .into_iter()
+        .map(|(path, (files, bytes))| DirSize {
+            path: path.to_string_lossy().replace('\\', "/"),
+            files,
+            bytes,
+        })
+        .collect();
+    largest_dirs.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(a.path.cmp(&b.path)));
+    largest_dirs.truncate(HISTOGRAM_TOP_DIRS);
+
+    SizeHistogram {
+        buckets,
+        largest_dirs,
+        files_sampled,
+        bytes_sampled,
+        complete,
+    }
+}
+
+/// Folder names cloud sync clients create, matched against path components
+const SYNCED_FOLDER_NAMES: &[(&str, &str)] = &[
+    ("Dropbox", "Dropbox"),
+    ("OneDrive", "OneDrive"),
+    ("Google Drive", "Google Drive"),
+    ("iCloud Drive", "iCloud Drive"),
+    ("Mobile Documents", "iCloud Drive"),
+];
+
+/// Name of the cloud sync service whose folder contains `path`, if any.
+/// Uses the roots OneDrive and Dropbox advertise, then falls back to well-known folder names.
+pub fn synced_folder_service(path: &Path) -> Option<&'static str> {
+    for var in ["OneDrive", "OneDriveCommercial", "OneDriveConsumer"] {
+        if let Some(root) = std::env::var_os(var).filter(|root| !root.is_empty()) {
+            if path.starts_with(root) {
+                return Some("OneDrive");
+            }
+        }
+    }
+
+    // Dropbox lists its folders in ~/.dropbox/info.json, e.g. {"personal": {"path": "..."}}
+    let dropbox_roots = dirs::home_dir()
+        .and_then(|home| fs::read_to_string(home.join(".dropbox").join("info.json")).ok())
+        .and_then(|info| serde_json::from_str::<serde_json::Value>(&info).ok());
+    if let Some(accounts) = dropbox_roots.as_ref().and_then(|info| info.as_object()) {
+        let in_dropbox = accounts
+            .values()
+            .filter_map(|account| account.get("path")?.as_str())
+            .any(|root| path.starts_with(root));
+        if in_dropbox {
+            return Some("Dropbox");
+        }
+    }
+
+    path.components().find_map(|component| {
+        let name = component.as_os_str().to_string_lossy();
+        SYNCED_FOLDER_NAMES
+            .iter()
+            // OneDrive for Business folders are named "OneDrive - Company"
+            .find(|(folder, _)| name == *folder || name.starts_with(&format!("{} - ", folder)))
+            .map(|(_, service)| *service)
+    })
+}
+
+/// Why scanning `path` is almost never what the user meant: "home" for their home directory,
+/// "root" for `/` or a drive root such as `C:\`
+pub fn dangerous_root_kind(path: &Path) -> Option<&'static str> {
+    if path.as_os_str().is_empty() {
+        return None;
+    }
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if path.parent().is_none() {
+        return Some("root");
+    }
+    let home = dirs::home_dir().map(|home| home.canonicalize().unwrap_or(home));
+    home.is_some_and(|home| home == path).then_some("home")
+}
+
+/// Check whether a directory looks like the root of a project (VCS root or project manifest)
+pub fn is_project_root(dir: &Path) -> bool {
+    PROJECT_ROOT_MARKERS
+        .iter()
+        .any(|marker| dir.join(marker).exists())
+}
+
+/// Check whether a directory holds more than `limit` entries, counting no further than that.
+/// Directories named in `default_ignores` and, when `use_git_ignore` is set, gitignored paths
+/// don't count.
+pub fn exceeds_entry_count(
+    root_dir: &Path,
+    use_git_ignore: bool,
+    default_ignores: &[String],
+    limit: usize,
+) -> bool {
+    count_entries(root_dir, use_git_ignore, default_ignores, limit + 1) > limit
+}
+
+/// Count a directory's entries, stopping at `limit`. Skips the same paths as `exceeds_entry_count`.
+pub fn count_entries(
+    root_dir: &Path,
+    use_git_ignore: bool,
+    default_ignores: &[String],
+    limit: usize,
+) -> usize {
+    let root = root_dir.to_path_buf();
+    let names = default_ignores.to_vec();
+    WalkBuilder::new(root_dir)
+        .hidden(false)
+        .git_ignore(use_git_ignore)
+        .filter_entry(move |entry| !matches_ignored_name(&root, entry.path(), &names))
+        .build()
+        .filter_map(Result::ok)
+        .skip(1)
+        .take(limit)
+        .count()
+}
+
+/// Detect the project's stack from manifests in its root and suggest matching filters
+/// Only exclude dirs that actually exist are suggested. Returns None if no stack is recognised.
+pub fn detect_project_filters(root_dir: &Path) -> Option<FilterSuggestion> {
+    let mut project_types = Vec::new();
+    let mut include_types: Vec<&str> = Vec::new();
+    let mut exclude_dirs: Vec<String> = Vec::new();
+
+    for (manifest, project_type, extensions, dirs) in PROJECT_MARKERS.iter() {
+        if !root_dir.join(manifest).is_file() {
+            continue;
+        }
+        project_types.push(project_type.to_string());
+        for ext in extensions.iter() {
+            if !include_types.contains(ext) {
+                include_types.push(ext);
+            }
+        }
+        for dir in dirs.iter() {
+            if root_dir.join(dir).is_dir() && !exclude_dirs.iter().any(|d| d == dir) {
+                exclude_dirs.push(dir.to_string());
+            }
+        }
+    }
+
+    if project_types.is_empty() {
+        return None;
+    }
+
+    Some(FilterSuggestion {
+        project_types,
+        include_file_types: include_types.join(","),
+        exclude_dirs,
+    })
+}
+
+/// Files recognised by their full name: (file name, score, reason)
+const KEY_FILE_NAMES: &[(&str, u32, &str)] = &[
+    ("README.md", 90, "Project overview"),
+    ("README", 90, "Project overview"),
+    ("Cargo.toml", 80, "Dependency manifest"),
+    ("package.json", 80, "Dependency manifest"),
+    ("pyproject.toml", 80, "Dependency manifest"),
+    ("go.mod", 80, "Dependency manifest"),
+    ("pom.xml", 80, "Dependency manifest"),
+    ("build.gradle", 80, "Dependency manifest"),
+    ("build.gradle.kts", 80, "Dependency manifest"),
+    ("Gemfile", 80, "Dependency manifest"),
+    ("composer.json", 80, "Dependency manifest"),
+    ("requirements.txt", 70, "Dependency manifest"),
+    ("setup.py", 70, "Dependency manifest"),
+    ("settings.py", 70, "Application settings"),
+    ("tauri.conf.json", 65, "Tauri configuration"),
+    ("Dockerfile", 60, "Container setup"),
+    ("docker-compose.yml", 60, "Container setup"),
+    ("docker-compose.yaml", 60, "Container setup"),
+    ("compose.yaml", 60, "Container setup"),
+    ("manage.py", 55, "Django entry point"),
+    ("vite.config.ts", 55, "Build configuration"),
+    ("vite.config.js", 55, "Build configuration"),
+    ("next.config.js", 55, "Build configuration"),
+    ("next.config.mjs", 55, "Build configuration"),
+    ("webpack.config.js", 55, "Build configuration"),
+    ("Makefile", 50, "Build tasks"),
+    ("justfile", 50, "Build tasks"),
+    (".gitlab-ci.yml", 50, "CI configuration"),
+    ("tsconfig.json", 45, "TypeScript configuration"),
+];
+
+/// Source files recognised by their name without extension: (stem, score, reason)
+const KEY_FILE_STEMS: &[(&str, u32, &str)] = &[
+    ("main", 85, "Entry point"),
+    ("lib", 75, "Library root"),
+    ("index", 70, "Entry point"),
+    ("app", 70, "Application entry point"),
+    ("server", 65, "Server entry point"),
+    ("routes", 65, "Routing"),
+    ("router", 65, "Routing"),
+    ("urls", 65, "Routing"),
+];
+
+/// Extensions that make a matching stem count as source code
+const KEY_FILE_SOURCE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "jsx", "mjs", "ts", "tsx", "go", "java", "kt", "rb", "php", "cs", "swift",
+    "c", "cpp", "svelte", "vue",
+];
+
+/// Directories whose files are CI definitions
+const CI_DIRS: &[&str] = &[".github/workflows", ".circleci"];
+
+/// How deep suggest_key_files looks below the project root
+const KEY_FILE_MAX_DEPTH: usize = 4;
+
+/// Score a file as a likely starting point for understanding the project
+fn score_key_file(rel_path: &Path) -> Option<(u32, &'static str)> {
+    let file_name = rel_path.file_name()?.to_str()?;
+    let parent = rel_path
+        .parent()
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default();
+
+    if CI_DIRS.contains(&parent.as_str()) {
+        return Some((50, "CI configuration"));
+    }
+
+    if let Some(&(_, score, reason)) = KEY_FILE_NAMES
+        .iter()
+        .find(|(name, _, _)| *name == file_name)
+    {
+        return Some((score, reason));
+    }
+
+    let extension = rel_path.extension()?.to_str()?.to_lowercase();
+    if !KEY_FILE_SOURCE_EXTENSIONS.contains(&extension.as_str()) {
+        return None;
+    }
+    let stem = rel_path.file_stem()?.to_str()?.to_lowercase();
+    KEY_FILE_STEMS
+        .iter()
+        .find(|(key_stem, _, _)| *key_stem == stem)
+        .map(|&(_, score, reason)| (score, reason))
+}
+
+/// Rank entry points, manifests, routers and config files in the project, best first.
+/// Files nested deeper score lower, so the top-level manifest beats one in a fixture dir.
+pub fn suggest_key_files(
+    root_dir: &Path,
+    config: &Config,
+    excluded_dirs: &HashSet<String>,
+    limit: usize,
+) -> Result<Vec<KeyFileSuggestion>, AppError> {
+    // Only the ignore rules apply here; Dockerfiles and CI files rarely match the type filters
+    let filter = PathFilter::new(root_dir, config, &load_skip_worktree(root_dir))?;
+    let mut suggestions = Vec::new();
+
+    let mut pending = vec![(root_dir.to_path_buf(), 0usize)];
+    while let Some((dir, depth)) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.filter_map(Result::ok).map(|e| e.path()) {
+            let is_dir = path.is_dir();
+            if !filter.passes_ignores(&path, is_dir) {
+                continue;
+            }
+            let rel_path = path.strip_prefix(root_dir).unwrap_or(&path);
+
+            if is_dir {
+                if depth < KEY_FILE_MAX_DEPTH
+                    && !excluded_dirs.contains(&rel_path.to_string_lossy().to_string())
+                {
+                    pending.push((path.clone(), depth + 1));
+                }
+                continue;
+            }
+
+            if let Some((score, reason)) = score_key_file(rel_path) {
+                let depth_penalty = 10 * rel_path.components().count().saturating_sub(1) as u32;
+                suggestions.push(KeyFileSuggestion {
+                    path: path.to_string_lossy().to_string(),
+                    reason: reason.to_string(),
+                    score: score.saturating_sub(depth_penalty).max(1),
+                });
+            }
+        }
+    }
+
+    suggestions.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.path.cmp(&b.path)));
+    suggestions.truncate(limit);
+    Ok(suggestions)
+}
+
+/// CI definitions recognised by their full name
+const CI_FILE_NAMES: &[&str] = &[
+    ".gitlab-ci.yml",
+    ".travis.yml",
+    "Jenkinsfile",
+    "azure-pipelines.yml",
+    "bitbucket-pipelines.yml",
+    "cloudbuild.yaml",
+    "cloudbuild.yml",
+];
+
+/// Deployment manifests recognised by their full name
+const DEPLOY_FILE_NAMES: &[&str] = &[
+    "Chart.yaml",
+    "values.yaml",
+    "kustomization.yaml",
+    "kustomization.yml",
+    "skaffold.yaml",
+    "Procfile",
+    "fly.toml",
+    "app.yaml",
+    "render.yaml",
+    "vercel.json",
+    "netlify.toml",
+    "serverless.yml",
+    "serverless.yaml",
+];
+
+/// Directories whose YAML files are deployment manifests, e.g. Kubernetes resources
+const DEPLOY_DIR_NAMES: &[&str] = &[
+    "k8s",
+    "kubernetes",
+    "helm",
+    "charts",
+    "deploy",
+    "deployment",
+    "deployments",
+    "manifests",
+];
+
+/// Which infra category a file belongs to, if any
+fn infra_category(rel_path: &Path) -> Option<&'static str> {
+    let file_name = rel_path.file_name()?.to_str()?;
+    let lower_name = file_name.to_lowercase();
+    let extension = rel_path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let parent = rel_path
+        .parent()
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default();
+
+    if CI_DIRS
+        .iter()
+        .any(|dir| parent == *dir || parent.ends_with(&format!("/{}", dir)))
+        || CI_FILE_NAMES.contains(&file_name)
+    {
+        return Some("ci");
+    }
+    if lower_name == "dockerfile"
+        || lower_name.starts_with("dockerfile.")
+        || extension == "dockerfile"
+        || lower_name == ".dockerignore"
+        || lower_name.starts_with("docker-compose")
+        || lower_name.starts_with("compose.")
+    {
+        return Some("container");
+    }
+    if matches!(extension.as_str(), "tf" | "tfvars" | "hcl") {
+        return Some("terraform");
+    }
+    let in_deploy_dir = rel_path.parent().is_some_and(|parent| {
+        parent.components().any(|component| {
+            DEPLOY_DIR_NAMES.contains(&component.as_os_str().to_string_lossy().as_ref())
+        })
+    });
+    if DEPLOY_FILE_NAMES.contains(&file_name)
+        || (in_deploy_dir && matches!(extension.as_str(), "yaml" | "yml"))
+    {
+        return Some("deployment");
+    }
+    None
+}
+
+/// Gather the CI workflows, Dockerfiles, Terraform and deployment manifests anywhere in the
+/// project, sorted by category then path. Like suggest_key_files, only the ignore rules apply.
+pub fn infra_context_files(
+    root_dir: &Path,
+    config: &Config,
+    excluded_dirs: &HashSet<String>,
+) -> Result<Vec<InfraFile>, AppError> {
+    let filter = PathFilter::new(root_dir, config, &load_skip_worktree(root_dir))?;
+    let mut files = Vec::new();
+
+    let mut pending = vec![root_dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.filter_map(Result::ok).map(|e| e.path()) {
+            let is_dir = path.is_dir();
+            if !filter.passes_ignores(&path, is_dir) {
+                continue;
+            }
+            let rel_path = path.strip_prefix(root_dir).unwrap_or(&path);
+
+            if is_dir {
+                if !excluded_dirs.contains(&rel_path.to_string_lossy().to_string()) {
+                    pending.push(path.clone());
+                }
+                continue;
+            }
+
+            if let Some(category) = infra_category(rel_path) {
+                files.push(InfraFile {
+                    path: path.to_string_lossy().to_string(),
+                    category: category.to_string(),
+                });
+            }
+        }
+    }
+
+    const CATEGORY_ORDER: [&str; 4] = ["ci", "container", "terraform", "deployment"];
+    let rank = |category: &str| CATEGORY_ORDER.iter().position(|c| *c == category);
+    files.sort_by(|a, b| {
+        rank(&a.category)
+            .cmp(&rank(&b.category))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    Ok(files)
+}
+
+/// Private keys that `ssh-keygen` creates under these names by default
+const SSH_KEY_NAMES: &[&str] = &[
+    "id_rsa",
+    "id_dsa",
+    "id_ecdsa",
+    "id_ed25519",
+    "id_ecdsa_sk",
+    "id_ed25519_sk",
+];
+
+/// Settings in an .npmrc that hold registry credentials
+const NPMRC_SECRET_KEYS: &[&str] = &["_authToken", "_auth", "_password"];
+
+/// Folders in the home directory that hold credentials, whatever the files in them are called
+const CREDENTIAL_DIRS: &[(&str, &str)] = &[
+    (".ssh", "file in ~/.ssh"),
+    (".gnupg", "file in ~/.gnupg"),
+    (".aws", "AWS credentials"),
+    (".kube", "Kubernetes credentials"),
+    (".docker", "Docker credentials"),
+    (".config/gcloud", "Google Cloud credentials"),
+];
+
+/// Why a file must never be included in an output, or None if it's fine.
+/// This list isn't configurable: no setting or explicit selection lets these files through.
+/// A symlink is judged by what it points to as well, so a harmless-looking link to
+/// ~/.ssh/id_rsa is refused like the key itself.
+pub fn secret_file_reason(path: &Path) -> Option<&'static str> {
+    if let Some(reason) = secret_name_reason(path) {
+        return Some(reason);
+    }
+    let target = fs::canonicalize(path).ok()?;
+    if let Some(home) = dirs::home_dir().and_then(|home| fs::canonicalize(home).ok()) {
+        let in_credential_dir = CREDENTIAL_DIRS
+            .iter()
+            .find(|(dir, _)| target.starts_with(home.join(dir)));
+        if let Some((_, reason)) = in_credential_dir {
+            return Some(reason);
+        }
+    }
+    if target != path {
+        return secret_name_reason(&target);
+    }
+    None
+}
+
+/// secret_file_reason judged from the path alone
+fn secret_name_reason(path: &Path) -> Option<&'static str> {
+    let file_name = path.file_name()?.to_str()?;
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let parent_name = path
+        .parent()
+        .and_then(Path::file_name)
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    if SSH_KEY_NAMES.contains(&file_name) {
+        return Some("SSH private key");
+    }
+    match extension.as_str() {
+        "pem" | "key" => return Some("private key or certificate"),
+        "p12" | "pfx" => return Some("PKCS#12 key store"),
+        "gpg" | "pgp" => return Some("GPG key or encrypted file"),
+        _ => {}
+    }
+    if parent_name == "private-keys-v1.d" || file_name.starts_with("secring.") {
+        return Some("GPG private keyring");
+    }
+    if file_name == "kubeconfig" || (parent_name == ".kube" && file_name == "config") {
+        return Some("Kubernetes credentials");
+    }
+    if file_name == ".npmrc" {
+        let content = fs::read_to_string(path).unwrap_or_default();
+        let has_token = content.lines().any(|line| {
+            line.split_once('=').is_some_and(|(key, _)| {
+                NPMRC_SECRET_KEYS
+                    .iter()
+                    .any(|secret| key.trim().ends_with(secret))
+            })
+        });
+        if has_token {
+            return Some(".npmrc with registry credentials");
+        }
+    }
+    None
+}
+
+/// Count the tokens in a text with the named BPE encoding ("o200k_base" or "cl100k_base").
+/// Any other encoding falls back to the approximation of 4 characters per token.
+pub fn estimate_tokens(text: &str, encoding: &str) -> usize {
+    let bpe = match encoding {
+        "o200k_base" => tiktoken_rs::o200k_base_singleton(),
+        "cl100k_base" => tiktoken_rs::cl100k_base_singleton(),
+        _ => return text.len() / 4,
+    };
+    bpe.encode_ordinary(text).len()
 }
 
-/// Estimate the number of tokens in a text
-/// Uses a simple approximation of 4 characters per token
-pub fn estimate_tokens(text: &str) -> usize {
-    text.len() / 4
+/// Estimate the tokens in a file from its size, without reading it
+pub fn estimate_tokens_from_size(bytes: u64) -> usize {
+    bytes as usize / 4
 }
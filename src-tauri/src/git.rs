@@ -0,0 +1,413 @@
+use crate::cli::shell_quote;
+use crate::models::AppError;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const HOOK_BEGIN_MARKER: &str = "# >>> gptree context snapshot >>>";
+const HOOK_END_MARKER: &str = "# <<< gptree context snapshot <<<";
+
+/// A git command to run inside `dir`
+fn git_command(dir: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new("git");
+    command.arg("-C").arg(dir).args(args);
+
+    // Avoid flashing a console window for every git call on Windows
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    command
+}
+
+/// Run a git command inside `dir` and return its trimmed stdout if it succeeded
+fn run_git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = git_command(dir, args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if stdout.is_empty() {
+        None
+    } else {
+        Some(stdout)
+    }
+}
+
+/// Get the short hash of the last commit that touched each of `files`, given relative to
+/// `root_dir` with `/` separators. One `git log` walk covers them all and stops as soon as
+/// every file has been seen. Untracked files are left out, and the map is empty if git is
+/// unavailable or the directory is not a repo.
+pub fn last_commit_hashes(root_dir: &Path, files: &HashSet<String>) -> HashMap<String, String> {
+    let mut hashes = HashMap::new();
+    if files.is_empty() {
+        return hashes;
+    }
+    let args = [
+        "-c",
+        "core.quotePath=false",
+        "log",
+        "--format=%x01%h",
+        "--name-only",
+        "--relative",
+        "--",
+        ".",
+    ];
+    let Ok(mut child) = git_command(root_dir, &args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    else {
+        return hashes;
+    };
+
+    if let Some(stdout) = child.stdout.take() {
+        // Commits come newest first, each a marked hash line followed by the files it changed
+        let mut commit = String::new();
+        for line in BufReader::new(stdout).split(b'\n') {
+            let Ok(line) = line else {
+                break;
+            };
+            let line = String::from_utf8_lossy(&line);
+            if let Some(hash) = line.strip_prefix('\u{1}') {
+                commit = hash.to_string();
+            } else if files.contains(line.as_ref()) && !hashes.contains_key(line.as_ref()) {
+                hashes.insert(line.to_string(), commit.clone());
+                if hashes.len() == files.len() {
+                    break;
+                }
+            }
+        }
+    }
+    // The rest of the history isn't needed once every file is found
+    let _ = child.kill();
+    let _ = child.wait();
+    hashes
+}
+
+/// Find the root of the git repository containing `dir` by walking up to the nearest `.git`
+/// `.git` may be a directory or, for worktrees and submodules, a file. No git binary needed.
+pub fn find_repo_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|ancestor| ancestor.join(".git").exists())
+        .map(Path::to_path_buf)
+}
+
+/// Resolve the git directory of the repository rooted at `repo_root`.
+/// In linked worktrees and submodules `.git` is a file holding `gitdir: <path>`.
+pub fn resolve_git_dir(repo_root: &Path) -> Option<PathBuf> {
+    let dot_git = repo_root.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+
+    let content = fs::read_to_string(&dot_git).ok()?;
+    let git_dir = PathBuf::from(content.trim().strip_prefix("gitdir:")?.trim());
+    Some(if git_dir.is_absolute() {
+        git_dir
+    } else {
+        repo_root.join(git_dir)
+    })
+}
+
+/// The directory shared by all worktrees of a repository (holding `info/exclude`, config, ...).
+/// For the main worktree this is the git directory itself.
+pub fn common_git_dir(git_dir: &Path) -> PathBuf {
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common) => {
+            let common = PathBuf::from(common.trim());
+            if common.is_absolute() {
+                common
+            } else {
+                git_dir.join(common)
+            }
+        }
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+/// Absolute paths of tracked files marked skip-worktree, which is how sparse checkouts
+/// (and `git update-index --skip-worktree`) leave files out of the working tree
+pub fn skip_worktree_paths(repo_root: &Path) -> HashSet<PathBuf> {
+    let Some(listing) = run_git(repo_root, &["ls-files", "-v", "-z"]) else {
+        return HashSet::new();
+    };
+
+    listing
+        .split('\0')
+        .filter_map(|entry| {
+            let (tag, path) = entry.split_once(' ')?;
+            // "S" is skip-worktree, "s" is skip-worktree plus assume-unchanged
+            matches!(tag, "S" | "s").then(|| repo_root.join(path))
+        })
+        .collect()
+}
+
+/// Get the full hash of the current HEAD commit
+pub fn head_commit(root_dir: &Path) -> Option<String> {
+    run_git(root_dir, &["rev-parse", "HEAD"])
+}
+
+/// Get the name of the currently checked out branch ("HEAD" when detached)
+pub fn current_branch(root_dir: &Path) -> Option<String> {
+    run_git(root_dir, &["rev-parse", "--abbrev-ref", "HEAD"])
+}
+
+/// Files changed in the repository containing `root_dir`, as absolute paths. `base` is "head"
+/// for everything uncommitted (staged, unstaged and untracked), "staged" for what's in the
+/// index, or a branch or other ref: everything since its merge base with HEAD, uncommitted
+/// changes included. Only files under `root_dir` that still exist are returned.
+pub fn changed_files(root_dir: &Path, base: &str) -> Result<Vec<PathBuf>, AppError> {
+    if find_repo_root(root_dir).is_none() {
+        return Err(AppError::Git(format!(
+            "Not a git repository: {}",
+            root_dir.to_string_lossy()
+        )));
+    }
+
+    let mut listings = Vec::new();
+    match base {
+        "head" => {
+            listings.push(run_git(root_dir, &[&DIFF_NAMES[..], &["HEAD"]].concat()));
+            listings.push(untracked_files(root_dir));
+        }
+        "staged" => listings.push(run_git(
+            root_dir,
+            &[&DIFF_NAMES[..], &["--cached"]].concat(),
+        )),
+        // Anything else is a ref; one starting with "-" would be read as an option
+        reference if !reference.is_empty() && !reference.starts_with('-') => {
+            let merge_base = run_git(root_dir, &["merge-base", reference, "HEAD"])
+                .ok_or_else(|| AppError::Git(format!("Unknown branch or ref: {}", reference)))?;
+            listings.push(run_git(
+                root_dir,
+                &[&DIFF_NAMES[..], &[&merge_base]].concat(),
+            ));
+            listings.push(untracked_files(root_dir));
+        }
+        other => {
+            return Err(AppError::Git(format!("Invalid base: {}", other)));
+        }
+    }
+
+    // With -z the names are NUL-separated, so unusual file names come through intact
+    let relative: BTreeSet<String> = listings
+        .iter()
+        .flatten()
+        .flat_map(|listing| listing.split('\0'))
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+    Ok(relative
+        .into_iter()
+        .map(|name| root_dir.join(name))
+        .filter(|path| path.is_file())
+        .collect())
+}
+
+/// Shallow-clone `url` into `dest`, checking out `reference` (a branch or tag) or the
+/// default branch. Credential prompts are disabled so a private repo fails instead of hanging.
+pub fn shallow_clone(url: &str, reference: Option<&str>, dest: &Path) -> Result<(), AppError> {
+    // Either one starting with "-" would be read as an option
+    if url.is_empty() || url.starts_with('-') {
+        return Err(AppError::Git(format!("Invalid repository URL: {}", url)));
+    }
+    if let Some(reference) = reference {
+        if reference.is_empty() || reference.starts_with('-') {
+            return Err(AppError::Git(format!(
+                "Invalid branch or tag: {}",
+                reference
+            )));
+        }
+    }
+    let parent = dest
+        .parent()
+        .ok_or_else(|| AppError::Git(format!("Invalid clone target: {:?}", dest)))?;
+    let dest_arg = dest.to_string_lossy();
+
+    let mut args = vec!["clone", "--depth", "1", "--single-branch", "--no-tags"];
+    if let Some(reference) = reference {
+        args.extend(["--branch", reference]);
+    }
+    args.extend(["--", url, &dest_arg]);
+
+    let output = git_command(parent, &args)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .map_err(|e| AppError::Git(format!("Could not run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(AppError::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// The commit `reference` (a branch or tag, or the default branch when None) points at in the
+/// remote repository at `url`, asked without cloning. None when the remote can't be reached.
+pub fn remote_commit(dir: &Path, url: &str, reference: Option<&str>) -> Option<String> {
+    if url.starts_with('-') || reference.is_some_and(|reference| reference.starts_with('-')) {
+        return None;
+    }
+    let output = git_command(dir, &["ls-remote", "--", url, reference.unwrap_or("HEAD")])
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let refs: Vec<(&str, &str)> = stdout
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .collect();
+    // ls-remote matches on the end of the name, so "main" also finds "refs/heads/feature/main".
+    // An annotated tag is listed twice; the peeled "^{}" entry is the commit it points at.
+    let wanted = match reference {
+        Some(reference) => vec![
+            format!("refs/heads/{}", reference),
+            format!("refs/tags/{}^{{}}", reference),
+            format!("refs/tags/{}", reference),
+        ],
+        None => vec!["HEAD".to_string()],
+    };
+    wanted.iter().find_map(|wanted| {
+        refs.iter()
+            .find(|(_, name)| name == wanted)
+            .map(|(commit, _)| commit.to_string())
+    })
+}
+
+/// Unified diff of `path` between `base` and `target`, or between `base` and the working tree
+/// when there's no target. Files git doesn't track yet diff as entirely new.
+/// Returns None when the file hasn't changed.
+pub fn file_diff(
+    root_dir: &Path,
+    path: &Path,
+    base: &str,
+    target: Option<&str>,
+) -> Result<Option<String>, AppError> {
+    let refs: Vec<&str> = std::iter::once(base).chain(target).collect();
+    if let Some(option) = refs.iter().find(|reference| reference.starts_with('-')) {
+        return Err(AppError::Git(format!("Invalid ref: {}", option)));
+    }
+    // Relative, so an untracked file's diff header names it the way tracked files' do
+    let path = path
+        .strip_prefix(root_dir)
+        .unwrap_or(path)
+        .to_string_lossy();
+    let tracked = run_git(root_dir, &["ls-files", "--", &path]).is_some();
+
+    let mut args = vec!["diff", "--no-color", "--no-ext-diff"];
+    if target.is_none() && !tracked {
+        args.extend(["--no-index", "--", "/dev/null", &path]);
+    } else {
+        args.extend(refs);
+        args.extend(["--", &path]);
+    }
+
+    let output = git_command(root_dir, &args).output()?;
+    // `git diff --no-index` exits with 1 when the files differ
+    let differs = output.status.code() == Some(1) && !tracked;
+    if !output.status.success() && !differs {
+        return Err(AppError::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    let diff = String::from_utf8_lossy(&output.stdout).to_string();
+    Ok((!diff.trim().is_empty()).then_some(diff))
+}
+
+/// `git diff` arguments listing changed names relative to the directory git runs in,
+/// leaving out deleted files
+const DIFF_NAMES: [&str; 5] = ["diff", "--name-only", "-z", "--relative", "--diff-filter=d"];
+
+/// Untracked files that aren't ignored, relative to `root_dir`
+fn untracked_files(root_dir: &Path) -> Option<String> {
+    run_git(
+        root_dir,
+        &["ls-files", "--others", "--exclude-standard", "-z"],
+    )
+}
+
+/// Resolve the hooks directory for the repository containing `root_dir`
+/// Uses `--git-path` so `core.hooksPath` and linked worktrees are respected
+pub fn hooks_dir(root_dir: &Path) -> Option<PathBuf> {
+    let hooks = PathBuf::from(run_git(root_dir, &["rev-parse", "--git-path", "hooks"])?);
+    if hooks.is_absolute() {
+        Some(hooks)
+    } else {
+        Some(root_dir.join(hooks))
+    }
+}
+
+/// Install (or refresh) a post-commit hook that regenerates the project's context file at
+/// `output_path` after every commit, by running `executable` (this app) in headless mode.
+/// Existing hook content is preserved. Returns the path of the hook file.
+pub fn install_post_commit_hook(
+    root_dir: &Path,
+    executable: &Path,
+    output_path: &Path,
+) -> Result<PathBuf, AppError> {
+    let hooks_dir = hooks_dir(root_dir).ok_or_else(|| {
+        AppError::Git(format!(
+            "Not a git repository: {}",
+            root_dir.to_string_lossy()
+        ))
+    })?;
+    fs::create_dir_all(&hooks_dir)?;
+
+    let hook_path = hooks_dir.join("post-commit");
+    let existing = if hook_path.exists() {
+        fs::read_to_string(&hook_path)?
+    } else {
+        "#!/bin/sh\n".to_string()
+    };
+
+    // Drop any block from a previous install so re-installing doesn't duplicate it
+    let mut lines = Vec::new();
+    let mut in_block = false;
+    for line in existing.lines() {
+        if line.trim() == HOOK_BEGIN_MARKER {
+            in_block = true;
+        } else if line.trim() == HOOK_END_MARKER {
+            in_block = false;
+        } else if !in_block {
+            lines.push(line.to_string());
+        }
+    }
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+
+    lines.push(String::new());
+    lines.push(HOOK_BEGIN_MARKER.to_string());
+    lines.push(
+        "# Regenerates the GPTree context file from the previously selected files".to_string(),
+    );
+    let executable = shell_quote(&executable.to_string_lossy());
+    lines.push(format!("if [ -x {} ]; then", executable));
+    lines.push(format!(
+        "  {} --headless --dir {} --select-previous --out {} >/dev/null 2>&1 || true",
+        executable,
+        shell_quote(&root_dir.to_string_lossy()),
+        shell_quote(&output_path.to_string_lossy())
+    ));
+    lines.push("fi".to_string());
+    lines.push(HOOK_END_MARKER.to_string());
+
+    fs::write(&hook_path, lines.join("\n") + "\n")?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755))?;
+    }
+
+    Ok(hook_path)
+}
@@ -0,0 +1,163 @@
+use crate::config;
+use crate::fs::{read_file_list, resolve_project_path, save_to_file};
+use crate::models::{AppError, Config, GenerationOptions};
+use crate::processor;
+use crate::profile::Profiler;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage: gptree-gui --headless [--dir <path>] (--select-previous | --file-list <path>) [--out <path>] [--allow-command <command>]";
+
+/// What a headless run was asked to do
+struct HeadlessArgs {
+    dir: PathBuf,
+    select_previous: bool,
+    file_list: Option<PathBuf>,
+    out: Option<PathBuf>,
+    /// The externalCommand the project's `command` transform may run
+    allow_command: Option<String>,
+}
+
+impl HeadlessArgs {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut parsed = HeadlessArgs {
+            dir: PathBuf::from("."),
+            select_previous: false,
+            file_list: None,
+            out: None,
+            allow_command: None,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .map(PathBuf::from)
+                    .ok_or_else(|| format!("{} needs a value", arg))
+            };
+            match arg.as_str() {
+                "--headless" => {}
+                "--dir" => parsed.dir = value()?,
+                "--select-previous" => parsed.select_previous = true,
+                "--file-list" => parsed.file_list = Some(value()?),
+                "--out" => parsed.out = Some(value()?),
+                "--allow-command" => {
+                    parsed.allow_command = Some(value()?.to_string_lossy().to_string())
+                }
+                other => return Err(format!("Unknown argument: {}", other)),
+            }
+        }
+        if !parsed.select_previous && parsed.file_list.is_none() {
+            return Err("Nothing selected: pass --select-previous or --file-list".to_string());
+        }
+        Ok(parsed)
+    }
+}
+
+/// Generate an output from the command line without starting the webview, for CI and scripts.
+/// The output goes to `--out`, or to stdout. Returns the process exit code.
+pub fn run(args: &[String]) -> i32 {
+    attach_parent_console();
+    let args = match HeadlessArgs::parse(args) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            return 2;
+        }
+    };
+    match generate(&args) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("[GPTree] {}", e);
+            1
+        }
+    }
+}
+
+/// Release builds on Windows are GUI-subsystem apps without a console, so stdout and stderr
+/// go nowhere. Borrow the console of the shell that started us so output and errors show up.
+#[cfg(windows)]
+fn attach_parent_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    // Fails harmlessly when there's no parent console, e.g. when started from Explorer
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_parent_console() {}
+
+fn generate(args: &HeadlessArgs) -> Result<(), AppError> {
+    let root_dir = args
+        .dir
+        .canonicalize()
+        .map_err(|_| AppError::PathNotFound(args.dir.to_string_lossy().to_string()))?;
+    let mut config = load_config(&root_dir)?;
+    config::apply_env_overrides(&mut config);
+
+    let mut selected_files = Vec::new();
+    if args.select_previous {
+        for relative in &config.previous_files {
+            let path = resolve_project_path(&root_dir, relative);
+            if path.is_file() {
+                selected_files.push(path.to_string_lossy().to_string());
+            } else {
+                eprintln!("[GPTree] Skipping missing previous file: {}", relative);
+            }
+        }
+    }
+    if let Some(list_path) = &args.file_list {
+        let list = read_file_list(list_path, &root_dir)?;
+        for missing in &list.missing {
+            eprintln!("[GPTree] Skipping missing listed file: {}", missing);
+        }
+        selected_files.extend(list.files);
+    }
+    if selected_files.is_empty() {
+        return Err(AppError::Config("No files selected".to_string()));
+    }
+
+    let mut profiler = Profiler::new("generate");
+    let output = processor::combine_files_with_structure(
+        &root_dir,
+        &config,
+        &selected_files,
+        &config.exclude_dirs,
+        &GenerationOptions {
+            approved_commands: args.allow_command.iter().cloned().collect(),
+            ..GenerationOptions::default()
+        },
+        &|_, _| {},
+        &mut profiler,
+    )?;
+    for warning in &output.warnings {
+        eprintln!("[GPTree] Warning: {}", warning.message);
+    }
+
+    match &args.out {
+        Some(out) => {
+            save_to_file(out, &output.combined_content)?;
+            eprintln!(
+                "[GPTree] Wrote {} files ({} tokens) to {}",
+                output.file_details.len(),
+                output.token_estimate,
+                out.to_string_lossy()
+            );
+        }
+        None => println!("{}", output.combined_content),
+    }
+    Ok(())
+}
+
+/// The project's config if it has one, otherwise the global config, migrated the same way the
+/// GUI migrates them. Neither is written to.
+fn load_config(root_dir: &Path) -> Result<Config, AppError> {
+    if config::project_config_exists(root_dir) {
+        config::load_project_config_read_only(root_dir)
+    } else {
+        config::load_or_create_global_config(true)
+    }
+}
@@ -0,0 +1,381 @@
+use crate::config;
+use crate::fs::{
+    estimate_tokens_from_size, get_directory_tree, hash_content, hash_files, SkipWorktree,
+};
+use crate::models::{AppError, ChangeReport, Config, DirectoryHeatmap, DirectoryItem};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicUsize;
+use std::time::UNIX_EPOCH;
+use tauri::Manager;
+
+const INDEX_DIR: &str = "index";
+/// Name of the index file inside a project's `.gptree/cache/` folder
+const INDEX_FILE: &str = "index.json";
+
+/// A single file or directory recorded in a project index
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Path relative to the project root
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Last-modified time in nanoseconds since the Unix epoch, so quick same-size edits show
+    pub modified: u64,
+    /// SHA-256 of the content; None for directories and files that couldn't be read
+    #[serde(default)]
+    pub hash: Option<String>,
+}
+
+/// Persisted snapshot of a project's tree, used to answer `load_directory` without a walk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectIndex {
+    pub root: String,
+    /// Fingerprint of the settings that shaped the tree; a mismatch invalidates the index
+    pub filter_fingerprint: String,
+    pub updated_at: String,
+    pub entries: Vec<IndexEntry>,
+    pub tree: DirectoryItem,
+}
+
+/// Fingerprint the config fields that affect which entries appear in the tree
+pub fn filter_fingerprint(config: &Config, excluded_dirs: &HashSet<String>) -> String {
+    let mut excluded: Vec<&String> = excluded_dirs.iter().collect();
+    excluded.sort();
+    let key = serde_json::json!({
+        "useGitIgnore": config.use_git_ignore,
+        "includeFileTypes": config.include_file_types,
+        "excludeFileTypes": config.exclude_file_types,
+        "showIgnoredInTree": config.show_ignored_in_tree,
+        "showDefaultIgnoredInTree": config.show_default_ignored_in_tree,
+        "defaultIgnores": config.default_ignores,
+        "respectLinguistAttributes": config.respect_linguist_attributes,
+        "excludeDirs": excluded,
+    });
+    hash_content(&key.to_string())
+}
+
+/// Get the path of the index file for a project root: in its `.gptree/cache/` folder when it
+/// uses the folder layout, otherwise in the app data directory. Nothing is created.
+fn index_location(app_handle: &tauri::AppHandle, root_dir: &Path) -> Result<PathBuf, AppError> {
+    if let Some(cache_dir) = config::project_cache_dir(root_dir) {
+        return Ok(cache_dir.join(INDEX_FILE));
+    }
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Config(format!("Could not determine app data directory: {}", e)))?;
+    let key = hash_content(&root_dir.to_string_lossy());
+    Ok(data_dir.join(INDEX_DIR).join(format!("{}.json", key)))
+}
+
+/// Like index_location, creating the folder the index goes in, for writing it
+fn index_path(app_handle: &tauri::AppHandle, root_dir: &Path) -> Result<PathBuf, AppError> {
+    let path = index_location(app_handle, root_dir)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(path)
+}
+
+/// Whether a project has been indexed before. Only looks, so it never writes into the project.
+pub fn has_index(app_handle: &tauri::AppHandle, root_dir: &Path) -> bool {
+    index_location(app_handle, root_dir).is_ok_and(|path| path.is_file())
+}
+
+/// Load the stored index for a project, if there is a readable one
+pub fn load_index(app_handle: &tauri::AppHandle, root_dir: &Path) -> Option<ProjectIndex> {
+    let path = index_location(app_handle, root_dir).ok()?;
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Persist a project index
+pub fn save_index(app_handle: &tauri::AppHandle, index: &ProjectIndex) -> Result<(), AppError> {
+    let path = index_path(app_handle, Path::new(&index.root))?;
+    let content = serde_json::to_string(index).map_err(|e| AppError::Json(e.to_string()))?;
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Walk the project and build a fresh index
+pub fn build_index(
+    root_dir: &Path,
+    config: &Config,
+    excluded_dirs: &HashSet<String>,
+    skip_worktree: &SkipWorktree,
+    is_cancelled: &(dyn Fn() -> bool + Sync),
+    files_scanned: &AtomicUsize,
+    scan_threads: usize,
+) -> Result<ProjectIndex, AppError> {
+    let tree = get_directory_tree(
+        root_dir,
+        config,
+        excluded_dirs,
+        skip_worktree,
+        is_cancelled,
+        files_scanned,
+        scan_threads,
+    )?;
+
+    let mut entries = Vec::new();
+    collect_entries(&tree, root_dir, &mut entries);
+
+    Ok(ProjectIndex {
+        root: root_dir.to_string_lossy().to_string(),
+        filter_fingerprint: filter_fingerprint(config, excluded_dirs),
+        updated_at: chrono::Local::now().to_rfc3339(),
+        entries,
+        tree,
+    })
+}
+
+/// Size and last-modified time (nanoseconds since the Unix epoch) as recorded in index entries
+pub fn entry_stamp(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or(0);
+    Some((metadata.len(), modified))
+}
+
+/// Record every node below `item` as an index entry
+fn collect_entries(item: &DirectoryItem, root_dir: &Path, entries: &mut Vec<IndexEntry>) {
+    for child in &item.children {
+        let path = Path::new(&*child.path);
+        let relative = path
+            .strip_prefix(root_dir)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string();
+
+        let (size, modified) = entry_stamp(path).unwrap_or((0, 0));
+
+        entries.push(IndexEntry {
+            path: relative,
+            is_dir: child.is_dir,
+            size,
+            modified,
+            hash: None,
+        });
+
+        if child.is_dir {
+            collect_entries(child, root_dir, entries);
+        }
+    }
+}
+
+/// A file as detect_changes last saw it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileState {
+    pub size: u64,
+    /// Last-modified time in nanoseconds since the Unix epoch
+    pub modified: u64,
+    /// SHA-256 of the content; None when the file couldn't be read
+    pub hash: Option<String>,
+}
+
+/// The project's files as of the last detect_changes call. Kept apart from the project
+/// index, which every load rewrites, so only detect_changes moves it forward and edits
+/// made between two checks always show up in the next report.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChangeBaseline {
+    /// Fingerprint of the filters the file list was built with; a mismatch invalidates it
+    pub filter_fingerprint: String,
+    pub updated_at: String,
+    /// Keyed by path relative to the project root
+    pub files: BTreeMap<String, FileState>,
+}
+
+fn baseline_path(app_handle: &tauri::AppHandle, root_dir: &Path) -> Result<PathBuf, AppError> {
+    let index_file = index_path(app_handle, root_dir)?;
+    Ok(index_file.with_extension("baseline.json"))
+}
+
+/// Load the change-detection baseline for a project, if there is a readable one
+pub fn load_baseline(app_handle: &tauri::AppHandle, root_dir: &Path) -> Option<ChangeBaseline> {
+    let path = baseline_path(app_handle, root_dir).ok()?;
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// Persist a project's change-detection baseline
+pub fn save_baseline(
+    app_handle: &tauri::AppHandle,
+    root_dir: &Path,
+    baseline: &ChangeBaseline,
+) -> Result<(), AppError> {
+    let content = serde_json::to_string(baseline).map_err(|e| AppError::Json(e.to_string()))?;
+    fs::write(baseline_path(app_handle, root_dir)?, content)?;
+    Ok(())
+}
+
+/// Hash the files in a freshly built `index`, `concurrency` at a time. Files whose size and
+/// modified time match `previous` keep its hash instead of being read again.
+/// Returns the new baseline and how many files were hashed and reused.
+pub fn snapshot_files(
+    root_dir: &Path,
+    index: &ProjectIndex,
+    previous: Option<&ChangeBaseline>,
+    concurrency: usize,
+) -> (ChangeBaseline, usize, usize) {
+    let mut files = BTreeMap::new();
+    let mut pending = Vec::new();
+    for entry in index.entries.iter().filter(|entry| !entry.is_dir) {
+        let reused = previous
+            .and_then(|previous| previous.files.get(&entry.path))
+            .filter(|state| {
+                state.size == entry.size && state.modified == entry.modified && state.hash.is_some()
+            });
+        match reused {
+            Some(state) => {
+                files.insert(entry.path.clone(), state.clone());
+            }
+            None => pending.push(entry),
+        }
+    }
+    let reused = files.len();
+
+    let paths: Vec<PathBuf> = pending
+        .iter()
+        .map(|entry| root_dir.join(&entry.path))
+        .collect();
+    let hashes = hash_files(&paths, concurrency);
+    for (entry, hash) in pending.iter().zip(hashes) {
+        files.insert(
+            entry.path.clone(),
+            FileState {
+                size: entry.size,
+                modified: entry.modified,
+                hash,
+            },
+        );
+    }
+
+    let baseline = ChangeBaseline {
+        filter_fingerprint: index.filter_fingerprint.clone(),
+        updated_at: chrono::Local::now().to_rfc3339(),
+        files,
+    };
+    (baseline, pending.len(), reused)
+}
+
+/// Fill in the content hashes of a freshly built `index`, `concurrency` files at a time.
+/// Files whose size and modified time match `previous` keep its hash instead of being read again.
+pub fn hash_entries(
+    root_dir: &Path,
+    index: &mut ProjectIndex,
+    previous: Option<&ProjectIndex>,
+    concurrency: usize,
+) {
+    let previous = previous.map(|previous| ChangeBaseline {
+        files: previous
+            .entries
+            .iter()
+            .filter(|entry| !entry.is_dir)
+            .map(|entry| {
+                let state = FileState {
+                    size: entry.size,
+                    modified: entry.modified,
+                    hash: entry.hash.clone(),
+                };
+                (entry.path.clone(), state)
+            })
+            .collect(),
+        ..ChangeBaseline::default()
+    });
+    let (mut snapshot, _, _) = snapshot_files(root_dir, index, previous.as_ref(), concurrency);
+    for entry in index.entries.iter_mut().filter(|entry| !entry.is_dir) {
+        entry.hash = snapshot
+            .files
+            .remove(&entry.path)
+            .and_then(|state| state.hash);
+    }
+}
+
+/// Compare two baselines of the same project. A file counts as modified when its hash
+/// changed, or when either side has no hash and its size or modified time changed.
+pub fn diff_baselines(previous: &ChangeBaseline, current: &ChangeBaseline) -> ChangeReport {
+    let mut report = ChangeReport {
+        had_baseline: true,
+        ..Default::default()
+    };
+    for (path, state) in &current.files {
+        match previous.files.get(path) {
+            None => report.added.push(path.clone()),
+            Some(old) => {
+                let changed = match (&old.hash, &state.hash) {
+                    (Some(old_hash), Some(new_hash)) => old_hash != new_hash,
+                    _ => old.size != state.size || old.modified != state.modified,
+                };
+                if changed {
+                    report.modified.push(path.clone());
+                }
+            }
+        }
+    }
+    report.removed = previous
+        .files
+        .keys()
+        .filter(|path| !current.files.contains_key(*path))
+        .cloned()
+        .collect();
+    report
+}
+
+/// Total up file counts, sizes and estimated tokens per directory, down to `max_depth` levels
+pub fn directory_heatmap(index: &ProjectIndex, max_depth: usize) -> DirectoryHeatmap {
+    // Keyed by the directory's components, so parents sort before their children
+    let mut totals: BTreeMap<Vec<String>, (u32, u64)> = BTreeMap::new();
+    totals.insert(Vec::new(), (0, 0));
+
+    for entry in index.entries.iter().filter(|entry| !entry.is_dir) {
+        let components: Vec<String> = Path::new(&entry.path)
+            .parent()
+            .map(|parent| {
+                parent
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let depth = components.len().min(max_depth);
+        for end in 0..=depth {
+            let total = totals.entry(components[..end].to_vec()).or_insert((0, 0));
+            total.0 += 1;
+            total.1 += entry.size;
+        }
+    }
+
+    let mut heatmap = DirectoryHeatmap {
+        root_path: index.root.clone(),
+        names: Vec::with_capacity(totals.len()),
+        parents: Vec::with_capacity(totals.len()),
+        files: Vec::with_capacity(totals.len()),
+        bytes: Vec::with_capacity(totals.len()),
+        tokens: Vec::with_capacity(totals.len()),
+    };
+    let mut positions: HashMap<&[String], i32> = HashMap::new();
+    for (position, (components, (files, bytes))) in totals.iter().enumerate() {
+        let parent = match components.split_last() {
+            Some((_, parent)) => positions.get(parent).copied().unwrap_or(-1),
+            None => -1,
+        };
+        positions.insert(components.as_slice(), position as i32);
+        heatmap
+            .names
+            .push(components.last().cloned().unwrap_or_default());
+        heatmap.parents.push(parent);
+        heatmap.files.push(*files);
+        heatmap.bytes.push(*bytes);
+        heatmap
+            .tokens
+            .push(estimate_tokens_from_size(*bytes) as u64);
+    }
+    heatmap
+}
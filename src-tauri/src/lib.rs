@@ -1,18 +1,47 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 
 // Modules
+mod calibration;
+mod cli;
 mod config;
+mod deps;
+mod exclusions;
 mod fs;
+mod git;
+mod headless;
+mod index;
 mod models;
+mod power;
 mod processor;
+mod profile;
+mod stats;
+mod symbols;
+mod tasks;
+mod theme;
+mod transforms;
+mod watcher;
 
-use models::{AppError, Config, DirectoryItem, OutputContent};
+use models::{
+    ActivityEntry, AppError, BatchPreset, BatchResult, ChangeReport, CliCommand, Config,
+    ConfigFieldSchema, ConfigWithSources, DirectoryHeatmap, DirectoryItem, DirectoryLoadResult,
+    ExclusionSuggestion, FavoriteProject, FileListInput, FileWarning, GenerationOptions,
+    IgnoreReason, InfraFile, KeyFileSuggestion, OperationProfile, OutputContent, OutputHistoryItem,
+    ResourceLimits, SelectionDrift, SizeHistogram, StartupProbe, TokenCalibration, TreePayload,
+    DEFAULT_BATCH_FILE_TEMPLATE, DEFAULT_FILE_HEADER_TEMPLATE,
+};
+use profile::Profiler;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs as StdFs;
 use std::path::{Path, PathBuf};
-use tauri::Manager;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::menu::{Menu, MenuBuilder, MenuItem, MenuItemBuilder, SubmenuBuilder};
+use tauri::window::{ProgressBarState, ProgressBarStatus};
+use tauri::{Emitter, Manager};
 use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_notification::NotificationExt;
 use tauri_plugin_opener::OpenerExt;
 
 // Add ConfigMode enum
@@ -28,20 +57,148 @@ enum ConfigMode {
 struct SessionState {
     last_directory: Option<String>,
     last_config_mode: Option<String>,
+    // Most recently opened first, at most RECENT_DIRECTORIES_LIMIT
+    #[serde(default)]
+    recent_directories: Vec<RecentDirectory>,
+    // Projects already offered .gitignore entries after a save, so the offer is made once
+    #[serde(default)]
+    gitignore_offered: Vec<String>,
+}
+
+// A previously opened directory, for reopening it from the recent list
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct RecentDirectory {
+    path: String,
+    last_opened: String,
+    // Files in the tree when it was last loaded; unknown when the tree was loaded lazily
+    file_count: Option<usize>,
 }
 
 // Store the app state
 struct AppState {
     current_dir: std::sync::Mutex<PathBuf>,
     config_mode: std::sync::Mutex<ConfigMode>,
+    // Incremented by every load_directory call so older, superseded scans can bail out
+    load_generation: AtomicU64,
+    // Generation of the load_directory call cancel_scan last stopped
+    cancelled_scan: AtomicU64,
+    // Puts the config files back if they're deleted while the directory is open
+    config_watcher: std::sync::Mutex<Option<watcher::ConfigWatcher>>,
+    // Pushes entries created, deleted or renamed under the open directory to the frontend
+    tree_watcher: std::sync::Mutex<Option<watcher::TreeWatcher>>,
+    // Skip-worktree files of the directory last loaded, looked up once per load
+    skip_worktree: std::sync::Mutex<Option<(PathBuf, fs::SkipWorktree)>>,
+    // Project and excluded directories of the last generation that went over budget, to learn
+    // which directories get excluded to fix that
+    over_budget: std::sync::Mutex<Option<(PathBuf, HashSet<String>)>>,
+    // Regenerates the output when a selected file changes, while live mode is on
+    live_watcher: std::sync::Mutex<Option<watcher::LiveWatcher>>,
+    // Timing breakdown of the most recent scan or generation
+    last_profile: std::sync::Mutex<Option<OperationProfile>>,
+    // Recent generated outputs, newest first, for copy_history_item
+    output_history: std::sync::Mutex<VecDeque<(OutputHistoryItem, String)>>,
+    next_history_id: AtomicU64,
+    // Output files written since the app started; trash_output_file only deletes these and
+    // the configured output file
+    saved_outputs: std::sync::Mutex<HashSet<PathBuf>>,
+    // Directories loaded and generations run since the app started
+    session_log: std::sync::Mutex<Vec<ActivityEntry>>,
+    // Operations started with the start_* commands
+    tasks: tasks::TaskRegistry,
+    // Operation counts and durations since launch, for get_runtime_stats
+    stats: stats::StatsRegistry,
+    // Labels of windows currently showing a native file dialog
+    open_dialogs: std::sync::Mutex<HashSet<String>>,
+}
+
+// Held while a native dialog is open for a window; dropping it frees the window for the next one
+struct DialogGuard<'a> {
+    open_dialogs: &'a std::sync::Mutex<HashSet<String>>,
+    label: String,
+}
+
+impl Drop for DialogGuard<'_> {
+    fn drop(&mut self) {
+        self.open_dialogs.lock().unwrap().remove(&self.label);
+    }
+}
+
+// Claim a window for a native dialog, or None if it's already showing one
+fn begin_dialog<'a>(state: &'a AppState, window: &tauri::Window) -> Option<DialogGuard<'a>> {
+    let label = window.label().to_string();
+    if !state.open_dialogs.lock().unwrap().insert(label.clone()) {
+        return None;
+    }
+    Some(DialogGuard {
+        open_dialogs: &state.open_dialogs,
+        label,
+    })
+}
+
+fn dialog_busy<T>() -> CommandResult<T> {
+    CommandResult::error_with_code(
+        CODE_DIALOG_BUSY,
+        "A dialog is already open in this window".to_string(),
+    )
 }
 
+// Error codes the frontend can match on instead of parsing error messages
+const CODE_CONFIRM_REQUIRED: &str = "confirm_required";
+const CODE_SUPERSEDED: &str = "superseded";
+const CODE_PROJECT_MISSING: &str = "project_missing";
+const CODE_OUTPUT_TOO_LARGE: &str = "output_too_large";
+const CODE_SAFE_MODE_LIMIT: &str = "safe_mode_limit";
+const CODE_CANCELLED: &str = "cancelled";
+const CODE_SECRET_FILE_BLOCKED: &str = "secret_file_blocked";
+const CODE_SAVED_TO_PROJECT_CONFIG: &str = "saved_to_project_config";
+const CODE_DIALOG_BUSY: &str = "dialog_busy";
+const CODE_SCAN_CANCELLED: &str = "scan_cancelled";
+const CODE_DANGEROUS_ROOT: &str = "dangerous_root";
+const CODE_COMMAND_NOT_APPROVED: &str = "command_not_approved";
+const CODE_NOT_AN_OUTPUT: &str = "not_an_output";
+
+// How long load_directory waits for a newer call before starting its walk
+const LOAD_DEBOUNCE_MS: u64 = 75;
+
+// Least time between two "scan-progress" events
+const SCAN_PROGRESS_INTERVAL_MS: u64 = 200;
+
+// Entry count past which a first scan asks for confirmation, if enabled in the app settings
+const LARGE_SCAN_ENTRY_LIMIT: usize = 50_000;
+
+// How long probe_last_directory waits before reporting the remembered directory as timed out
+const STARTUP_PROBE_TIMEOUT_MS: u64 = 3000;
+
+// Files quick_size_histogram looks at before returning a partial picture
+const HISTOGRAM_FILE_LIMIT: usize = 100_000;
+
+// Most entries the session activity log keeps before dropping the oldest
+const SESSION_LOG_LIMIT: usize = 1000;
+
+// Directory levels get_directory_heatmap breaks down unless asked otherwise
+const DEFAULT_HEATMAP_DEPTH: usize = 6;
+
+// How many files suggest_key_files returns unless asked otherwise
+const DEFAULT_KEY_FILE_LIMIT: usize = 15;
+
+// Most directories the recent list keeps before dropping the oldest
+const RECENT_DIRECTORIES_LIMIT: usize = 10;
+
+// Folder in the app cache directory holding clones made by load_remote_repo
+const REMOTE_REPOS_DIR: &str = "remote_repos";
+
 // Command return types
 #[derive(Debug, Serialize, Deserialize)]
 struct CommandResult<T> {
     success: bool,
     data: Option<T>,
     error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    code: Option<String>,
+    // Structured information about the error, for codes that come with some
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    details: Option<serde_json::Value>,
 }
 
 impl<T> CommandResult<T> {
@@ -50,6 +207,8 @@ impl<T> CommandResult<T> {
             success: true,
             data: Some(data),
             error: None,
+            code: None,
+            details: None,
         }
     }
 
@@ -58,28 +217,125 @@ impl<T> CommandResult<T> {
             success: false,
             data: None,
             error: Some(error),
+            code: None,
+            details: None,
+        }
+    }
+
+    fn error_with_code(code: &str, error: String) -> Self {
+        Self {
+            code: Some(code.to_string()),
+            ..Self::error(error)
+        }
+    }
+
+    fn error_with_details(code: &str, error: String, details: impl Serialize) -> Self {
+        Self {
+            details: serde_json::to_value(details).ok(),
+            ..Self::error_with_code(code, error)
         }
     }
 }
 
+// How scans and generations should treat the rest of the machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+enum IoPriority {
+    Normal,
+    Low,
+    // Low priority only while running on battery
+    LowOnBattery,
+}
+
+// Upper bound for thread counts picked automatically
+const MAX_AUTO_THREADS: usize = 8;
+
+// Current layout of settings.json. Bump it and add a step to migrate_settings whenever a
+// field is renamed or changes type, so existing preferences carry over.
+const SETTINGS_VERSION: u32 = 1;
+
 // App Settings Struct
 #[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")] // Use camelCase for JSON compatibility
+#[serde(rename_all = "camelCase", default)] // Use camelCase for JSON compatibility
 struct AppSettings {
+    // Layout version of settings.json, see migrate_settings
+    settings_version: u32,
     default_to_local_config: bool,
     prompt_for_directory_on_startup: bool,
     enable_folder_checkboxes: bool,
     auto_show_output_preview: bool,
+    // Desktop notification when a generation or scan runs longer than the threshold
+    notify_on_long_operations: bool,
+    long_operation_threshold_secs: u64,
+    // Menu accelerators keyed by menu action id; an empty string means no shortcut
+    shortcuts: HashMap<String, String>,
+    // Threads used to walk directories and files read at once during generation (0 = automatic)
+    scan_threads: usize,
+    read_concurrency: usize,
+    // Low priority limits scans and reads to a single thread
+    io_priority: IoPriority,
+    // Defaults written into each new project config
+    default_output_file: String,
+    default_line_numbers: bool,
+    default_file_header_template: String,
+    copy_on_generate: bool,
+    // Open the saved output file in its default app after generating
+    open_output_after_save: bool,
+    // Ask before the first scan of a directory with more than LARGE_SCAN_ENTRY_LIMIT entries
+    confirm_before_large_scan: bool,
+    // load_directory returns only the top level; folders load with load_directory_children
+    // when expanded instead of walking the whole tree up front
+    lazy_tree_loading: bool,
+    // Keep the loaded tree in sync with files created, deleted or renamed on disk
+    // ("tree-changed" events) instead of waiting for a refresh
+    watch_tree: bool,
+    // Don't save outputs whose destination is inside a Dropbox/OneDrive/... folder; by default
+    // they are saved with a warning
+    block_output_in_synced_folders: bool,
+    // How many generated outputs the in-app clipboard history keeps (0 disables it)
+    clipboard_history_size: usize,
+    // copy_to_clipboard asks for confirmation above this many KB, since very large clipboard
+    // contents can hang some Linux clipboard managers (0 disables the check)
+    clipboard_size_limit_kb: usize,
+    // Treat ~/.gptreerc as read-only, for users who manage it in their dotfiles: migrations
+    // aren't saved back and edits in global mode are refused
+    protect_global_config: bool,
+    // Command used by open_in_editor, e.g. `code --goto {path}:{line}`; empty opens the
+    // file in its default app
+    external_editor: String,
 }
 
 // Default implementation for AppSettings
 impl Default for AppSettings {
     fn default() -> Self {
         Self {
+            settings_version: SETTINGS_VERSION,
             default_to_local_config: false, // Default: prefer global config
             prompt_for_directory_on_startup: false, // Default: prompt user if no last dir (changed to false)
             enable_folder_checkboxes: true,         // <-- Default to true
             auto_show_output_preview: true,         // Default for the new setting
+            notify_on_long_operations: true,
+            long_operation_threshold_secs: 10,
+            shortcuts: DEFAULT_SHORTCUTS
+                .iter()
+                .map(|(action, accelerator)| (action.to_string(), accelerator.to_string()))
+                .collect(),
+            scan_threads: 0,
+            read_concurrency: 0,
+            io_priority: IoPriority::Normal,
+            default_output_file: Config::default().output_file,
+            default_line_numbers: false,
+            default_file_header_template: DEFAULT_FILE_HEADER_TEMPLATE.to_string(),
+            copy_on_generate: false,
+            open_output_after_save: false,
+            confirm_before_large_scan: true,
+            lazy_tree_loading: false,
+            watch_tree: true,
+            external_editor: String::new(),
+            clipboard_history_size: 5,
+            clipboard_size_limit_kb: 8192,
+            block_output_in_synced_folders: false,
+            protect_global_config: false,
         }
     }
 }
@@ -95,11 +351,122 @@ fn get_settings_path(app_handle: &tauri::AppHandle) -> Result<PathBuf, AppError>
     Ok(config_dir.join("settings.json"))
 }
 
+// Config a new project starts from, with the app-wide defaults applied
+fn new_project_config(app_handle: &tauri::AppHandle) -> Config {
+    let settings = load_app_settings(app_handle);
+    Config {
+        output_file: settings.default_output_file,
+        line_numbers: settings.default_line_numbers,
+        file_header_template: settings.default_file_header_template,
+        copy_to_clipboard: settings.copy_on_generate,
+        ..Config::default()
+    }
+}
+
+// Load the global config, leaving the file untouched if the settings protect it
+fn load_global_config(app_handle: &tauri::AppHandle) -> Result<Config, AppError> {
+    config::load_or_create_global_config(load_app_settings(app_handle).protect_global_config)
+}
+
+// Names skipped by the quick scans taken before a directory is loaded: the project
+// config's when it has one, otherwise the global config's
+fn scan_default_ignores(dir: &Path, app_handle: &tauri::AppHandle) -> Vec<String> {
+    let config = if config::project_config_exists(dir) {
+        config::load_config(&config::project_config_path(dir))
+    } else {
+        load_global_config(app_handle)
+    };
+    config
+        .map(|config| config.default_ignores)
+        .unwrap_or_else(|_| Config::default().default_ignores)
+}
+
+// With the global config protected, an edit made in global mode goes to the project's
+// config instead, and the project switches to local mode so it takes effect.
+// Returns whether the edit was rerouted.
+fn route_protected_global_edit(state: &AppState, app_handle: &tauri::AppHandle) -> bool {
+    let mut config_mode = state.config_mode.lock().unwrap();
+    if *config_mode != ConfigMode::Global || !load_app_settings(app_handle).protect_global_config {
+        return false;
+    }
+    *config_mode = ConfigMode::LocalOverride;
+    match config::load_session_state(app_handle) {
+        Ok(mut session) => {
+            session.last_config_mode = Some("local".to_string());
+            if let Err(e) = config::save_session_state(app_handle, &session) {
+                eprintln!("[GPTree] Warning: Failed to save session state: {}", e);
+            }
+        }
+        Err(e) => eprintln!("[GPTree] Warning: Failed to load session state: {}", e),
+    }
+    true
+}
+
+// Skip-worktree files of `dir` from its last load, so expanding folders and other lookups
+// between loads don't shell out to git again
+fn project_skip_worktree(state: &AppState, dir: &Path) -> fs::SkipWorktree {
+    let mut cached = state.skip_worktree.lock().unwrap();
+    match cached.as_ref() {
+        Some((cached_dir, skip_worktree)) if cached_dir == dir => skip_worktree.clone(),
+        _ => {
+            let skip_worktree = fs::load_skip_worktree(dir);
+            *cached = Some((dir.to_path_buf(), skip_worktree.clone()));
+            skip_worktree
+        }
+    }
+}
+
+// Helper function to load the config for the active mode
+fn load_active_config(
+    current_dir: &Path,
+    config_mode: ConfigMode,
+    app_handle: &tauri::AppHandle,
+) -> Result<Config, AppError> {
+    let mut config = match config_mode {
+        ConfigMode::LocalOverride => {
+            let config = config::load_project_config(current_dir, &new_project_config(app_handle))?;
+            // A cloned repository's own config doesn't get to choose what runs over its files,
+            // where the output is written, or the text the output starts with
+            if is_remote_clone(app_handle, current_dir) {
+                let defaults = Config::default();
+                Config {
+                    transforms: defaults.transforms,
+                    external_command: defaults.external_command,
+                    output_file: defaults.output_file,
+                    output_file_locally: defaults.output_file_locally,
+                    save_output_file: defaults.save_output_file,
+                    tree_header: defaults.tree_header,
+                    contents_header: defaults.contents_header,
+                    file_header_template: defaults.file_header_template,
+                    ..config
+                }
+            } else {
+                config
+            }
+        }
+        ConfigMode::Global => load_global_config(app_handle)?,
+    };
+    config::apply_env_overrides(&mut config);
+    Ok(config)
+}
+
+// Whether `dir` is one of the clones load_remote_repo keeps in the app cache
+fn is_remote_clone(app_handle: &tauri::AppHandle, dir: &Path) -> bool {
+    remote_repos_dir(app_handle).is_ok_and(|repos_dir| dir.starts_with(repos_dir))
+}
+
 // Command to select a directory
 #[tauri::command]
-async fn select_directory(app_handle: tauri::AppHandle) -> Result<CommandResult<String>, String> {
+async fn select_directory(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<String>, String> {
     use tauri_plugin_dialog::DialogExt;
 
+    let Some(_dialog) = begin_dialog(&state, &window) else {
+        return Ok(dialog_busy());
+    };
     let file_path = app_handle.dialog().file().blocking_pick_folder();
     match file_path {
         Some(path) => {
@@ -119,54 +486,611 @@ async fn select_directory(app_handle: tauri::AppHandle) -> Result<CommandResult<
                     );
                 }
             }
+            if let Err(e) = refresh_app_menu(&app_handle) {
+                eprintln!("[GPTree] Failed to refresh application menu: {}", e);
+            }
             Ok(CommandResult::success(path_str))
         }
         None => Ok(CommandResult::error("No directory selected".to_string())),
     }
 }
 
+// Menu item ids; everything except quit is forwarded to the frontend as a "menu-action" event
+const MENU_OPEN_FOLDER: &str = "open_folder";
+const MENU_OPEN_RECENT_PREFIX: &str = "open_recent:";
+const MENU_GENERATE: &str = "generate";
+const MENU_COPY_OUTPUT: &str = "copy_output";
+const MENU_SETTINGS: &str = "settings";
+const MENU_DIAGNOSTICS: &str = "diagnostics";
+
+// Menu actions that can be given a keyboard shortcut, with their defaults
+const DEFAULT_SHORTCUTS: [(&str, &str); 5] = [
+    (MENU_OPEN_FOLDER, "CmdOrCtrl+O"),
+    (MENU_GENERATE, "CmdOrCtrl+G"),
+    (MENU_COPY_OUTPUT, "CmdOrCtrl+Shift+C"),
+    (MENU_SETTINGS, "CmdOrCtrl+,"),
+    (MENU_DIAGNOSTICS, ""),
+];
+
+// Event payload sent when a menu item is chosen
+#[derive(Debug, Clone, Serialize)]
+struct MenuActionPayload {
+    action: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<String>,
+}
+
+// Build a menu item for an action, with the user's shortcut for it if they have one
+fn action_menu_item(
+    app_handle: &tauri::AppHandle,
+    settings: &AppSettings,
+    action: &str,
+    text: &str,
+) -> tauri::Result<MenuItem<tauri::Wry>> {
+    let item = MenuItemBuilder::with_id(action, text);
+    let accelerator = settings
+        .shortcuts
+        .get(action)
+        .map(String::as_str)
+        .or_else(|| {
+            DEFAULT_SHORTCUTS
+                .iter()
+                .find(|(a, _)| *a == action)
+                .map(|(_, accelerator)| *accelerator)
+        });
+    match accelerator.filter(|s| !s.is_empty()) {
+        Some(accelerator) => item.accelerator(accelerator).build(app_handle),
+        None => item.build(app_handle),
+    }
+}
+
+// Build the native application menu
+fn build_app_menu(app_handle: &tauri::AppHandle) -> tauri::Result<Menu<tauri::Wry>> {
+    let settings = load_app_settings(app_handle);
+    let open_folder = action_menu_item(app_handle, &settings, MENU_OPEN_FOLDER, "Open Folder...")?;
+
+    // Sessions saved before the recent list existed only know the last directory
+    let session = config::load_session_state(app_handle).unwrap_or_default();
+    let mut recent_dirs: Vec<String> = session
+        .recent_directories
+        .into_iter()
+        .map(|recent| recent.path)
+        .collect();
+    if recent_dirs.is_empty() {
+        recent_dirs.extend(session.last_directory);
+    }
+    let mut open_recent = SubmenuBuilder::new(app_handle, "Open Recent");
+    if recent_dirs.is_empty() {
+        open_recent = open_recent.item(
+            &MenuItemBuilder::new("No Recent Folders")
+                .enabled(false)
+                .build(app_handle)?,
+        );
+    }
+    for dir in &recent_dirs {
+        open_recent = open_recent.item(
+            &MenuItemBuilder::with_id(format!("{}{}", MENU_OPEN_RECENT_PREFIX, dir), dir)
+                .build(app_handle)?,
+        );
+    }
+    let open_recent = open_recent.build()?;
+
+    let generate = action_menu_item(app_handle, &settings, MENU_GENERATE, "Generate")?;
+    let copy_output = action_menu_item(app_handle, &settings, MENU_COPY_OUTPUT, "Copy Output")?;
+    let diagnostics = action_menu_item(app_handle, &settings, MENU_DIAGNOSTICS, "Diagnostics")?;
+    let settings = action_menu_item(app_handle, &settings, MENU_SETTINGS, "Settings...")?;
+
+    // macOS keeps Settings and Quit in the application menu
+    #[cfg(target_os = "macos")]
+    let app_menu = Some(
+        SubmenuBuilder::new(app_handle, "GPTree")
+            .about(None)
+            .separator()
+            .item(&settings)
+            .separator()
+            .services()
+            .separator()
+            .hide()
+            .hide_others()
+            .show_all()
+            .separator()
+            .quit()
+            .build()?,
+    );
+    #[cfg(not(target_os = "macos"))]
+    let app_menu: Option<tauri::menu::Submenu<tauri::Wry>> = None;
+
+    let mut file_menu = SubmenuBuilder::new(app_handle, "File")
+        .item(&open_folder)
+        .item(&open_recent)
+        .separator()
+        .item(&generate)
+        .item(&copy_output);
+    if app_menu.is_none() {
+        file_menu = file_menu.separator().item(&settings).separator().quit();
+    }
+    let file_menu = file_menu.build()?;
+
+    // Without an Edit menu the standard clipboard shortcuts stop working in text fields on macOS
+    let edit_menu = SubmenuBuilder::new(app_handle, "Edit")
+        .undo()
+        .redo()
+        .separator()
+        .cut()
+        .copy()
+        .paste()
+        .select_all()
+        .build()?;
+
+    let help_menu = SubmenuBuilder::new(app_handle, "Help")
+        .item(&diagnostics)
+        .build()?;
+
+    let mut menu = MenuBuilder::new(app_handle);
+    if let Some(app_menu) = &app_menu {
+        menu = menu.item(app_menu);
+    }
+    menu.items(&[&file_menu, &edit_menu, &help_menu]).build()
+}
+
+// Rebuild the menu, e.g. after the recent folder or a shortcut changed
+fn refresh_app_menu(app_handle: &tauri::AppHandle) -> tauri::Result<()> {
+    let menu = build_app_menu(app_handle)?;
+    app_handle.set_menu(menu)?;
+    Ok(())
+}
+
+// Forward a menu selection to the frontend
+fn handle_menu_event(app_handle: &tauri::AppHandle, id: &str) {
+    let payload = match id.strip_prefix(MENU_OPEN_RECENT_PREFIX) {
+        Some(path) => MenuActionPayload {
+            action: "open_recent".to_string(),
+            path: Some(path.to_string()),
+        },
+        None => MenuActionPayload {
+            action: id.to_string(),
+            path: None,
+        },
+    };
+    if let Err(e) = app_handle.emit("menu-action", payload) {
+        eprintln!("[GPTree] Failed to emit menu action: {}", e);
+    }
+}
+
+// Event payload sent when a background refresh finds the tree has changed on disk
+#[derive(Debug, Clone, Serialize)]
+struct TreeUpdatedPayload {
+    root: String,
+    tree: DirectoryItem,
+}
+
+// Event payload asking the frontend to load a directory, e.g. one passed on the command line
+#[derive(Debug, Clone, Serialize)]
+struct OpenDirectoryPayload {
+    path: String,
+}
+
+// Event payload sent every so often while load_directory walks a directory
+#[derive(Debug, Clone, Serialize)]
+struct ScanProgressPayload {
+    root: String,
+    files_scanned: usize,
+}
+
+// Event payload sent after live mode regenerates the output
+#[derive(Debug, Clone, Serialize)]
+struct LiveOutputPayload {
+    token_estimate: usize,
+    file_count: usize,
+    saved_path: Option<String>,
+    copied_to_clipboard: bool,
+    // Set instead of the rest when the regeneration failed
+    error: Option<String>,
+}
+
+// Encode a command result as MessagePack, sent to the frontend as raw bytes (an ArrayBuffer)
+// instead of a JSON string that has to be escaped and parsed
+fn pack_response<T: Serialize>(value: &T) -> Result<tauri::ipc::Response, String> {
+    rmp_serde::to_vec_named(value)
+        .map(tauri::ipc::Response::new)
+        .map_err(|e| format!("Failed to encode response: {}", e))
+}
+
 // Command to load a directory and its structure
 #[tauri::command]
 async fn load_directory(
     path: String,
+    flat: Option<bool>,
+    confirmed: Option<bool>,
+    allow_dangerous_root: Option<bool>,
     state: tauri::State<'_, AppState>,
-) -> Result<CommandResult<DirectoryItem>, String> {
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<DirectoryLoadResult>, String> {
+    load_directory_result(
+        path,
+        flat,
+        confirmed,
+        allow_dangerous_root,
+        &state,
+        app_handle,
+        None,
+    )
+    .await
+}
+
+// Same as load_directory, but the result is MessagePack-encoded
+#[tauri::command]
+async fn load_directory_packed(
+    path: String,
+    flat: Option<bool>,
+    confirmed: Option<bool>,
+    allow_dangerous_root: Option<bool>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<tauri::ipc::Response, String> {
+    let result = load_directory_result(
+        path,
+        flat,
+        confirmed,
+        allow_dangerous_root,
+        &state,
+        app_handle,
+        None,
+    )
+    .await?;
+    pack_response(&result)
+}
+
+async fn load_directory_result(
+    path: String,
+    flat: Option<bool>,
+    confirmed: Option<bool>,
+    allow_dangerous_root: Option<bool>,
+    state: &AppState,
+    app_handle: tauri::AppHandle,
+    cancel: Option<Arc<AtomicBool>>,
+) -> Result<CommandResult<DirectoryLoadResult>, String> {
     let path = Path::new(&path);
 
+    // Each call supersedes the ones before it; wait briefly so bursts of calls
+    // (mode switch + filter change) collapse into a single walk
+    let generation = state.load_generation.fetch_add(1, Ordering::SeqCst) + 1;
+    tokio::time::sleep(std::time::Duration::from_millis(LOAD_DEBOUNCE_MS)).await;
+    let is_superseded = || state.load_generation.load(Ordering::SeqCst) != generation;
+    if is_superseded() {
+        return Ok(superseded_load());
+    }
+
+    if !path.is_dir() {
+        forget_missing_project(path, state, &app_handle);
+        return Ok(project_missing(AppError::ProjectMissing(
+            path.to_string_lossy().to_string(),
+        )));
+    }
+
+    if let Some(refusal) = dangerous_root_refusal(path, allow_dangerous_root) {
+        return Ok(refusal);
+    }
+
+    // Loading lazily only lists the top level, which is quick however big the directory is
+    let settings = load_app_settings(&app_handle);
+    let lazy = settings.lazy_tree_loading;
+
+    // A first scan of something like a home directory can take a long time,
+    // so ask the frontend to confirm before walking it
+    if !confirmed.unwrap_or(false)
+        && !lazy
+        && settings.confirm_before_large_scan
+        && !config::project_config_exists(path)
+        && !index::has_index(&app_handle, path)
+        && fs::exceeds_entry_count(
+            path,
+            true,
+            &scan_default_ignores(path, &app_handle),
+            LARGE_SCAN_ENTRY_LIMIT,
+        )
+    {
+        return Ok(CommandResult::error_with_code(
+            CODE_CONFIRM_REQUIRED,
+            format!(
+                "{} contains more than {} files and folders. Scan it anyway?",
+                path.to_string_lossy(),
+                LARGE_SCAN_ENTRY_LIMIT
+            ),
+        ));
+    }
+
     // Update the current directory
     *state.current_dir.lock().unwrap() = path.to_path_buf();
 
+    // A project without a saved config has never been configured
+    let is_first_open = !config::project_config_exists(path);
+
     // Get the active config mode
     let config_mode = *state.config_mode.lock().unwrap();
 
+    let mut profiler = Profiler::new("scan");
+
     // Load config based on active mode instead of always trying local first
-    let display_config = match config_mode {
-        ConfigMode::LocalOverride => {
-            // Try local first, fall back to global
-            config::load_or_create_project_config(path)
-                .or_else(|_| config::load_or_create_global_config())
-                .unwrap_or_default()
+    let display_config = profiler.time("config", || {
+        let mut config = match config_mode {
+            ConfigMode::LocalOverride => {
+                // Try local first, fall back to global
+                config::load_project_config(path, &new_project_config(&app_handle))
+                    .or_else(|_| load_global_config(&app_handle))
+                    .unwrap_or_default()
+            }
+            ConfigMode::Global => {
+                // Only use global config
+                load_global_config(&app_handle).unwrap_or_default()
+            }
+        };
+        config::apply_env_overrides(&mut config);
+        config
+    });
+
+    // Watch the config files now that a local one may have just been created
+    let config_watcher = match watcher::watch_config_files(
+        app_handle.clone(),
+        Some(path),
+        !settings.protect_global_config,
+    ) {
+        Ok(config_watcher) => {
+            // A pause requested by the user carries over to the new project
+            let was_paused = state
+                .config_watcher
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(watcher::ConfigWatcher::is_paused_by_user);
+            if was_paused {
+                config_watcher.pause();
+            }
+            Some(config_watcher)
         }
-        ConfigMode::Global => {
-            // Only use global config
-            config::load_or_create_global_config().unwrap_or_default()
+        Err(e) => {
+            eprintln!("[GPTree] Failed to watch config files: {}", e);
+            None
         }
     };
+    *state.config_watcher.lock().unwrap() = config_watcher;
 
     // Convert exclude_dirs from Vec<String> to HashSet<String> for fs function
     let excluded_dirs_set: HashSet<String> = display_config.exclude_dirs.iter().cloned().collect();
 
+    // Saved selections outlive the files in them; point out the ones that are gone
+    let missing_previous_files = config::missing_previous_files(&display_config, path);
+
+    // Look up the sparse checkout once for everything this load filters
+    let skip_worktree = fs::load_skip_worktree(path);
+    *state.skip_worktree.lock().unwrap() = Some((path.to_path_buf(), skip_worktree.clone()));
+
+    // The tree watcher judges new entries with the same filters as the walk
+    *state.tree_watcher.lock().unwrap() = None;
+    let tree_filter = if settings.watch_tree {
+        fs::PathFilter::new(path, &display_config, &skip_worktree)
+            .ok()
+            .map(|filter| (filter, excluded_dirs_set.clone()))
+    } else {
+        None
+    };
+
+    // Answer from the on-disk index when it was built with the same filters,
+    // then re-walk in the background and push the fresh tree if anything changed
+    let fingerprint = index::filter_fingerprint(&display_config, &excluded_dirs_set);
+    let cached = profiler.time("index", || {
+        index::load_index(&app_handle, path)
+            .filter(|cached| cached.filter_fingerprint == fingerprint)
+    });
+    if !lazy {
+        state.stats.increment(if cached.is_some() {
+            "index_cache_hit"
+        } else {
+            "index_cache_miss"
+        });
+    }
+
+    let limits = resource_limits(&app_handle);
+    let scan_started = Instant::now();
+    let taskbar_progress = TaskbarProgress::start(&app_handle);
+    let tree_result = if lazy {
+        profiler.time("walk", || {
+            fs::get_directory_level(
+                path,
+                path,
+                &display_config,
+                &excluded_dirs_set,
+                &skip_worktree,
+            )
+        })
+    } else {
+        match cached {
+            Some(cached) => {
+                let tree = cached.tree.clone();
+                let root = path.to_path_buf();
+                let app_handle = app_handle.clone();
+                tauri::async_runtime::spawn_blocking(move || {
+                    // Give up if another load_directory call has started since
+                    let is_stopped = || scan_stopped(&app_handle, generation);
+                    match index::build_index(
+                        &root,
+                        &display_config,
+                        &excluded_dirs_set,
+                        &skip_worktree,
+                        &is_stopped,
+                        &AtomicUsize::new(0),
+                        limits.scan_threads,
+                    ) {
+                        Ok(mut fresh) => {
+                            index::hash_entries(
+                                &root,
+                                &mut fresh,
+                                Some(&cached),
+                                limits.read_concurrency,
+                            );
+                            if fresh.entries != cached.entries {
+                                let payload = TreeUpdatedPayload {
+                                    root: fresh.root.clone(),
+                                    tree: fresh.tree.clone(),
+                                };
+                                if let Err(e) = app_handle.emit("directory-tree-updated", payload) {
+                                    eprintln!(
+                                        "[GPTree] Warning: Failed to emit tree update: {}",
+                                        e
+                                    );
+                                }
+                            }
+                            if let Err(e) = index::save_index(&app_handle, &fresh) {
+                                eprintln!("[GPTree] Warning: Failed to save project index: {}", e);
+                            }
+                        }
+                        Err(AppError::Cancelled) => {}
+                        Err(e) => eprintln!("[GPTree] Warning: Background refresh failed: {}", e),
+                    }
+                });
+                Ok(tree)
+            }
+            None => {
+                // Walk on a blocking thread, reporting how far it has got as it goes
+                let root = path.to_path_buf();
+                let walk_handle = app_handle.clone();
+                let walk_cancel = cancel.clone();
+                let walk_started = Instant::now();
+                let walk = tauri::async_runtime::spawn_blocking(move || {
+                    let files_scanned = AtomicUsize::new(0);
+                    let last_progress = std::sync::Mutex::new(Instant::now());
+                    let is_stopped = || {
+                        emit_scan_progress(&walk_handle, &root, &files_scanned, &last_progress);
+                        scan_stopped(&walk_handle, generation) || is_set(&walk_cancel)
+                    };
+                    index::build_index(
+                        &root,
+                        &display_config,
+                        &excluded_dirs_set,
+                        &skip_worktree,
+                        &is_stopped,
+                        &files_scanned,
+                        limits.scan_threads,
+                    )
+                })
+                .await;
+                profiler.add("walk", walk_started.elapsed());
+                walk.unwrap_or_else(|e| Err(AppError::Config(format!("Scan failed: {}", e))))
+                    .map(|mut fresh| {
+                        // Hashing reads every file, so the tree is shown first and the index
+                        // is saved once its hashes are in. Hashes of an index built with other
+                        // filters are still good for the files it shares.
+                        let tree = fresh.tree.clone();
+                        let root = path.to_path_buf();
+                        let app_handle = app_handle.clone();
+                        tauri::async_runtime::spawn_blocking(move || {
+                            let previous = index::load_index(&app_handle, &root);
+                            index::hash_entries(
+                                &root,
+                                &mut fresh,
+                                previous.as_ref(),
+                                limits.read_concurrency,
+                            );
+                            if let Err(e) = index::save_index(&app_handle, &fresh) {
+                                eprintln!("[GPTree] Warning: Failed to save project index: {}", e);
+                            }
+                        });
+                        tree
+                    })
+            }
+        }
+    };
+
+    drop(taskbar_progress);
+
+    if is_set(&cancel) {
+        state.stats.increment("scan_cancelled");
+        return Ok(CommandResult::error_with_code(
+            CODE_SCAN_CANCELLED,
+            "Scan cancelled".to_string(),
+        ));
+    }
+    // A newer call owns the result now, even if this walk happened to finish
+    if is_superseded() {
+        state.stats.increment("load_superseded");
+        return Ok(superseded_load());
+    }
+
     // Load directory tree based on display settings
-    match fs::get_directory_tree(
-        path,
-        display_config.use_git_ignore,
-        display_config.show_ignored_in_tree,
-        display_config.show_default_ignored_in_tree,
-        &display_config.include_file_types,
-        &display_config.exclude_file_types,
-        &excluded_dirs_set,
-    ) {
-        Ok(tree) => Ok(CommandResult::success(tree)),
+    match tree_result {
+        Ok(tree) => {
+            if let Some((filter, excluded_dirs)) = tree_filter {
+                match watcher::watch_tree(app_handle.clone(), path, filter, excluded_dirs) {
+                    Ok(tree_watcher) => {
+                        // Like the config watcher, stay paused if the user paused watching
+                        let paused = state
+                            .config_watcher
+                            .lock()
+                            .unwrap()
+                            .as_ref()
+                            .is_some_and(watcher::ConfigWatcher::is_paused_by_user);
+                        if paused {
+                            tree_watcher.pause();
+                        }
+                        *state.tree_watcher.lock().unwrap() = Some(tree_watcher)
+                    }
+                    Err(e) => eprintln!("[GPTree] Failed to watch directory tree: {}", e),
+                }
+            }
+            let folder_name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.to_string_lossy().to_string());
+            notify_if_slow(
+                &app_handle,
+                scan_started,
+                format!("Finished scanning {}", folder_name),
+            );
+            record_activity(
+                state,
+                "load_directory",
+                path,
+                scan_started,
+                Some(count_files(&tree)),
+                None,
+            );
+            remember_recent_directory(&app_handle, path, (!lazy).then(|| count_files(&tree)));
+
+            // Offer stack-specific filters for projects that have never been configured
+            let suggested_filters = if is_first_open {
+                fs::detect_project_filters(path)
+            } else {
+                None
+            };
+            // Point out the repo root if a subdirectory was opened
+            let suggested_root = git::find_repo_root(path)
+                .filter(|root| root != path)
+                .map(|root| root.to_string_lossy().to_string());
+            // Large trees can be requested in the compact flat form
+            let tree = if flat.unwrap_or(false) {
+                TreePayload::Flat(profiler.time("flatten", || fs::flatten_tree(&tree)))
+            } else {
+                TreePayload::Nested(tree)
+            };
+            *state.last_profile.lock().unwrap() = Some(profiler.finish());
+            Ok(CommandResult::success(DirectoryLoadResult {
+                tree,
+                suggested_filters,
+                suggested_root,
+                missing_previous_files,
+            }))
+        }
+        Err(AppError::Cancelled) if state.cancelled_scan.load(Ordering::SeqCst) == generation => {
+            state.stats.increment("scan_cancelled");
+            Ok(CommandResult::error_with_code(
+                CODE_SCAN_CANCELLED,
+                "Scan cancelled".to_string(),
+            ))
+        }
+        Err(AppError::Cancelled) => {
+            state.stats.increment("load_superseded");
+            Ok(superseded_load())
+        }
         Err(e) => Ok(CommandResult::error(format!(
             "Failed to get directory tree: {}",
             e
@@ -174,67 +1098,113 @@ async fn load_directory(
     }
 }
 
-// Command to get the current configuration
+// Command to load one folder's direct children, for expanding a lazily loaded tree.
+// Child folders come back with children_pending set, unless `recursive` asks for the
+// whole subtree (to select a folder that hasn't been expanded).
 #[tauri::command]
-async fn get_config(state: tauri::State<'_, AppState>) -> Result<CommandResult<Config>, String> {
-    let config_mode = state.config_mode.lock().unwrap();
-    let current_dir = state.current_dir.lock().unwrap().clone();
-    let config = match *config_mode {
-        ConfigMode::LocalOverride => {
-            let local = config::load_or_create_project_config(&current_dir);
-            local.unwrap_or_else(|_| config::load_or_create_global_config().unwrap_or_default())
+async fn load_directory_children(
+    path: String,
+    recursive: Option<bool>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<DirectoryItem>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    let dir_path = Path::new(&path);
+    // Compare resolved paths, so `..` or a symlink can't lead out of the project
+    let project_dir = StdFs::canonicalize(&current_dir).unwrap_or(current_dir.clone());
+    let inside =
+        StdFs::canonicalize(dir_path).is_ok_and(|resolved| resolved.starts_with(&project_dir));
+    if !inside {
+        return Ok(CommandResult::error(format!(
+            "{} is not inside the current project",
+            path
+        )));
+    }
+    let config_mode = *state.config_mode.lock().unwrap();
+
+    let config = match load_active_config(&current_dir, config_mode, &app_handle) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            return Ok(CommandResult::error(format!(
+                "Failed to load active config: {}",
+                e
+            )))
         }
-        ConfigMode::Global => config::load_or_create_global_config().unwrap_or_default(),
     };
-    Ok(CommandResult::success(config))
-}
+    let excluded_dirs_set: HashSet<String> = config.exclude_dirs.iter().cloned().collect();
 
-// Command to update the configuration
-#[tauri::command]
-async fn update_config(
-    config: Config,
-    state: tauri::State<'_, AppState>,
-) -> Result<CommandResult<bool>, AppError> {
-    let config_mode = *state.config_mode.lock().unwrap();
-    let current_dir = state.current_dir.lock().unwrap().clone();
+    let skip_worktree = project_skip_worktree(&state, &current_dir);
 
-    // Use the more reliable helper function
-    let is_global = config_mode == ConfigMode::Global;
-    let current_dir_ref = if is_global {
-        None
+    let started = Instant::now();
+    let result = if recursive.unwrap_or(false) {
+        fs::get_directory_subtree(
+            &current_dir,
+            dir_path,
+            &config,
+            &excluded_dirs_set,
+            &skip_worktree,
+        )
     } else {
-        Some(current_dir.as_path())
+        fs::get_directory_level(
+            &current_dir,
+            dir_path,
+            &config,
+            &excluded_dirs_set,
+            &skip_worktree,
+        )
     };
-
-    match config::ensure_config_saved(&config, is_global, current_dir_ref) {
-        Ok(saved_path) => {
-            eprintln!("[GPTree] Successfully saved config to {:?}", saved_path);
-            Ok(CommandResult::success(true))
-        }
-        Err(e) => {
-            eprintln!("[GPTree] Error saving config: {:?}", e);
-            Err(e)
-        }
+    state
+        .stats
+        .record("load_directory_children", started.elapsed());
+    match result {
+        Ok(item) => Ok(CommandResult::success(item)),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to load directory children: {}",
+            e
+        ))),
     }
 }
 
-// Command to generate output based on selected files
+// Command to report every rule keeping `path` (absolute or project-relative) out of the tree:
+// which .gitignore line, default ignore, excluded directory or file type filter.
+// `excluded_dirs` are the directories excluded in the UI, defaulting to the config's.
 #[tauri::command]
-async fn generate_output(
-    selected_files: Vec<String>,
-    excluded_dirs: Vec<String>,
+async fn explain_ignore(
+    path: String,
+    excluded_dirs: Option<Vec<String>>,
     state: tauri::State<'_, AppState>,
-) -> Result<CommandResult<OutputContent>, String> {
-    let current_dir = state.current_dir.lock().unwrap().clone();
-    let config_mode = *state.config_mode.lock().unwrap();
-
-    // Load the active config based on the mode
-    let config_result = match config_mode {
-        ConfigMode::LocalOverride => config::load_or_create_project_config(&current_dir),
-        ConfigMode::Global => config::load_or_create_global_config(),
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<IgnoreReason>>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    let target = if Path::new(&path).is_absolute() {
+        PathBuf::from(&path)
+    } else {
+        fs::resolve_project_path(&current_dir, &path)
+    };
+    let Ok(resolved) = StdFs::canonicalize(&target) else {
+        return Ok(CommandResult::error(format!(
+            "{} does not exist",
+            target.to_string_lossy()
+        )));
     };
+    // Compare resolved paths, so `..` or a symlink can't lead out of the project
+    let project_dir = StdFs::canonicalize(&current_dir).unwrap_or(current_dir.clone());
+    let Ok(relative) = resolved.strip_prefix(&project_dir) else {
+        return Ok(CommandResult::error(format!(
+            "{} is not inside the current project",
+            path
+        )));
+    };
+    let target = current_dir.join(relative);
 
-    let config = match config_result {
+    let config_mode = *state.config_mode.lock().unwrap();
+    let config = match load_active_config(&current_dir, config_mode, &app_handle) {
         Ok(cfg) => cfg,
         Err(e) => {
             return Ok(CommandResult::error(format!(
@@ -243,368 +1213,3178 @@ async fn generate_output(
             )))
         }
     };
+    let excluded_dirs_set: HashSet<String> = excluded_dirs
+        .unwrap_or_else(|| config.exclude_dirs.clone())
+        .into_iter()
+        .collect();
 
-    // Process the files
-    match processor::combine_files_with_structure(
-        &current_dir,
-        &config,
-        &selected_files,
-        &excluded_dirs,
-    ) {
-        Ok(mut output) => {
-            // Save the list of selected files if configured
-            if config.store_files_chosen {
-                // Ensure config path exists for local saving
-                if config::load_or_create_project_config(&current_dir).is_ok() {
-                    let config_path = current_dir.join(".gptree_config");
-                    if let Err(e) =
-                        config::update_previous_files(&config_path, &selected_files, &current_dir)
-                    {
-                        eprintln!("Warning: Failed to update previous files in config: {}", e);
+    let skip_worktree = project_skip_worktree(&state, &current_dir);
+    match fs::PathFilter::new(&current_dir, &config, &skip_worktree) {
+        Ok(filter) => Ok(CommandResult::success(filter.explain(
+            &current_dir,
+            &target,
+            &excluded_dirs_set,
+        ))),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to build the tree filters: {}",
+            e
+        ))),
+    }
+}
+
+// Whether an optional cancel flag has been raised
+fn is_set(flag: &Option<Arc<AtomicBool>>) -> bool {
+    flag.as_ref()
+        .is_some_and(|flag| flag.load(Ordering::SeqCst))
+}
+
+// Whether the walk for load_directory call `generation` should stop: a newer call has
+// started, or cancel_scan was called for it
+fn scan_stopped(app_handle: &tauri::AppHandle, generation: u64) -> bool {
+    let state = app_handle.state::<AppState>();
+    state.load_generation.load(Ordering::SeqCst) != generation
+        || state.cancelled_scan.load(Ordering::SeqCst) == generation
+}
+
+// Send a "scan-progress" event, at most once every SCAN_PROGRESS_INTERVAL_MS
+fn emit_scan_progress(
+    app_handle: &tauri::AppHandle,
+    root: &Path,
+    files_scanned: &AtomicUsize,
+    last_sent: &std::sync::Mutex<Instant>,
+) {
+    let mut last_sent = last_sent.lock().unwrap();
+    if last_sent.elapsed() < std::time::Duration::from_millis(SCAN_PROGRESS_INTERVAL_MS) {
+        return;
+    }
+    *last_sent = Instant::now();
+    let payload = ScanProgressPayload {
+        root: root.to_string_lossy().to_string(),
+        files_scanned: files_scanned.load(Ordering::Relaxed),
+    };
+    if let Err(e) = app_handle.emit("scan-progress", payload) {
+        eprintln!("[GPTree] Failed to emit scan progress: {}", e);
+    }
+}
+
+// Command to stop the directory scan in progress, e.g. after opening a home directory by
+// mistake. The load_directory call it stops returns the "scan_cancelled" code.
+#[tauri::command]
+async fn cancel_scan(state: tauri::State<'_, AppState>) -> Result<CommandResult<()>, String> {
+    let generation = state.load_generation.load(Ordering::SeqCst);
+    state.cancelled_scan.store(generation, Ordering::SeqCst);
+    Ok(CommandResult::success(()))
+}
+
+// Number of files in a directory tree
+fn count_files(item: &DirectoryItem) -> usize {
+    item.children
+        .iter()
+        .map(|child| if child.is_dir { count_files(child) } else { 1 })
+        .sum()
+}
+
+// Add an entry to the session activity log
+fn record_activity(
+    state: &AppState,
+    kind: &str,
+    root_dir: &Path,
+    started: Instant,
+    file_count: Option<usize>,
+    token_estimate: Option<usize>,
+) {
+    let elapsed = started.elapsed();
+    state.stats.record(kind, elapsed);
+    let started_at = chrono::Local::now()
+        - chrono::Duration::from_std(elapsed).unwrap_or_else(|_| chrono::Duration::zero());
+    let mut log = state.session_log.lock().unwrap();
+    if log.len() >= SESSION_LOG_LIMIT {
+        log.remove(0);
+    }
+    log.push(ActivityEntry {
+        kind: kind.to_string(),
+        root_dir: root_dir.to_string_lossy().to_string(),
+        started_at: started_at.to_rfc3339(),
+        duration_ms: elapsed.as_millis() as u64,
+        file_count,
+        token_estimate,
+    });
+}
+
+// Move `dir` to the front of the recent directories list, dropping the oldest past the limit
+fn remember_recent_directory(app_handle: &tauri::AppHandle, dir: &Path, file_count: Option<usize>) {
+    let path = dir.to_string_lossy().to_string();
+    let mut session = config::load_session_state(app_handle).unwrap_or_default();
+    session
+        .recent_directories
+        .retain(|recent| recent.path != path);
+    session.recent_directories.insert(
+        0,
+        RecentDirectory {
+            path,
+            last_opened: chrono::Local::now().to_rfc3339(),
+            file_count,
+        },
+    );
+    session
+        .recent_directories
+        .truncate(RECENT_DIRECTORIES_LIMIT);
+    if let Err(e) = config::save_session_state(app_handle, &session) {
+        eprintln!("[GPTree] Failed to save recent directories: {}", e);
+    }
+    if let Err(e) = refresh_app_menu(app_handle) {
+        eprintln!("[GPTree] Failed to refresh application menu: {}", e);
+    }
+}
+
+// Command to list recently opened directories, most recent first
+#[tauri::command]
+async fn get_recent_directories(
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<RecentDirectory>>, String> {
+    match config::load_session_state(&app_handle) {
+        Ok(session) => Ok(CommandResult::success(session.recent_directories)),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to load recent directories: {}",
+            e
+        ))),
+    }
+}
+
+// Command to drop a directory from the recent list, returning what's left of it
+#[tauri::command]
+async fn remove_recent_directory(
+    path: String,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<RecentDirectory>>, String> {
+    let mut session = match config::load_session_state(&app_handle) {
+        Ok(session) => session,
+        Err(e) => {
+            return Ok(CommandResult::error(format!(
+                "Failed to load recent directories: {}",
+                e
+            )))
+        }
+    };
+    session
+        .recent_directories
+        .retain(|recent| recent.path != path);
+    if let Err(e) = config::save_session_state(&app_handle, &session) {
+        return Ok(CommandResult::error(format!(
+            "Failed to save recent directories: {}",
+            e
+        )));
+    }
+    if let Err(e) = refresh_app_menu(&app_handle) {
+        eprintln!("[GPTree] Failed to refresh application menu: {}", e);
+    }
+    Ok(CommandResult::success(session.recent_directories))
+}
+
+// Command to find the files added, modified or removed since the last call. Unchanged size
+// and modified time reuse the stored hash; everything else is hashed in parallel. The baseline
+// is its own file, which only this command advances, so loads in between don't hide changes.
+#[tauri::command]
+async fn detect_changes(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<ChangeReport>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    let config_mode = *state.config_mode.lock().unwrap();
+    let config = match load_active_config(&current_dir, config_mode, &app_handle) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            return Ok(CommandResult::error(format!(
+                "Failed to load active config: {}",
+                e
+            )))
+        }
+    };
+    let excluded_dirs_set: HashSet<String> = config.exclude_dirs.iter().cloned().collect();
+
+    // A baseline built with other filters lists other files, so it can't be compared with
+    let fingerprint = index::filter_fingerprint(&config, &excluded_dirs_set);
+    let previous = index::load_baseline(&app_handle, &current_dir)
+        .filter(|previous| previous.filter_fingerprint == fingerprint);
+    let limits = resource_limits(&app_handle);
+    let skip_worktree = project_skip_worktree(&state, &current_dir);
+
+    let started = Instant::now();
+    let worker_handle = app_handle.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let fresh = index::build_index(
+            &current_dir,
+            &config,
+            &excluded_dirs_set,
+            &skip_worktree,
+            &|| false,
+            &AtomicUsize::new(0),
+            limits.scan_threads,
+        )?;
+        let (baseline, hashed, reused) = index::snapshot_files(
+            &current_dir,
+            &fresh,
+            previous.as_ref(),
+            limits.read_concurrency,
+        );
+        let mut report = previous
+            .as_ref()
+            .map(|previous| index::diff_baselines(previous, &baseline))
+            .unwrap_or_default();
+        report.hashed = hashed;
+        report.reused = reused;
+        index::save_baseline(&worker_handle, &current_dir, &baseline)?;
+        Ok::<_, AppError>(report)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    state.stats.record("detect_changes", started.elapsed());
+
+    match result {
+        Ok(mut report) => {
+            report.elapsed_ms = started.elapsed().as_millis() as u64;
+            Ok(CommandResult::success(report))
+        }
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to detect changes: {}",
+            e
+        ))),
+    }
+}
+
+// Command to describe every config field (type, default, description, the config modes
+// it's saved in), so the settings UI can be generated from the backend's options
+#[tauri::command]
+async fn describe_config_schema() -> Result<CommandResult<Vec<ConfigFieldSchema>>, String> {
+    Ok(CommandResult::success(config::config_schema()))
+}
+
+// Command to list the favorite projects
+#[tauri::command]
+async fn list_favorites(
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<FavoriteProject>>, String> {
+    match config::list_favorites(&app_handle) {
+        Ok(favorites) => Ok(CommandResult::success(favorites)),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to load favorites: {}",
+            e
+        ))),
+    }
+}
+
+// Command to pin a project as a favorite, optionally under a name of its own
+#[tauri::command]
+async fn add_favorite(
+    path: String,
+    name: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<FavoriteProject>>, String> {
+    match config::add_favorite(&app_handle, Path::new(&path), name) {
+        Ok(favorites) => Ok(CommandResult::success(favorites)),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to add favorite: {}",
+            e
+        ))),
+    }
+}
+
+// Command to unpin a favorite project, returning the ones left
+#[tauri::command]
+async fn remove_favorite(
+    path: String,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<FavoriteProject>>, String> {
+    match config::remove_favorite(&app_handle, &path) {
+        Ok(favorites) => Ok(CommandResult::success(favorites)),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to remove favorite: {}",
+            e
+        ))),
+    }
+}
+
+// Command to list the directories loaded and generations run this session, oldest first
+#[tauri::command]
+async fn get_session_history(
+    state: tauri::State<'_, AppState>,
+) -> Result<CommandResult<Vec<ActivityEntry>>, String> {
+    Ok(CommandResult::success(
+        state.session_log.lock().unwrap().clone(),
+    ))
+}
+
+// Command to report how often each operation ran since launch and how long it took,
+// plus counters like index cache hits. Kept in memory only.
+#[tauri::command]
+async fn get_runtime_stats(
+    state: tauri::State<'_, AppState>,
+) -> Result<CommandResult<stats::RuntimeStats>, String> {
+    Ok(CommandResult::success(state.stats.snapshot()))
+}
+
+// Result returned by a load_directory call that a newer call replaced
+fn superseded_load() -> CommandResult<DirectoryLoadResult> {
+    CommandResult::error_with_code(
+        CODE_SUPERSEDED,
+        "Directory load superseded by a newer request".to_string(),
+    )
+}
+
+// Forget a project directory that has disappeared so the next launch doesn't try to reopen it
+fn forget_missing_project(dir: &Path, state: &AppState, app_handle: &tauri::AppHandle) {
+    {
+        let mut current_dir = state.current_dir.lock().unwrap();
+        if current_dir.as_path() == dir {
+            *current_dir = PathBuf::new();
+            *state.tree_watcher.lock().unwrap() = None;
+            *state.live_watcher.lock().unwrap() = None;
+        }
+    }
+
+    if let Ok(mut session) = config::load_session_state(app_handle) {
+        if session.last_directory.as_deref() == Some(&*dir.to_string_lossy()) {
+            session.last_directory = None;
+            if let Err(e) = config::save_session_state(app_handle, &session) {
+                eprintln!(
+                    "[GPTree] Failed to clear missing project from session: {}",
+                    e
+                );
+            }
+        }
+    }
+}
+
+// Get the loaded directory, checking it still exists (it may have been deleted or unmounted)
+fn current_project_dir(
+    state: &AppState,
+    app_handle: &tauri::AppHandle,
+) -> Result<PathBuf, AppError> {
+    let current_dir = state.current_dir.lock().unwrap().clone();
+    if current_dir.as_os_str().is_empty() || current_dir.is_dir() {
+        return Ok(current_dir);
+    }
+
+    eprintln!("[GPTree] Project directory disappeared: {:?}", current_dir);
+    forget_missing_project(&current_dir, state, app_handle);
+    Err(AppError::ProjectMissing(
+        current_dir.to_string_lossy().to_string(),
+    ))
+}
+
+fn project_missing<T>(e: AppError) -> CommandResult<T> {
+    CommandResult::error_with_code(CODE_PROJECT_MISSING, e.to_string())
+}
+
+// Details of the "dangerous_root" refusal load_directory and generate_output return
+#[derive(Debug, Clone, Serialize)]
+struct DangerousRootDetails {
+    path: String,
+    // "home" or "root"
+    kind: &'static str,
+}
+
+// Scanning a home directory or a filesystem root is effectively never intentional,
+// so refuse unless the caller passed `allow_dangerous_root`
+fn dangerous_root_refusal<T>(
+    path: &Path,
+    allow_dangerous_root: Option<bool>,
+) -> Option<CommandResult<T>> {
+    if allow_dangerous_root.unwrap_or(false) {
+        return None;
+    }
+    let kind = fs::dangerous_root_kind(path)?;
+    let described = if kind == "home" {
+        "your home directory"
+    } else {
+        "the root of a drive"
+    };
+    Some(CommandResult::error_with_details(
+        CODE_DANGEROUS_ROOT,
+        format!(
+            "{} is {}, which would scan nearly everything on it. Open it anyway?",
+            path.to_string_lossy(),
+            described
+        ),
+        DangerousRootDetails {
+            path: path.to_string_lossy().to_string(),
+            kind,
+        },
+    ))
+}
+
+// Command to get the current configuration, with where each value comes from.
+// `session_overrides` names the fields the UI overrides for this session only.
+#[tauri::command]
+async fn get_config(
+    session_overrides: Option<Vec<String>>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<ConfigWithSources>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    let config_mode = *state.config_mode.lock().unwrap();
+    let global = load_global_config(&app_handle).unwrap_or_default();
+    let (mut config, is_local) = match config_mode {
+        ConfigMode::LocalOverride => {
+            match config::load_project_config(&current_dir, &new_project_config(&app_handle)) {
+                Ok(local) => (local, true),
+                Err(_) => (global, false),
+            }
+        }
+        ConfigMode::Global => (global, false),
+    };
+
+    // Sources are what the parsers saw, not guesses from comparing values
+    let source_path = if is_local {
+        Some(config::project_config_path(&current_dir))
+    } else {
+        config::global_config_path()
+    };
+    let file_fields = source_path
+        .and_then(|path| config::load_config_with_fields(&path).ok())
+        .map(|(_, fields)| fields)
+        .unwrap_or_default();
+    let env_fields = config::apply_env_overrides(&mut config);
+    let session_fields: HashSet<String> =
+        session_overrides.unwrap_or_default().into_iter().collect();
+    let sources = config::config_sources(
+        &file_fields,
+        if is_local { "local" } else { "global" },
+        &env_fields,
+        &session_fields,
+    );
+    Ok(CommandResult::success(ConfigWithSources {
+        config,
+        sources,
+    }))
+}
+
+// Command to update the configuration
+#[tauri::command]
+async fn update_config(
+    config: Config,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<bool>, AppError> {
+    let config_mode = *state.config_mode.lock().unwrap();
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+
+    // Use the more reliable helper function
+    let routed = route_protected_global_edit(&state, &app_handle);
+    let is_global = config_mode == ConfigMode::Global && !routed;
+    let current_dir_ref = if is_global {
+        None
+    } else {
+        Some(current_dir.as_path())
+    };
+
+    let mut config = config;
+    if !is_global && config.prune_missing_previous_files {
+        let missing: HashSet<String> = config::missing_previous_files(&config, &current_dir)
+            .into_iter()
+            .collect();
+        config
+            .previous_files
+            .retain(|relative| !missing.contains(relative));
+    }
+
+    match config::ensure_config_saved(&config, is_global, current_dir_ref) {
+        Ok(saved_path) => {
+            eprintln!("[GPTree] Successfully saved config to {:?}", saved_path);
+            if routed {
+                return Ok(CommandResult {
+                    code: Some(CODE_SAVED_TO_PROJECT_CONFIG.to_string()),
+                    ..CommandResult::success(true)
+                });
+            }
+            Ok(CommandResult::success(true))
+        }
+        Err(e) => {
+            eprintln!("[GPTree] Error saving config: {:?}", e);
+            Err(e)
+        }
+    }
+}
+
+// Command to get the names hidden from the tree by the active config's default ignores
+#[tauri::command]
+async fn get_default_ignores(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<String>>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    let config_mode = *state.config_mode.lock().unwrap();
+    match load_active_config(&current_dir, config_mode, &app_handle) {
+        Ok(config) => Ok(CommandResult::success(config.default_ignores)),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to load active config: {}",
+            e
+        ))),
+    }
+}
+
+// Command to replace the active config's default ignores, in the global config or the
+// project's depending on the config mode
+#[tauri::command]
+async fn set_default_ignores(
+    names: Vec<String>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<String>>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    let config_mode = *state.config_mode.lock().unwrap();
+    let routed = route_protected_global_edit(&state, &app_handle);
+    let is_global = config_mode == ConfigMode::Global && !routed;
+
+    let mut config = match load_active_config(&current_dir, config_mode, &app_handle) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            return Ok(CommandResult::error(format!(
+                "Failed to load active config: {}",
+                e
+            )))
+        }
+    };
+    // Names are matched against single path components, and the config file keeps them
+    // comma-separated
+    config.default_ignores = names
+        .iter()
+        .map(|name| name.trim().trim_matches(['/', '\\']).to_string())
+        .filter(|name| !name.is_empty() && !name.contains(','))
+        .fold(Vec::new(), |mut unique, name| {
+            if !unique.contains(&name) {
+                unique.push(name);
+            }
+            unique
+        });
+
+    let dir = (!is_global).then_some(current_dir.as_path());
+    match config::ensure_config_saved(&config, is_global, dir) {
+        Ok(_) => Ok(CommandResult::success(config.default_ignores)),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to save default ignores: {}",
+            e
+        ))),
+    }
+}
+
+// Command to generate output based on selected files
+#[tauri::command]
+async fn generate_output(
+    selected_files: Vec<String>,
+    excluded_dirs: Vec<String>,
+    options: Option<GenerationOptions>,
+    allow_dangerous_root: Option<bool>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<OutputContent>, String> {
+    generate_output_result(
+        selected_files,
+        excluded_dirs,
+        options,
+        allow_dangerous_root,
+        &state,
+        app_handle,
+        None,
+    )
+    .await
+}
+
+// Same as generate_output, but the result is MessagePack-encoded, which avoids
+// JSON-escaping multi-megabyte outputs
+#[tauri::command]
+async fn generate_output_packed(
+    selected_files: Vec<String>,
+    excluded_dirs: Vec<String>,
+    options: Option<GenerationOptions>,
+    allow_dangerous_root: Option<bool>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<tauri::ipc::Response, String> {
+    let result = generate_output_result(
+        selected_files,
+        excluded_dirs,
+        options,
+        allow_dangerous_root,
+        &state,
+        app_handle,
+        None,
+    )
+    .await?;
+    pack_response(&result)
+}
+
+async fn generate_output_result(
+    selected_files: Vec<String>,
+    excluded_dirs: Vec<String>,
+    options: Option<GenerationOptions>,
+    allow_dangerous_root: Option<bool>,
+    state: &AppState,
+    app_handle: tauri::AppHandle,
+    task: Option<&tasks::TaskHandle>,
+) -> Result<CommandResult<OutputContent>, String> {
+    let current_dir = match current_project_dir(state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    if let Some(refusal) = dangerous_root_refusal(&current_dir, allow_dangerous_root) {
+        return Ok(refusal);
+    }
+    let config_mode = *state.config_mode.lock().unwrap();
+
+    // Load the active config based on the mode
+    let config = match load_active_config(&current_dir, config_mode, &app_handle) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            return Ok(CommandResult::error(format!(
+                "Failed to load active config: {}",
+                e
+            )))
+        }
+    };
+
+    // Process the files
+    let started = Instant::now();
+    let mut profiler = Profiler::new("generate");
+    let taskbar_progress = TaskbarProgress::start(&app_handle);
+    // Writing the output and stored selection shouldn't trigger the config watcher
+    let _watch_pause = state
+        .config_watcher
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(watcher::ConfigWatcher::pause_for_operation);
+    let generated = processor::combine_files_with_structure(
+        &current_dir,
+        &config,
+        &selected_files,
+        &excluded_dirs,
+        &GenerationOptions {
+            read_concurrency: resource_limits(&app_handle).read_concurrency,
+            cancel: task.map(tasks::TaskHandle::cancel_flag),
+            approved_commands: config::approved_commands(&app_handle, &current_dir),
+            ..options.unwrap_or_default()
+        },
+        &|done, total| {
+            taskbar_progress.set(done, total);
+            if let Some(task) = task {
+                task.report(&state.tasks, done, total);
+            }
+        },
+        &mut profiler,
+    );
+    drop(taskbar_progress);
+
+    let over_budget = match &generated {
+        Ok(output) => output
+            .model_fit
+            .as_ref()
+            .is_some_and(|fit| fit.exceeds_context_window),
+        Err(AppError::OutputTooLarge(_) | AppError::SafeMode(_)) => true,
+        Err(_) => false,
+    };
+    if generated.is_ok() || over_budget {
+        learn_exclusions(
+            state,
+            &app_handle,
+            &current_dir,
+            &excluded_dirs,
+            over_budget,
+        );
+    }
+
+    match generated {
+        Ok(mut output) => {
+            if let Some(fit) = output.model_fit.as_mut() {
+                calibration::apply(&app_handle, fit);
+            }
+
+            // Save the list of selected files if configured. Only a project config the user
+            // has saved remembers them; generating never creates one.
+            let config_path = config::project_config_path(&current_dir);
+            if config.store_files_chosen && config_path.exists() {
+                if let Err(e) =
+                    config::update_previous_files(&config_path, &selected_files, &current_dir)
+                {
+                    eprintln!("Warning: Failed to update previous files in config: {}", e);
+                }
+            }
+
+            // Rewriting the output inside a synced folder makes the sync client churn on every
+            // generation, so point out the app data directory as an alternative. Only users who
+            // asked for it have the save skipped.
+            // A rejected output path fails the save below, with the reason
+            let output_path =
+                processor::resolve_output_path(&config, &current_dir).unwrap_or_default();
+            let synced_service = fs::synced_folder_service(&output_path)
+                .filter(|_| config.save_output_file && !output_path.as_os_str().is_empty());
+            let block_synced = load_app_settings(&app_handle).block_output_in_synced_folders;
+            if let Some(service) = synced_service {
+                let alternative = app_data_output_path(&app_handle, &config.output_file)
+                    .map(|path| path.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                output.warnings.push(FileWarning {
+                    path: output_path.to_string_lossy().to_string(),
+                    kind: "synced_output_folder".to_string(),
+                    message: format!(
+                        "{} {} is in a {} folder, which re-syncs it after every generation. Save it to {} instead?",
+                        if block_synced { "Output not saved:" } else { "Output saved, but" },
+                        output_path.to_string_lossy(),
+                        service,
+                        alternative
+                    ),
+                });
+            }
+
+            // Process the output (save to file) and get the saved path
+            let saved = if synced_service.is_some() && block_synced {
+                Ok(None)
+            } else {
+                profiler.time("save", || {
+                    processor::process_output(&output, &config, &current_dir)
+                })
+            };
+            *state.last_profile.lock().unwrap() = Some(profiler.finish());
+            match saved {
+                Ok(saved_path_option) => {
+                    if let Some(saved_path) = saved_path_option
+                        .as_ref()
+                        .filter(|_| load_app_settings(&app_handle).open_output_after_save)
+                    {
+                        if let Err(e) = open_with_fallbacks(&app_handle, saved_path) {
+                            eprintln!("[GPTree] Failed to open output file: {}", e);
+                        }
+                    }
+                    if let Some(saved_path) = &saved_path_option {
+                        remember_saved_output(state, Path::new(saved_path));
                     }
+                    // Store the absolute path (or None) in the output object
+                    output.saved_path = saved_path_option;
+                }
+                Err(e) => {
+                    // Log the error but don't prevent returning the content
+                    eprintln!("Warning: Failed to save output file: {}", e);
+                    output.saved_path = None; // Indicate that saving failed
+                }
+            }
+
+            let mut body = format!(
+                "Context ready: {} tokens",
+                format_token_count(output.token_estimate)
+            );
+            if config.copy_to_clipboard {
+                body.push_str(", copied to clipboard");
+            }
+            notify_if_slow(&app_handle, started, body);
+            remember_output(state, &app_handle, &current_dir, &output);
+            record_activity(
+                state,
+                "generate",
+                &current_dir,
+                started,
+                Some(output.file_details.len()),
+                Some(output.token_estimate),
+            );
+
+            Ok(CommandResult::success(output))
+        }
+        Err(AppError::OutputTooLarge(details)) => {
+            let message = AppError::OutputTooLarge(details.clone()).to_string();
+            Ok(CommandResult::error_with_details(
+                CODE_OUTPUT_TOO_LARGE,
+                message,
+                details,
+            ))
+        }
+        Err(AppError::Cancelled) => Ok(CommandResult::error_with_code(
+            CODE_CANCELLED,
+            "Generation cancelled".to_string(),
+        )),
+        Err(AppError::SafeMode(suggestion)) => Ok(CommandResult::error_with_details(
+            CODE_SAFE_MODE_LIMIT,
+            suggestion.describe(),
+            suggestion,
+        )),
+        Err(AppError::SecretFiles(files)) => {
+            let message = AppError::SecretFiles(files.clone()).to_string();
+            Ok(CommandResult::error_with_details(
+                CODE_SECRET_FILE_BLOCKED,
+                message,
+                files,
+            ))
+        }
+        Err(AppError::CommandNotApproved(command)) => Ok(CommandResult::error_with_details(
+            CODE_COMMAND_NOT_APPROVED,
+            format!(
+                "This project's config pipes every file through `{}`. Run it?",
+                command
+            ),
+            CommandApprovalDetails { command },
+        )),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to generate output: {}",
+            e
+        ))),
+    }
+}
+
+// Details of the "command_not_approved" result generate_output returns when the project's
+// config has a command transform the user hasn't allowed yet
+#[derive(Debug, Clone, Serialize)]
+struct CommandApprovalDetails {
+    command: String,
+}
+
+// Command to allow the current project's `command` transform to run `command`. The approval
+// is tied to that exact string, so editing externalCommand asks again.
+#[tauri::command]
+async fn approve_external_command(
+    command: String,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<()>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    match config::approve_command(&app_handle, &current_dir, &command) {
+        Ok(()) => Ok(CommandResult::success(())),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to approve command: {}",
+            e
+        ))),
+    }
+}
+
+// Command to turn on live mode: until stop_live_mode (or another directory is opened), edits
+// to any of `selected_files` regenerate the output the way generate_output would, copying it
+// to the clipboard if the config asks for that, and send a "live-output" event
+#[tauri::command]
+async fn start_live_mode(
+    selected_files: Vec<String>,
+    excluded_dirs: Vec<String>,
+    options: Option<GenerationOptions>,
+    allow_dangerous_root: Option<bool>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<()>, String> {
+    if selected_files.is_empty() {
+        return Ok(CommandResult::error(
+            "Select some files before turning on live mode".to_string(),
+        ));
+    }
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    if let Some(refusal) = dangerous_root_refusal(&current_dir, allow_dangerous_root) {
+        return Ok(refusal);
+    }
+
+    let files: Vec<PathBuf> = selected_files.iter().map(PathBuf::from).collect();
+    let live_handle = app_handle.clone();
+    let regenerate = move || {
+        let state = live_handle.state::<AppState>();
+        // Another directory may have been opened since; the selection belongs to this one
+        if *state.current_dir.lock().unwrap() != current_dir {
+            return;
+        }
+        let result = tauri::async_runtime::block_on(generate_output_result(
+            selected_files.clone(),
+            excluded_dirs.clone(),
+            options.clone(),
+            allow_dangerous_root,
+            &state,
+            live_handle.clone(),
+            None,
+        ));
+        let payload = match result {
+            Ok(CommandResult {
+                success: true,
+                data: Some(output),
+                ..
+            }) => {
+                let copied_to_clipboard = load_active_config(
+                    &current_dir,
+                    *state.config_mode.lock().unwrap(),
+                    &live_handle,
+                )
+                .is_ok_and(|config| config.copy_to_clipboard)
+                    && clipboard_limit_bytes(&live_handle)
+                        .is_none_or(|limit| output.combined_content.len() <= limit)
+                    && live_handle
+                        .clipboard()
+                        .write_text(output.combined_content.clone())
+                        .is_ok();
+                LiveOutputPayload {
+                    token_estimate: output.token_estimate,
+                    file_count: output.file_details.len(),
+                    saved_path: output.saved_path,
+                    copied_to_clipboard,
+                    error: None,
+                }
+            }
+            Ok(CommandResult { error, .. }) => LiveOutputPayload {
+                token_estimate: 0,
+                file_count: 0,
+                saved_path: None,
+                copied_to_clipboard: false,
+                error: Some(error.unwrap_or_else(|| "Regeneration failed".to_string())),
+            },
+            Err(e) => LiveOutputPayload {
+                token_estimate: 0,
+                file_count: 0,
+                saved_path: None,
+                copied_to_clipboard: false,
+                error: Some(e),
+            },
+        };
+        if let Err(e) = live_handle.emit("live-output", payload) {
+            eprintln!("[GPTree] Failed to emit live output: {}", e);
+        }
+    };
+
+    match watcher::watch_selection(&files, regenerate) {
+        Ok(live_watcher) => {
+            *state.live_watcher.lock().unwrap() = Some(live_watcher);
+            Ok(CommandResult::success(()))
+        }
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to start live mode: {}",
+            e
+        ))),
+    }
+}
+
+// Command to turn live mode off
+#[tauri::command]
+async fn stop_live_mode(state: tauri::State<'_, AppState>) -> Result<CommandResult<()>, String> {
+    *state.live_watcher.lock().unwrap() = None;
+    Ok(CommandResult::success(()))
+}
+
+// Track exclusions around over-budget generations: remember what was excluded when one goes
+// over, and once a later generation of the same project fits, record the directories excluded
+// since then as learned exclusions
+fn learn_exclusions(
+    state: &AppState,
+    app_handle: &tauri::AppHandle,
+    current_dir: &Path,
+    excluded_dirs: &[String],
+    over_budget: bool,
+) {
+    let excluded: HashSet<String> = excluded_dirs.iter().cloned().collect();
+    let mut last_over_budget = state.over_budget.lock().unwrap();
+    if over_budget {
+        *last_over_budget = Some((current_dir.to_path_buf(), excluded));
+        return;
+    }
+    let Some((dir, excluded_then)) = last_over_budget.take() else {
+        return;
+    };
+    if dir != current_dir {
+        return;
+    }
+    let mut newly_excluded: Vec<String> = excluded.difference(&excluded_then).cloned().collect();
+    if newly_excluded.is_empty() {
+        return;
+    }
+    newly_excluded.sort();
+    if let Err(e) = exclusions::record(app_handle, current_dir, &newly_excluded) {
+        eprintln!("[GPTree] Failed to record learned exclusions: {}", e);
+    }
+}
+
+// Command to suggest directories to exclude in the current project, learned from what was
+// excluded in projects of the same type after their outputs went over budget
+#[tauri::command]
+async fn suggest_exclusions(
+    excluded_dirs: Vec<String>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<ExclusionSuggestion>>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    let already_excluded: HashSet<String> = excluded_dirs.into_iter().collect();
+    Ok(CommandResult::success(exclusions::suggest(
+        &app_handle,
+        &current_dir,
+        &already_excluded,
+    )))
+}
+
+// `file_name`, or `name-2.ext`, `name-3.ext`... if an earlier preset already took it
+fn unique_file_name(file_name: &str, used_names: &mut HashSet<String>) -> String {
+    let path = Path::new(file_name);
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    let mut candidate = file_name.to_string();
+    let mut n = 2;
+    while !used_names.insert(candidate.to_lowercase()) {
+        candidate = format!("{}-{}{}", stem, n, extension);
+        n += 1;
+    }
+    candidate
+}
+
+// Command to generate several selection presets back to back, saving each under a file name
+// from `file_name_template` (`{name}` and `{date}` are replaced) next to the configured output.
+// Presets that end up with the same file name get a numbered suffix. A preset that fails
+// doesn't stop the rest.
+#[tauri::command]
+async fn generate_batch(
+    presets: Vec<BatchPreset>,
+    file_name_template: Option<String>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<BatchResult>>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    let config_mode = *state.config_mode.lock().unwrap();
+    let config = match load_active_config(&current_dir, config_mode, &app_handle) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            return Ok(CommandResult::error(format!(
+                "Failed to load active config: {}",
+                e
+            )))
+        }
+    };
+    let template = file_name_template.unwrap_or_else(|| DEFAULT_BATCH_FILE_TEMPLATE.to_string());
+    // The template names files next to the usual output, never paths somewhere else
+    if template.trim().is_empty()
+        || template.contains(['/', '\\'])
+        || template == "."
+        || template == ".."
+    {
+        return Ok(CommandResult::error(format!(
+            "'{}' is not a valid file name template; it can't contain path separators or be '..'",
+            template
+        )));
+    }
+    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let read_concurrency = resource_limits(&app_handle).read_concurrency;
+    let approved_commands = config::approved_commands(&app_handle, &current_dir);
+    let _watch_pause = state
+        .config_watcher
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(watcher::ConfigWatcher::pause_for_operation);
+
+    let mut results = Vec::new();
+    // Lowercased, since presets differing only in case would share a file on most filesystems
+    let mut used_names = HashSet::new();
+    for preset in presets {
+        let started = Instant::now();
+        // Keep file names portable whatever the preset is called
+        let safe_name: String = preset
+            .name
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' || c == '_' {
+                    c
                 } else {
-                    eprintln!(
-                        "Warning: Could not load or create project config to save previous files."
-                    );
+                    '-'
+                }
+            })
+            .collect();
+        let file_name = unique_file_name(
+            &template
+                .replace("{name}", &safe_name)
+                .replace("{date}", &date),
+            &mut used_names,
+        );
+        let preset_config = Config {
+            output_file: Path::new(&config.output_file)
+                .with_file_name(file_name)
+                .to_string_lossy()
+                .to_string(),
+            save_output_file: true,
+            copy_to_clipboard: false,
+            ..config.clone()
+        };
+
+        let generated = processor::combine_files_with_structure(
+            &current_dir,
+            &preset_config,
+            &preset.selected_files,
+            &preset.excluded_dirs,
+            &GenerationOptions {
+                read_concurrency,
+                approved_commands: approved_commands.clone(),
+                ..preset.options
+            },
+            &|_, _| {},
+            &mut Profiler::new("generate"),
+        )
+        .and_then(|mut output| {
+            if let Some(fit) = output.model_fit.as_mut() {
+                calibration::apply(&app_handle, fit);
+            }
+            let saved_path = processor::process_output(&output, &preset_config, &current_dir)?;
+            Ok((output, saved_path))
+        });
+
+        results.push(match generated {
+            Ok((output, saved_path)) => {
+                if let Some(saved_path) = &saved_path {
+                    remember_saved_output(&state, Path::new(saved_path));
+                }
+                record_activity(
+                    &state,
+                    "generate",
+                    &current_dir,
+                    started,
+                    Some(output.file_details.len()),
+                    Some(output.token_estimate),
+                );
+                BatchResult {
+                    name: preset.name,
+                    saved_path,
+                    file_count: output.file_details.len(),
+                    token_estimate: output.token_estimate,
+                    error: None,
+                }
+            }
+            Err(e) => BatchResult {
+                name: preset.name,
+                saved_path: None,
+                file_count: 0,
+                token_estimate: 0,
+                error: Some(e.to_string()),
+            },
+        });
+    }
+
+    Ok(CommandResult::success(results))
+}
+
+// Where an output goes when saved to the app data directory instead of the configured location
+fn app_data_output_path(
+    app_handle: &tauri::AppHandle,
+    output_file: &str,
+) -> Result<PathBuf, AppError> {
+    let data_dir = app_handle
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Config(format!("Could not determine app data directory: {}", e)))?;
+    let file_name = Path::new(output_file)
+        .file_name()
+        .map(|name| name.to_os_string())
+        .unwrap_or_else(|| Config::default().output_file.into());
+    Ok(data_dir.join("outputs").join(file_name))
+}
+
+// Command to save an output to the app data directory, e.g. when the configured
+// location is in a synced folder
+#[tauri::command]
+async fn save_output_to_app_data(
+    content: String,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<String>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    let config_mode = *state.config_mode.lock().unwrap();
+    let output_file = load_active_config(&current_dir, config_mode, &app_handle)
+        .unwrap_or_default()
+        .output_file;
+
+    let saved = app_data_output_path(&app_handle, &output_file).and_then(|path| {
+        if let Some(parent) = path.parent() {
+            StdFs::create_dir_all(parent)?;
+        }
+        fs::save_to_file(&path, &content)?;
+        Ok(path)
+    });
+    match saved {
+        Ok(path) => {
+            remember_saved_output(&state, &path);
+            Ok(CommandResult::success(path.to_string_lossy().to_string()))
+        }
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to save output: {}",
+            e
+        ))),
+    }
+}
+
+// Add a generated output to the in-app clipboard history, dropping the oldest past the limit
+fn remember_output(
+    state: &AppState,
+    app_handle: &tauri::AppHandle,
+    root_dir: &Path,
+    output: &OutputContent,
+) {
+    let limit = load_app_settings(app_handle).clipboard_history_size;
+    let mut history = state.output_history.lock().unwrap();
+    if limit > 0 {
+        let item = OutputHistoryItem {
+            id: state.next_history_id.fetch_add(1, Ordering::SeqCst),
+            root_dir: root_dir.to_string_lossy().to_string(),
+            generated_at: chrono::Local::now().to_rfc3339(),
+            file_count: output.file_details.len(),
+            token_estimate: output.token_estimate,
+        };
+        history.push_front((item, output.combined_content.clone()));
+    }
+    history.truncate(limit);
+}
+
+// Command to list the outputs in the in-app clipboard history, newest first
+#[tauri::command]
+async fn get_clipboard_history(
+    state: tauri::State<'_, AppState>,
+) -> Result<CommandResult<Vec<OutputHistoryItem>>, String> {
+    let history = state.output_history.lock().unwrap();
+    Ok(CommandResult::success(
+        history.iter().map(|(item, _)| item.clone()).collect(),
+    ))
+}
+
+// Command to copy an earlier output from the history back to the clipboard. Like
+// copy_to_clipboard, content over the size limit needs `confirmed`.
+#[tauri::command]
+async fn copy_history_item(
+    id: u64,
+    confirmed: Option<bool>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<bool>, String> {
+    let content = state
+        .output_history
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(item, _)| item.id == id)
+        .map(|(_, content)| content.clone());
+    let Some(content) = content else {
+        return Ok(CommandResult::error(format!(
+            "Output {} is no longer in the history",
+            id
+        )));
+    };
+
+    Ok(write_clipboard(&app_handle, content, confirmed))
+}
+
+// Details of the "confirm_required" result copy_to_clipboard returns for large content
+#[derive(Debug, Clone, Serialize)]
+struct ClipboardSizeDetails {
+    bytes: usize,
+    limit_bytes: usize,
+}
+
+// The clipboard size limit from the settings, if there is one
+fn clipboard_limit_bytes(app_handle: &tauri::AppHandle) -> Option<usize> {
+    let limit_kb = load_app_settings(app_handle).clipboard_size_limit_kb;
+    (limit_kb > 0).then_some(limit_kb * 1024)
+}
+
+// Command to copy content to clipboard. Content over the clipboard size limit returns the
+// "confirm_required" code, so the frontend can offer saving to a file instead, until called
+// again with `confirmed`.
+#[tauri::command]
+async fn copy_to_clipboard(
+    app: tauri::AppHandle,
+    content: String,
+    confirmed: Option<bool>,
+) -> Result<CommandResult<bool>, String> {
+    Ok(write_clipboard(&app, content, confirmed))
+}
+
+// Copy `content` to the clipboard, asking for confirmation first if it's over the size limit
+fn write_clipboard(
+    app: &tauri::AppHandle,
+    content: String,
+    confirmed: Option<bool>,
+) -> CommandResult<bool> {
+    if !confirmed.unwrap_or(false) {
+        if let Some(limit_bytes) = clipboard_limit_bytes(app).filter(|limit| content.len() > *limit)
+        {
+            return CommandResult::error_with_details(
+                CODE_CONFIRM_REQUIRED,
+                format!(
+                    "The output is {}, over the {} clipboard limit, and clipboards this large can hang some clipboard managers. Copy it anyway?",
+                    fs::format_size(content.len() as u64),
+                    fs::format_size(limit_bytes as u64)
+                ),
+                ClipboardSizeDetails {
+                    bytes: content.len(),
+                    limit_bytes,
+                },
+            );
+        }
+    }
+
+    match app.clipboard().write_text(content) {
+        Ok(_) => CommandResult::success(true),
+        Err(e) => CommandResult::error(format!("Failed to copy to clipboard: {}", e)),
+    }
+}
+
+// Command to copy a tree node's path, either project-relative or absolute
+#[tauri::command]
+async fn copy_node_path(
+    path: String,
+    relative: bool,
+    forward_slashes: Option<bool>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<String>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+
+    let node_path = Path::new(&path);
+    let copied_path = if relative {
+        match node_path.strip_prefix(&current_dir) {
+            Ok(rel) if rel.as_os_str().is_empty() => ".".to_string(),
+            Ok(rel) => rel.to_string_lossy().to_string(),
+            Err(_) => {
+                return Ok(CommandResult::error(format!(
+                    "Path is not inside the current project: {}",
+                    path
+                )))
+            }
+        }
+    } else {
+        node_path.to_string_lossy().to_string()
+    };
+
+    let copied_path = if forward_slashes.unwrap_or(false) {
+        copied_path.replace('\\', "/")
+    } else {
+        copied_path
+    };
+
+    match app_handle.clipboard().write_text(copied_path.clone()) {
+        Ok(_) => Ok(CommandResult::success(copied_path)),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to copy to clipboard: {}",
+            e
+        ))),
+    }
+}
+
+// Command to open the output file
+#[tauri::command]
+async fn open_output_file(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<CommandResult<String>, String> {
+    // Log the path we are trying to open
+    println!("[GPTree] Attempting to open path: {}", path);
+
+    match open_with_fallbacks(&app, &path) {
+        Ok(method) => Ok(CommandResult::success(method.to_string())),
+        Err(e) => {
+            // Log the error as well
+            eprintln!("[GPTree] Failed to open path '{}': {}", path, e);
+            Ok(CommandResult::error(format!("Failed to open file: {}", e)))
+        }
+    }
+}
+
+// Open a file, trying each way in turn until one works: its default app, xdg-open on Linux
+// (some setups have no handler registered with the opener for .txt), the configured external
+// editor, then revealing it in the file manager.
+// Returns which one succeeded: "default_app", "xdg_open", "editor" or "file_manager".
+fn open_with_fallbacks(app_handle: &tauri::AppHandle, path: &str) -> Result<&'static str, String> {
+    let mut failures = Vec::new();
+
+    match app_handle.opener().open_path(path, None::<&str>) {
+        Ok(_) => return Ok("default_app"),
+        Err(e) => failures.push(format!("default app: {}", e)),
+    }
+
+    #[cfg(target_os = "linux")]
+    match std::process::Command::new("xdg-open").arg(path).status() {
+        Ok(status) if status.success() => return Ok("xdg_open"),
+        Ok(status) => failures.push(format!("xdg-open: exited with {}", status)),
+        Err(e) => failures.push(format!("xdg-open: {}", e)),
+    }
+
+    let template = load_app_settings(app_handle).external_editor;
+    let args = editor_command_args(&template, path, 1);
+    if let Some((program, program_args)) = args.split_first() {
+        match std::process::Command::new(program)
+            .args(program_args)
+            .spawn()
+        {
+            Ok(_) => return Ok("editor"),
+            Err(e) => failures.push(format!("editor '{}': {}", program, e)),
+        }
+    }
+
+    match app_handle.opener().reveal_item_in_dir(path) {
+        Ok(_) => Ok("file_manager"),
+        Err(e) => {
+            failures.push(format!("file manager: {}", e));
+            Err(failures.join("; "))
+        }
+    }
+}
+
+// Split an editor command template into arguments, filling in {path} and {line}.
+// Double quotes keep arguments with spaces together, e.g. `"C:\Program Files\Editor\ed.exe" {path}`.
+fn editor_command_args(template: &str, path: &str, line: u32) -> Vec<String> {
+    cli::split_command_template(template)
+        .into_iter()
+        .map(|arg| {
+            arg.replace("{path}", path)
+                .replace("{line}", &line.to_string())
+        })
+        .collect()
+}
+
+// Command to open a file in the configured external editor, at a line if given.
+// Relative paths (as shown in the output's file headers) are resolved against the project,
+// and only files inside the project can be opened.
+#[tauri::command]
+async fn open_in_editor(
+    path: String,
+    line: Option<u32>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<bool>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    let Ok(file_path) = StdFs::canonicalize(current_dir.join(&path)) else {
+        return Ok(CommandResult::error(format!("File not found: {}", path)));
+    };
+    // `path` comes from the webview; "../" or a symlink must not reach outside the project
+    let project_dir = StdFs::canonicalize(&current_dir).unwrap_or(current_dir.clone());
+    if !file_path.starts_with(&project_dir) {
+        return Ok(CommandResult::error(format!(
+            "{} is outside the project",
+            path
+        )));
+    }
+    let file_path = file_path.to_string_lossy().to_string();
+
+    let template = load_app_settings(&app_handle).external_editor;
+    if template.trim().is_empty() {
+        return match app_handle.opener().open_path(&file_path, None::<&str>) {
+            Ok(_) => Ok(CommandResult::success(true)),
+            Err(e) => Ok(CommandResult::error(format!("Failed to open file: {}", e))),
+        };
+    }
+
+    let args = editor_command_args(&template, &file_path, line.unwrap_or(1).max(1));
+    let Some((program, program_args)) = args.split_first() else {
+        return Ok(CommandResult::error(
+            "The external editor command is empty".to_string(),
+        ));
+    };
+    match std::process::Command::new(program)
+        .args(program_args)
+        .current_dir(&current_dir)
+        .spawn()
+    {
+        Ok(_) => Ok(CommandResult::success(true)),
+        Err(e) => {
+            eprintln!("[GPTree] Failed to start editor '{}': {}", program, e);
+            Ok(CommandResult::error(format!(
+                "Failed to start editor '{}': {}",
+                program, e
+            )))
+        }
+    }
+}
+
+// Note an output file the app wrote, so it can be trashed later
+fn remember_saved_output(state: &AppState, path: &Path) {
+    let path = StdFs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    state.saved_outputs.lock().unwrap().insert(path);
+}
+
+// Command to move an output file to the OS trash so deleting it can be undone. Only files
+// this app saved as outputs, or the current project's configured output file, qualify.
+#[tauri::command]
+async fn trash_output_file(
+    path: String,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<bool>, String> {
+    let Ok(canonical) = StdFs::canonicalize(&path) else {
+        return Ok(CommandResult::error(format!(
+            "Output file not found: {}",
+            path
+        )));
+    };
+    if !canonical.is_file() {
+        return Ok(CommandResult::error(format!(
+            "Output file not found: {}",
+            path
+        )));
+    }
+
+    let is_configured_output = current_project_dir(&state, &app_handle)
+        .ok()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .and_then(|dir| {
+            let config_mode = *state.config_mode.lock().unwrap();
+            let config = load_active_config(&dir, config_mode, &app_handle).ok()?;
+            StdFs::canonicalize(processor::resolve_output_path(&config, &dir).ok()?).ok()
+        })
+        .is_some_and(|configured| configured == canonical);
+    if !is_configured_output && !state.saved_outputs.lock().unwrap().contains(&canonical) {
+        return Ok(CommandResult::error_with_code(
+            CODE_NOT_AN_OUTPUT,
+            format!(
+                "Refusing to trash {}: it isn't an output GPTree saved",
+                path
+            ),
+        ));
+    }
+
+    match trash::delete(&canonical) {
+        Ok(_) => Ok(CommandResult::success(true)),
+        Err(e) => {
+            eprintln!("[GPTree] Failed to move '{}' to trash: {}", path, e);
+            Ok(CommandResult::error(format!(
+                "Failed to move file to trash: {}",
+                e
+            )))
+        }
+    }
+}
+
+// The directory passed on the command line, if there's one and it exists.
+// Flags (anything starting with "-") are skipped.
+fn launch_directory(args: &[String]) -> Option<PathBuf> {
+    let arg = args.iter().find(|arg| !arg.starts_with('-'))?;
+    match StdFs::canonicalize(arg) {
+        Ok(dir) if dir.is_dir() => Some(dir),
+        _ => {
+            eprintln!(
+                "[GPTree] Ignoring launch argument, not a directory: {}",
+                arg
+            );
+            None
+        }
+    }
+}
+
+// Make a directory from the command line the current project: the frontend's startup
+// reopens the session's last directory, and the "open-directory" event covers a frontend
+// that is already running
+fn open_launch_directory(app_handle: &tauri::AppHandle, dir: &Path) {
+    *app_handle.state::<AppState>().current_dir.lock().unwrap() = dir.to_path_buf();
+
+    let path = dir.to_string_lossy().to_string();
+    let mut session = config::load_session_state(app_handle).unwrap_or_default();
+    session.last_directory = Some(path.clone());
+    if let Err(e) = config::save_session_state(app_handle, &session) {
+        eprintln!("[GPTree] Failed to save launch directory to session: {}", e);
+    }
+    if let Err(e) = app_handle.emit("open-directory", OpenDirectoryPayload { path }) {
+        eprintln!("[GPTree] Failed to emit open-directory: {}", e);
+    }
+}
+
+// Forget the last folder and config mode, and with `include_settings` also put the app
+// settings back to their defaults (keeping a settings.json.bak copy)
+fn reset_saved_state(
+    app_handle: &tauri::AppHandle,
+    include_settings: bool,
+) -> Result<(), AppError> {
+    config::save_session_state(app_handle, &SessionState::default())?;
+    if include_settings {
+        let settings_path = get_settings_path(app_handle)?;
+        if settings_path.exists() {
+            backup_settings_file(&settings_path);
+        }
+        write_settings_file(&settings_path, &AppSettings::default())?;
+    }
+    Ok(())
+}
+
+// Command to forget the last opened folder (and optionally reset the app settings), so the
+// next launch starts fresh. The folder that's open now stays open.
+#[tauri::command]
+async fn reset_session(
+    include_settings: Option<bool>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<()>, String> {
+    let include_settings = include_settings.unwrap_or(false);
+    if let Err(e) = reset_saved_state(&app_handle, include_settings) {
+        return Ok(CommandResult::error(format!(
+            "Failed to reset session: {}",
+            e
+        )));
+    }
+
+    if include_settings {
+        if let Err(e) = refresh_app_menu(&app_handle) {
+            eprintln!("[GPTree] Failed to rebuild application menu: {}", e);
+        }
+    }
+    Ok(CommandResult::success(()))
+}
+
+// Command to get session state
+#[tauri::command]
+async fn get_session_state(
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<SessionState>, String> {
+    match config::load_session_state(&app_handle) {
+        Ok(state) => Ok(CommandResult::success(state)),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to load session state: {}",
+            e
+        ))),
+    }
+}
+
+// Command to take a quick, bounded look at the remembered last directory before it's
+// loaded, so a stale or enormous path can be declined first. A directory that no longer
+// exists is forgotten.
+#[tauri::command]
+async fn probe_last_directory(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Option<StartupProbe>>, String> {
+    let last_directory = config::load_session_state(&app_handle)
+        .ok()
+        .and_then(|session| session.last_directory);
+    let Some(path) = last_directory else {
+        return Ok(CommandResult::success(None));
+    };
+
+    // Even checking that a path exists can hang on an unreachable network drive,
+    // so the whole probe runs on a blocking thread with a time limit
+    let dir = PathBuf::from(&path);
+    let probe_dir = dir.clone();
+    let probe_handle = app_handle.clone();
+    let probed = tokio::time::timeout(
+        std::time::Duration::from_millis(STARTUP_PROBE_TIMEOUT_MS),
+        tauri::async_runtime::spawn_blocking(move || {
+            let has_index = index::has_index(&probe_handle, &probe_dir);
+            let entries = probe_dir.is_dir().then(|| {
+                let default_ignores = scan_default_ignores(&probe_dir, &probe_handle);
+                fs::count_entries(
+                    &probe_dir,
+                    true,
+                    &default_ignores,
+                    LARGE_SCAN_ENTRY_LIMIT + 1,
+                )
+            });
+            (has_index, entries)
+        }),
+    )
+    .await;
+
+    let probe = match probed {
+        Ok(Ok((has_index, entries))) => StartupProbe {
+            path,
+            exists: entries.is_some(),
+            entry_estimate: entries.unwrap_or(0),
+            is_large: entries.is_some_and(|count| count > LARGE_SCAN_ENTRY_LIMIT),
+            timed_out: false,
+            has_index,
+        },
+        Ok(Err(e)) => {
+            return Ok(CommandResult::error(format!(
+                "Failed to probe last directory: {}",
+                e
+            )))
+        }
+        Err(_) => StartupProbe {
+            path,
+            exists: true,
+            entry_estimate: 0,
+            is_large: false,
+            timed_out: true,
+            has_index: false,
+        },
+    };
+
+    if !probe.exists {
+        forget_missing_project(&dir, &state, &app_handle);
+    }
+    Ok(CommandResult::success(Some(probe)))
+}
+
+// New command: set_config_mode
+#[tauri::command]
+async fn set_config_mode(
+    mode: String,
+    confirmed: Option<bool>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<bool>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+
+    // Saving a local config somewhere that isn't a project leaves a stray file behind,
+    // so ask the frontend to confirm first
+    if mode == "local"
+        && !config::project_config_exists(&current_dir)
+        && !fs::is_project_root(&current_dir)
+        && !confirmed.unwrap_or(false)
+    {
+        return Ok(CommandResult::error_with_code(
+            CODE_CONFIRM_REQUIRED,
+            format!(
+                "{} doesn't look like a project root (no version control or project manifest found). Use a local config here anyway? It's created when you first save.",
+                current_dir.to_string_lossy()
+            ),
+        ));
+    }
+
+    // Update config mode in state
+    let mut config_mode = state.config_mode.lock().unwrap();
+    *config_mode = match mode.as_str() {
+        "local" => ConfigMode::LocalOverride,
+        _ => ConfigMode::Global,
+    };
+
+    // The project config is only written on the first save; the global one is created here
+    if mode != "local" {
+        if let Err(e) = load_global_config(&app_handle) {
+            return Ok(CommandResult::error(format!(
+                "Failed to create global config: {}",
+                e
+            )));
+        }
+    }
+
+    // Update session state
+    match config::load_session_state(&app_handle) {
+        Ok(mut state) => {
+            state.last_config_mode = Some(mode.clone());
+            if let Err(e) = config::save_session_state(&app_handle, &state) {
+                eprintln!("[GPTree] Warning: Failed to save session state: {}", e);
+            }
+        }
+        Err(e) => {
+            eprintln!("[GPTree] Warning: Failed to load session state: {}", e);
+        }
+    }
+
+    Ok(CommandResult::success(true))
+}
+
+// New command: get_configs
+#[tauri::command]
+async fn get_configs(
+    path: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<serde_json::Value>, String> {
+    use serde_json::json;
+    let global = load_global_config(&app_handle).ok();
+    let local = if let Some(path) = path {
+        let p = Path::new(&path);
+        config::load_project_config(p, &new_project_config(&app_handle)).ok()
+    } else {
+        None
+    };
+    Ok(CommandResult::success(json!({
+        "global": global,
+        "local": local,
+    })))
+}
+
+#[tauri::command]
+async fn pick_save_path(
+    window: tauri::Window,
+    state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle,
+    content: String,
+) -> Result<CommandResult<String>, String> {
+    use std::fs;
+    use std::path::PathBuf;
+    use tauri_plugin_dialog::DialogExt;
+
+    println!("Starting pick_save_path function");
+
+    let Some(_dialog) = begin_dialog(&state, &window) else {
+        return Ok(dialog_busy());
+    };
+
+    // Add a default text extension filter
+    let file_path = app
+        .dialog()
+        .file()
+        .add_filter("Text Files", &["txt"])
+        .set_file_name("gptree-output.txt")
+        .blocking_save_file();
+
+    println!("File dialog returned: {:?}", file_path);
+
+    match file_path {
+        Some(path) => {
+            let path_str = path.to_string();
+            println!("Path to string: {}", path_str);
+
+            // Try to convert the FilePath to a PathBuf
+            let path_buf = PathBuf::from(path_str.clone());
+            println!("Created PathBuf: {:?}", path_buf);
+
+            // Write content to the selected file
+            match fs::write(&path_buf, content) {
+                Ok(_) => {
+                    println!("File written successfully to {:?}", path_buf);
+                    Ok(CommandResult::success(path_str))
+                }
+                Err(e) => {
+                    println!("Error writing file: {}", e);
+                    Ok(CommandResult::error(format!("Failed to write file: {}", e)))
+                }
+            }
+        }
+        None => {
+            println!("File save operation was cancelled");
+            Ok(CommandResult::error(
+                "File save operation was cancelled".to_string(),
+            ))
+        }
+    }
+}
+
+// Read the app settings for use on the Rust side, falling back to defaults
+fn load_app_settings(app_handle: &tauri::AppHandle) -> AppSettings {
+    let Ok(settings_path) = get_settings_path(app_handle) else {
+        return AppSettings::default();
+    };
+    match StdFs::read_to_string(&settings_path) {
+        Ok(content) => settings_from_content(&settings_path, &content),
+        Err(_) => AppSettings::default(),
+    }
+}
+
+// Bring a settings.json value written by an older version up to SETTINGS_VERSION
+fn migrate_settings(mut value: serde_json::Value) -> serde_json::Value {
+    let Some(fields) = value.as_object_mut() else {
+        return value;
+    };
+    // Files from before settings were versioned have no settingsVersion; their layout is version 1
+    fields.insert("settingsVersion".to_string(), SETTINGS_VERSION.into());
+    value
+}
+
+// Parse settings.json without ever discarding the user's preferences wholesale: older layouts
+// are migrated, and if some fields no longer parse only those fall back to their defaults.
+// Migrated or repaired settings are written back, after backing up a file that lost fields.
+fn settings_from_content(settings_path: &Path, content: &str) -> AppSettings {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(content) else {
+        eprintln!(
+            "[GPTree] Warning: Settings file {:?} isn't valid JSON; using defaults",
+            settings_path
+        );
+        backup_settings_file(settings_path);
+        return AppSettings::default();
+    };
+
+    let version = value
+        .get("settingsVersion")
+        .and_then(serde_json::Value::as_u64)
+        .unwrap_or(0);
+    let migrating = version < SETTINGS_VERSION as u64;
+    let value = if migrating {
+        migrate_settings(value)
+    } else {
+        value
+    };
+
+    if let Ok(settings) = serde_json::from_value::<AppSettings>(value.clone()) {
+        if migrating {
+            if let Err(e) = write_settings_file(settings_path, &settings) {
+                eprintln!("[GPTree] Failed to save migrated settings: {}", e);
+            }
+        }
+        return settings;
+    }
+
+    // Keep each field that still parses on its own, one at a time on top of the defaults
+    let mut salvaged = serde_json::to_value(AppSettings::default()).unwrap_or_default();
+    let mut dropped = Vec::new();
+    if let (Some(fields), Some(target)) = (value.as_object(), salvaged.as_object_mut()) {
+        for (key, field) in fields {
+            let previous = target.insert(key.clone(), field.clone());
+            let candidate = serde_json::Value::Object(target.clone());
+            if serde_json::from_value::<AppSettings>(candidate).is_err() {
+                dropped.push(key.clone());
+                match previous {
+                    Some(previous) => target.insert(key.clone(), previous),
+                    None => target.remove(key),
+                };
+            }
+        }
+    }
+    let settings: AppSettings = serde_json::from_value(salvaged).unwrap_or_default();
+
+    eprintln!(
+        "[GPTree] Warning: Reset unreadable settings to their defaults: {}",
+        dropped.join(", ")
+    );
+    backup_settings_file(settings_path);
+    if let Err(e) = write_settings_file(settings_path, &settings) {
+        eprintln!("[GPTree] Failed to save repaired settings: {}", e);
+    }
+    settings
+}
+
+// Copy a settings file that couldn't be read in full to settings.json.bak before replacing it
+fn backup_settings_file(settings_path: &Path) {
+    let backup_path = settings_path.with_extension("json.bak");
+    match StdFs::copy(settings_path, &backup_path) {
+        Ok(_) => eprintln!("[GPTree] Backed up settings to {:?}", backup_path),
+        Err(e) => eprintln!("[GPTree] Failed to back up settings: {}", e),
+    }
+}
+
+// Work out the thread limits to use right now from the app settings
+fn resource_limits(app_handle: &tauri::AppHandle) -> ResourceLimits {
+    let settings = load_app_settings(app_handle);
+    let low_priority = match settings.io_priority {
+        IoPriority::Normal => false,
+        IoPriority::Low => true,
+        IoPriority::LowOnBattery => power::on_battery(),
+    };
+    if low_priority {
+        return ResourceLimits {
+            scan_threads: 1,
+            read_concurrency: 1,
+        };
+    }
+
+    let auto = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(MAX_AUTO_THREADS);
+    let resolve = |configured: usize| if configured == 0 { auto } else { configured };
+    ResourceLimits {
+        scan_threads: resolve(settings.scan_threads),
+        read_concurrency: resolve(settings.read_concurrency),
+    }
+}
+
+// Write the app settings file
+fn store_app_settings(
+    app_handle: &tauri::AppHandle,
+    settings: &AppSettings,
+) -> Result<(), AppError> {
+    write_settings_file(&get_settings_path(app_handle)?, settings)
+}
+
+fn write_settings_file(settings_path: &Path, settings: &AppSettings) -> Result<(), AppError> {
+    let content =
+        serde_json::to_string_pretty(settings).map_err(|e| AppError::Json(e.to_string()))?;
+    StdFs::write(settings_path, content)?;
+    Ok(())
+}
+
+// Show a desktop notification for an operation that took longer than the configured
+// threshold, unless the window is focused and the user saw it finish anyway
+fn notify_if_slow(app_handle: &tauri::AppHandle, started: Instant, body: String) {
+    let settings = load_app_settings(app_handle);
+    if !settings.notify_on_long_operations
+        || started.elapsed().as_secs() < settings.long_operation_threshold_secs
+    {
+        return;
+    }
+
+    let focused = app_handle
+        .get_webview_window("main")
+        .and_then(|window| window.is_focused().ok())
+        .unwrap_or(false);
+    if focused {
+        return;
+    }
+
+    if let Err(e) = app_handle
+        .notification()
+        .builder()
+        .title("GPTree")
+        .body(body)
+        .show()
+    {
+        eprintln!("[GPTree] Failed to show notification: {}", e);
+    }
+}
+
+// Shows an operation's progress on the taskbar button (Windows) or dock icon (macOS, where it
+// also gets a percentage badge) so it stays visible while minimized. Cleared when dropped.
+struct TaskbarProgress {
+    window: Option<tauri::WebviewWindow>,
+}
+
+impl TaskbarProgress {
+    // Start with an indeterminate indicator until there's something to count
+    fn start(app_handle: &tauri::AppHandle) -> Self {
+        let progress = Self {
+            window: app_handle.get_webview_window("main"),
+        };
+        progress.set_state(ProgressBarState {
+            status: Some(ProgressBarStatus::Indeterminate),
+            progress: None,
+        });
+        progress
+    }
+
+    fn set(&self, done: usize, total: usize) {
+        let percent = (done * 100).checked_div(total).unwrap_or(0) as u64;
+        self.set_state(ProgressBarState {
+            status: Some(ProgressBarStatus::Normal),
+            progress: Some(percent),
+        });
+
+        #[cfg(target_os = "macos")]
+        if let Some(window) = &self.window {
+            let _ = window.set_badge_label(Some(format!("{}%", percent)));
+        }
+    }
+
+    fn set_state(&self, state: ProgressBarState) {
+        if let Some(window) = &self.window {
+            if let Err(e) = window.set_progress_bar(state) {
+                eprintln!("[GPTree] Failed to update taskbar progress: {}", e);
+            }
+        }
+    }
+}
+
+impl Drop for TaskbarProgress {
+    fn drop(&mut self) {
+        self.set_state(ProgressBarState {
+            status: Some(ProgressBarStatus::None),
+            progress: None,
+        });
+
+        #[cfg(target_os = "macos")]
+        if let Some(window) = &self.window {
+            let _ = window.set_badge_label(None);
+        }
+    }
+}
+
+// Format a token count the way it's shown in notifications, e.g. 84k
+fn format_token_count(tokens: usize) -> String {
+    if tokens >= 1000 {
+        format!("{}k", (tokens + 500) / 1000)
+    } else {
+        tokens.to_string()
+    }
+}
+
+// Command to get application settings
+#[tauri::command]
+async fn get_app_settings(
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<AppSettings>, String> {
+    let settings_path = match get_settings_path(&app_handle) {
+        Ok(path) => path,
+        Err(e) => {
+            return Ok(CommandResult::error(format!(
+                "Error getting settings path: {}",
+                e
+            )))
+        }
+    };
+
+    if !settings_path.exists() {
+        println!(
+            "[GPTree] Settings file not found at {:?}, returning defaults.",
+            settings_path
+        );
+        return Ok(CommandResult::success(AppSettings::default()));
+    }
+
+    match StdFs::read_to_string(&settings_path) {
+        Ok(content) => Ok(CommandResult::success(settings_from_content(
+            &settings_path,
+            &content,
+        ))),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to read settings file {:?}: {}",
+            settings_path, e
+        ))),
+    }
+}
+
+// Command to save application settings
+#[tauri::command]
+async fn save_app_settings(
+    app_handle: tauri::AppHandle,
+    settings: AppSettings,
+) -> Result<CommandResult<bool>, String> {
+    let settings_path = match get_settings_path(&app_handle) {
+        Ok(path) => path,
+        Err(e) => {
+            return Ok(CommandResult::error(format!(
+                "Error getting settings path: {}",
+                e
+            )))
+        }
+    };
+
+    match serde_json::to_string_pretty(&settings) {
+        Ok(content) => match StdFs::write(&settings_path, content) {
+            Ok(_) => {
+                // Shortcuts may have changed
+                if let Err(e) = refresh_app_menu(&app_handle) {
+                    eprintln!("[GPTree] Failed to refresh application menu: {}", e);
                 }
+                Ok(CommandResult::success(true))
             }
+            Err(e) => Ok(CommandResult::error(format!(
+                "Failed to write settings file {:?}: {}",
+                settings_path, e
+            ))),
+        },
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to serialize settings: {}",
+            e
+        ))),
+    }
+}
+
+// Current OS theme, falling back to the main window's theme where the OS setting can't be read
+fn current_system_theme(app_handle: &tauri::AppHandle) -> theme::SystemTheme {
+    let window_dark = app_handle
+        .get_webview_window("main")
+        .and_then(|window| window.theme().ok())
+        .is_some_and(|theme| theme == tauri::Theme::Dark);
+    theme::system_theme(window_dark)
+}
+
+// Command to get the OS dark/light preference and accent color
+#[tauri::command]
+async fn get_system_theme(
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<theme::SystemTheme>, String> {
+    Ok(CommandResult::success(current_system_theme(&app_handle)))
+}
+
+// Command to match the native titlebar to the UI: "dark", "light", or "system" to follow the OS
+#[tauri::command]
+async fn set_window_theme(
+    theme: String,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<bool>, String> {
+    let window_theme = match theme.as_str() {
+        "dark" => Some(tauri::Theme::Dark),
+        "light" => Some(tauri::Theme::Light),
+        "system" => None,
+        other => return Ok(CommandResult::error(format!("Unknown theme: {}", other))),
+    };
+    let Some(window) = app_handle.get_webview_window("main") else {
+        return Ok(CommandResult::error("Main window not found".to_string()));
+    };
+    match window.set_theme(window_theme) {
+        Ok(_) => Ok(CommandResult::success(true)),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to set window theme: {}",
+            e
+        ))),
+    }
+}
+
+// Command to change the keyboard shortcut of a menu action.
+// `None` restores the default and an empty string removes the shortcut.
+#[tauri::command]
+async fn set_shortcut(
+    action: String,
+    accelerator: Option<String>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<AppSettings>, String> {
+    let Some(&(_, default_accelerator)) = DEFAULT_SHORTCUTS.iter().find(|(a, _)| *a == action)
+    else {
+        return Ok(CommandResult::error(format!(
+            "Unknown shortcut action '{}'",
+            action
+        )));
+    };
+
+    let previous = load_app_settings(&app_handle);
+    let mut settings = previous.clone();
+    settings.shortcuts.insert(
+        action.clone(),
+        accelerator.unwrap_or_else(|| default_accelerator.to_string()),
+    );
+
+    if let Err(e) = store_app_settings(&app_handle, &settings) {
+        return Ok(CommandResult::error(format!(
+            "Failed to save settings: {}",
+            e
+        )));
+    }
+
+    // An accelerator the menu can't parse fails here, so put the old settings back
+    if let Err(e) = refresh_app_menu(&app_handle) {
+        if let Err(restore_error) = store_app_settings(&app_handle, &previous) {
+            eprintln!("[GPTree] Failed to restore settings: {}", restore_error);
+        }
+        let _ = refresh_app_menu(&app_handle);
+        return Ok(CommandResult::error(format!(
+            "Invalid shortcut for '{}': {}",
+            action, e
+        )));
+    }
+
+    Ok(CommandResult::success(settings))
+}
+
+// Command to set the last used config mode preference
+#[tauri::command]
+async fn set_last_config_mode(
+    app_handle: tauri::AppHandle,
+    mode: String,
+) -> Result<CommandResult<bool>, String> {
+    // Validate mode
+    if mode != "global" && mode != "local" {
+        return Ok(CommandResult::error(format!(
+            "Invalid config mode '{}' provided. Must be 'global' or 'local'.",
+            mode
+        )));
+    }
+
+    // Load current session state or defaults
+    let mut current_state = match config::load_session_state(&app_handle) {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!(
+                "[GPTree] Warning: Failed to load session state to update mode: {}. Using default.",
+                e
+            );
+            SessionState::default()
+        }
+    };
 
-            // Process the output (save to file) and get the saved path
-            match processor::process_output(&output, &config, &current_dir) {
-                Ok(saved_path_option) => {
-                    // Store the absolute path (or None) in the output object
-                    output.saved_path = saved_path_option;
-                }
-                Err(e) => {
-                    // Log the error but don't prevent returning the content
-                    eprintln!("Warning: Failed to save output file: {}", e);
-                    output.saved_path = None; // Indicate that saving failed
-                }
-            }
+    // Update the mode
+    current_state.last_config_mode = Some(mode);
 
-            Ok(CommandResult::success(output))
+    // Save the updated state
+    match config::save_session_state(&app_handle, &current_state) {
+        Ok(_) => Ok(CommandResult::success(true)),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Error saving updated session state: {}",
+            e
+        ))),
+    }
+}
+
+// New command: diagnose_config_file
+#[tauri::command]
+async fn diagnose_config_file(
+    mode: String,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<String>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+
+    let config_path = if mode == "local" {
+        config::project_config_path(&current_dir)
+    } else {
+        dirs::home_dir()
+            .ok_or_else(|| "Could not find home directory".to_string())?
+            .join(".gptreerc")
+    };
+
+    let diagnosis = config::diagnose_config_file_access(&config_path);
+    Ok(CommandResult::success(diagnosis))
+}
+
+// Command to see how the current selection has drifted from the previous_files saved in the
+// project config
+#[tauri::command]
+async fn get_selection_drift(
+    selected_files: Vec<String>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<SelectionDrift>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+
+    let config_path = config::project_config_path(&current_dir);
+    if !config_path.exists() {
+        return Ok(CommandResult::error(
+            "This project has no config with previous files to compare against".to_string(),
+        ));
+    }
+    match config::load_config(&config_path) {
+        Ok(project_config) => Ok(CommandResult::success(config::selection_drift(
+            &project_config,
+            &selected_files,
+            &current_dir,
+        ))),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to load project config: {}",
+            e
+        ))),
+    }
+}
+
+// Command to rank the files most worth including when first building context for a project
+#[tauri::command]
+async fn suggest_key_files(
+    limit: Option<usize>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<KeyFileSuggestion>>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    let config_mode = *state.config_mode.lock().unwrap();
+
+    let config = match load_active_config(&current_dir, config_mode, &app_handle) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            return Ok(CommandResult::error(format!(
+                "Failed to load active config: {}",
+                e
+            )))
         }
+    };
+    let excluded_dirs_set: HashSet<String> = config.exclude_dirs.iter().cloned().collect();
+
+    match fs::suggest_key_files(
+        &current_dir,
+        &config,
+        &excluded_dirs_set,
+        limit.unwrap_or(DEFAULT_KEY_FILE_LIMIT),
+    ) {
+        Ok(suggestions) => Ok(CommandResult::success(suggestions)),
         Err(e) => Ok(CommandResult::error(format!(
-            "Failed to generate output: {}",
+            "Failed to suggest key files: {}",
             e
         ))),
     }
 }
 
-// Command to copy content to clipboard
+// Command to gather the files for the "infra context" selection group: CI workflows,
+// Dockerfiles, Terraform and deployment manifests, wherever they are in the project
 #[tauri::command]
-async fn copy_to_clipboard(
-    app: tauri::AppHandle,
-    content: String,
-) -> Result<CommandResult<bool>, String> {
-    match app.clipboard().write_text(content) {
-        Ok(_) => Ok(CommandResult::success(true)),
+async fn get_infra_context_files(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<InfraFile>>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    let config_mode = *state.config_mode.lock().unwrap();
+
+    let config = match load_active_config(&current_dir, config_mode, &app_handle) {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            return Ok(CommandResult::error(format!(
+                "Failed to load active config: {}",
+                e
+            )))
+        }
+    };
+    let excluded_dirs_set: HashSet<String> = config.exclude_dirs.iter().cloned().collect();
+
+    match fs::infra_context_files(&current_dir, &config, &excluded_dirs_set) {
+        Ok(files) => Ok(CommandResult::success(files)),
         Err(e) => Ok(CommandResult::error(format!(
-            "Failed to copy to clipboard: {}",
+            "Failed to gather infra files: {}",
             e
         ))),
     }
 }
 
-// Command to open the output file
+// Command to get per-directory file, size and token totals for rendering a treemap.
+// Uses the project index, so it reflects the last scan.
 #[tauri::command]
-async fn open_output_file(
-    app: tauri::AppHandle,
+async fn get_directory_heatmap(
+    max_depth: Option<usize>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<DirectoryHeatmap>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+
+    match index::load_index(&app_handle, &current_dir) {
+        Some(project_index) => Ok(CommandResult::success(index::directory_heatmap(
+            &project_index,
+            max_depth.unwrap_or(DEFAULT_HEATMAP_DEPTH),
+        ))),
+        None => Ok(CommandResult::error(
+            "The project hasn't been scanned yet".to_string(),
+        )),
+    }
+}
+
+// Command to move the project's GPTree files into a single `.gptree/` folder
+#[tauri::command]
+async fn migrate_project_dir(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<String>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+
+    match migrate_current_project(&state, &app_handle, &current_dir) {
+        Ok(config_path) => Ok(CommandResult::success(
+            config_path.to_string_lossy().to_string(),
+        )),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to move project files into .gptree: {}",
+            e
+        ))),
+    }
+}
+
+// Move the loaded project into the .gptree/ layout, watching the config at its new place
+fn migrate_current_project(
+    state: &AppState,
+    app_handle: &tauri::AppHandle,
+    current_dir: &Path,
+) -> Result<PathBuf, AppError> {
+    // Stop watching first so moving the config file doesn't look like a deletion
+    *state.config_watcher.lock().unwrap() = None;
+    let migrated = config::migrate_to_project_dir(current_dir);
+    let watch_global = !load_app_settings(app_handle).protect_global_config;
+    match watcher::watch_config_files(app_handle.clone(), Some(current_dir), watch_global) {
+        Ok(config_watcher) => *state.config_watcher.lock().unwrap() = Some(config_watcher),
+        Err(e) => eprintln!("[GPTree] Failed to watch config files: {}", e),
+    }
+    migrated
+}
+
+// Command to list the selection presets saved in the project's .gptree/presets/ folder
+#[tauri::command]
+async fn list_presets(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<BatchPreset>>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    Ok(CommandResult::success(config::list_presets(&current_dir)))
+}
+
+// Command to save a selection preset for generate_batch into .gptree/presets/, moving the
+// project into the .gptree/ layout first if it isn't yet
+#[tauri::command]
+async fn save_preset(
+    preset: BatchPreset,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<BatchPreset>>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    let saved = if config::uses_project_dir(&current_dir) {
+        Ok(())
+    } else {
+        migrate_current_project(&state, &app_handle, &current_dir).map(|_| ())
+    }
+    .and_then(|_| config::save_preset(&current_dir, &preset));
+    match saved {
+        Ok(()) => Ok(CommandResult::success(config::list_presets(&current_dir))),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to save preset: {}",
+            e
+        ))),
+    }
+}
+
+// Command to delete a saved selection preset
+#[tauri::command]
+async fn delete_preset(
+    name: String,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<BatchPreset>>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    match config::delete_preset(&current_dir, &name) {
+        Ok(()) => Ok(CommandResult::success(config::list_presets(&current_dir))),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to delete preset: {}",
+            e
+        ))),
+    }
+}
+
+// Command to report the token usage a provider returned for an output, so later estimates for
+// that model can be scaled to match. `estimated_tokens` is the output's uncalibrated_tokens.
+#[tauri::command]
+async fn record_token_usage(
+    model: String,
+    estimated_tokens: usize,
+    actual_tokens: usize,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<TokenCalibration>, String> {
+    match calibration::record_usage(&app_handle, &model, estimated_tokens, actual_tokens) {
+        Ok(calibration) => Ok(CommandResult::success(calibration)),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to record token usage: {}",
+            e
+        ))),
+    }
+}
+
+// Command to list the calibration factors learned from reported usage, keyed by model
+#[tauri::command]
+async fn get_token_calibrations(
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<HashMap<String, TokenCalibration>>, String> {
+    Ok(CommandResult::success(calibration::load_calibrations(
+        &app_handle,
+    )))
+}
+
+// Command to add GPTree's config, .gptree/ folder and in-project output file to the project's
+// .gitignore. Without `confirmed`, asks first and lists the entries in the error details.
+// With `offer`, as sent after saving into the project, only asks once per git project and only
+// about files GPTree has actually written. Returns the entries added.
+#[tauri::command]
+async fn add_gptree_to_gitignore(
+    confirmed: Option<bool>,
+    offer: Option<bool>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<String>>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    let config_mode = *state.config_mode.lock().unwrap();
+    let output_path = load_active_config(&current_dir, config_mode, &app_handle)
+        .and_then(|config| processor::resolve_output_path(&config, &current_dir))
+        .unwrap_or_default();
+
+    let mut entries = config::missing_gitignore_entries(&current_dir, &output_path);
+    if offer.unwrap_or(false) {
+        entries.retain(|entry| current_dir.join(entry.trim_end_matches('/')).exists());
+    }
+    if offer.unwrap_or(false) && !confirmed.unwrap_or(false) {
+        if entries.is_empty() || git::find_repo_root(&current_dir).is_none() {
+            return Ok(CommandResult::success(Vec::new()));
+        }
+        let project = current_dir.to_string_lossy().to_string();
+        let mut session = config::load_session_state(&app_handle).unwrap_or_default();
+        if session.gitignore_offered.contains(&project) {
+            return Ok(CommandResult::success(Vec::new()));
+        }
+        session.gitignore_offered.push(project);
+        if let Err(e) = config::save_session_state(&app_handle, &session) {
+            eprintln!("[GPTree] Warning: Failed to save session state: {}", e);
+        }
+    }
+    if entries.is_empty() {
+        return Ok(CommandResult::success(entries));
+    }
+    if !confirmed.unwrap_or(false) {
+        return Ok(CommandResult::error_with_details(
+            CODE_CONFIRM_REQUIRED,
+            format!("Add {} to the project's .gitignore?", entries.join(", ")),
+            entries,
+        ));
+    }
+
+    match config::append_gitignore_entries(&current_dir, &entries) {
+        Ok(()) => Ok(CommandResult::success(entries)),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to update .gitignore: {}",
+            e
+        ))),
+    }
+}
+
+// Serialize a command's result for collection with get_task_result
+fn task_result_value<T: Serialize>(result: Result<CommandResult<T>, String>) -> serde_json::Value {
+    let value = match result {
+        Ok(result) => serde_json::to_value(result),
+        Err(e) => serde_json::to_value(CommandResult::<()>::error(e)),
+    };
+    value.unwrap_or_else(|e| serde_json::json!({ "success": false, "error": e.to_string() }))
+}
+
+// Command to load a directory as a background task. Returns the task id at once;
+// the load_directory result is collected with get_task_result after "task-finished".
+#[tauri::command]
+async fn start_load_directory(
     path: String,
+    flat: Option<bool>,
+    confirmed: Option<bool>,
+    allow_dangerous_root: Option<bool>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<u64>, String> {
+    let task = state.tasks.start("load_directory", &app_handle);
+    let id = task.id();
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<AppState>();
+        let result = load_directory_result(
+            path,
+            flat,
+            confirmed,
+            allow_dangerous_root,
+            &state,
+            app_handle.clone(),
+            Some(task.cancel_flag()),
+        )
+        .await;
+        state.tasks.finish(&task, task_result_value(result));
+    });
+    Ok(CommandResult::success(id))
+}
+
+// Command to generate output as a background task, reporting progress per file.
+// The generate_output result is collected with get_task_result after "task-finished".
+#[tauri::command]
+async fn start_generate_output(
+    selected_files: Vec<String>,
+    excluded_dirs: Vec<String>,
+    options: Option<GenerationOptions>,
+    allow_dangerous_root: Option<bool>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<u64>, String> {
+    let task = state.tasks.start("generate", &app_handle);
+    let id = task.id();
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<AppState>();
+        let result = generate_output_result(
+            selected_files,
+            excluded_dirs,
+            options,
+            allow_dangerous_root,
+            &state,
+            app_handle.clone(),
+            Some(&task),
+        )
+        .await;
+        state.tasks.finish(&task, task_result_value(result));
+    });
+    Ok(CommandResult::success(id))
+}
+
+// Command to clone and load a remote repository as a background task. The load_remote_repo
+// result is collected with get_task_result after "task-finished".
+#[tauri::command]
+async fn start_load_remote_repo(
+    url: String,
+    git_ref: Option<String>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<u64>, String> {
+    let task = state.tasks.start("load_remote_repo", &app_handle);
+    let id = task.id();
+    tauri::async_runtime::spawn(async move {
+        let state = app_handle.state::<AppState>();
+        let result =
+            load_remote_repo_result(url, git_ref, &state, app_handle.clone(), Some(&task)).await;
+        state.tasks.finish(&task, task_result_value(result));
+    });
+    Ok(CommandResult::success(id))
+}
+
+// Command to cancel a running background task
+#[tauri::command]
+async fn cancel_task(
+    id: u64,
+    state: tauri::State<'_, AppState>,
 ) -> Result<CommandResult<bool>, String> {
-    // Log the path we are trying to open
-    println!("[GPTree] Attempting to open path: {}", path);
+    // Each task polls its own flag, so this stops only the task asked for
+    Ok(CommandResult::success(state.tasks.cancel(id)))
+}
 
-    match app.opener().open_path(&path, None::<&str>) {
-        Ok(_) => Ok(CommandResult::success(true)),
-        Err(e) => {
-            // Log the error as well
-            eprintln!("[GPTree] Failed to open path '{}': {}", path, e);
-            Ok(CommandResult::error(format!("Failed to open file: {}", e)))
+// Command to list background tasks that are running or have uncollected results
+#[tauri::command]
+async fn list_tasks(
+    state: tauri::State<'_, AppState>,
+) -> Result<CommandResult<Vec<tasks::TaskInfo>>, String> {
+    Ok(CommandResult::success(state.tasks.list()))
+}
+
+// Command to collect a finished task's result: the CommandResult its blocking command
+// would have returned. Each result can be collected once.
+#[tauri::command]
+async fn get_task_result(
+    id: u64,
+    state: tauri::State<'_, AppState>,
+) -> Result<CommandResult<serde_json::Value>, String> {
+    match state.tasks.take_result(id) {
+        Some(result) => Ok(CommandResult::success(result)),
+        None => Ok(CommandResult::error(format!(
+            "Task {} is still running or unknown",
+            id
+        ))),
+    }
+}
+
+// Command to sample a directory's file sizes before loading it, so excludes can be
+// suggested up front
+#[tauri::command]
+async fn quick_size_histogram(
+    path: String,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<SizeHistogram>, String> {
+    let root = PathBuf::from(&path);
+    if !root.is_dir() {
+        return Ok(CommandResult::error(format!("Not a directory: {}", path)));
+    }
+
+    let default_ignores = scan_default_ignores(&root, &app_handle);
+    match tauri::async_runtime::spawn_blocking(move || {
+        fs::quick_size_histogram(&root, &default_ignores, HISTOGRAM_FILE_LIMIT)
+    })
+    .await
+    {
+        Ok(histogram) => Ok(CommandResult::success(histogram)),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to sample directory: {}",
+            e
+        ))),
+    }
+}
+
+// Command to read a file list produced by another tool, resolving relative paths against the
+// current project. Generate with `tree_from_selection` to draw the tree from exactly these files.
+#[tauri::command]
+async fn load_file_list(
+    path: String,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<FileListInput>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+
+    match fs::read_file_list(Path::new(&path), &current_dir) {
+        Ok(file_list) => Ok(CommandResult::success(file_list)),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to read file list: {}",
+            e
+        ))),
+    }
+}
+
+// Command to stop reacting to config file changes, e.g. during a bulk operation. Tree
+// changes are held back until the watcher is resumed.
+#[tauri::command]
+async fn pause_watcher(state: tauri::State<'_, AppState>) -> Result<CommandResult<bool>, String> {
+    if let Some(tree_watcher) = state.tree_watcher.lock().unwrap().as_ref() {
+        tree_watcher.pause();
+    }
+    match state.config_watcher.lock().unwrap().as_ref() {
+        Some(config_watcher) => {
+            config_watcher.pause();
+            Ok(CommandResult::success(true))
+        }
+        None => Ok(CommandResult::success(false)),
+    }
+}
+
+// Command to react to config file changes again, catching up on anything missed while paused
+#[tauri::command]
+async fn resume_watcher(state: tauri::State<'_, AppState>) -> Result<CommandResult<bool>, String> {
+    if let Some(tree_watcher) = state.tree_watcher.lock().unwrap().as_ref() {
+        tree_watcher.resume();
+    }
+    match state.config_watcher.lock().unwrap().as_ref() {
+        Some(config_watcher) => {
+            config_watcher.resume();
+            Ok(CommandResult::success(true))
         }
+        None => Ok(CommandResult::success(false)),
     }
 }
 
-// Command to get session state
+// Command to report where the time went in the last scan or generation
 #[tauri::command]
-async fn get_session_state(
+async fn profile_last_operation(
+    state: tauri::State<'_, AppState>,
+) -> Result<CommandResult<Option<OperationProfile>>, String> {
+    Ok(CommandResult::success(
+        state.last_profile.lock().unwrap().clone(),
+    ))
+}
+
+// Command to install a post-commit hook that keeps the context file up to date by running
+// this app headlessly with the project's saved selection
+#[tauri::command]
+async fn install_git_hook(
+    state: tauri::State<'_, AppState>,
     app_handle: tauri::AppHandle,
-) -> Result<CommandResult<SessionState>, String> {
-    match config::load_session_state(&app_handle) {
-        Ok(state) => Ok(CommandResult::success(state)),
+) -> Result<CommandResult<String>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+
+    let executable = match std::env::current_exe() {
+        Ok(executable) => executable,
+        Err(e) => {
+            return Ok(CommandResult::error(format!(
+                "Could not locate the GPTree executable: {}",
+                e
+            )))
+        }
+    };
+    let config_mode = *state.config_mode.lock().unwrap();
+    let output_path = load_active_config(&current_dir, config_mode, &app_handle)
+        .and_then(|config| processor::resolve_output_path(&config, &current_dir))
+        .unwrap_or_else(|_| current_dir.join(Config::default().output_file));
+    let snapshot_path = config::project_snapshot_path(&current_dir, &output_path);
+
+    match git::install_post_commit_hook(&current_dir, &executable, &snapshot_path) {
+        Ok(hook_path) => Ok(CommandResult::success(
+            hook_path.to_string_lossy().to_string(),
+        )),
         Err(e) => Ok(CommandResult::error(format!(
-            "Failed to load session state: {}",
+            "Failed to install git hook: {}",
             e
         ))),
     }
 }
 
-// New command: set_config_mode
+// Command to list the files changed relative to `base` ("head", "staged", or a branch), so
+// exactly what changed can be selected for a review prompt
 #[tauri::command]
-async fn set_config_mode(
-    mode: String,
+async fn get_git_changed_files(
+    base: Option<String>,
     state: tauri::State<'_, AppState>,
     app_handle: tauri::AppHandle,
-) -> Result<CommandResult<bool>, String> {
-    let current_dir = state.current_dir.lock().unwrap().clone();
-
-    // Update config mode in state
-    let mut config_mode = state.config_mode.lock().unwrap();
-    *config_mode = match mode.as_str() {
-        "local" => ConfigMode::LocalOverride,
-        _ => ConfigMode::Global,
+) -> Result<CommandResult<Vec<String>>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
     };
 
-    // Create config file if it doesn't exist
-    match mode.as_str() {
-        "local" => {
-            if !current_dir.join(".gptree_config").exists() {
-                match config::load_or_create_project_config(&current_dir) {
-                    Ok(_) => {}
-                    Err(e) => {
-                        return Ok(CommandResult::error(format!(
-                            "Failed to create local config: {}",
-                            e
-                        )))
-                    }
-                }
-            }
-        }
-        _ => {
-            // global
-            match config::load_or_create_global_config() {
-                Ok(_) => {}
-                Err(e) => {
-                    return Ok(CommandResult::error(format!(
-                        "Failed to create global config: {}",
-                        e
-                    )))
-                }
-            }
-        }
+    let base = base.unwrap_or_else(|| "head".to_string());
+    match git::changed_files(&current_dir, &base) {
+        Ok(files) => Ok(CommandResult::success(
+            files
+                .into_iter()
+                .map(|path| path.to_string_lossy().to_string())
+                .collect(),
+        )),
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to list changed files: {}",
+            e
+        ))),
     }
+}
 
-    // Update session state
-    match config::load_session_state(&app_handle) {
-        Ok(mut state) => {
-            state.last_config_mode = Some(mode.clone());
-            if let Err(e) = config::save_session_state(&app_handle, &state) {
-                eprintln!("[GPTree] Warning: Failed to save session state: {}", e);
-            }
-        }
-        Err(e) => {
-            eprintln!("[GPTree] Warning: Failed to load session state: {}", e);
+// Where clones of remote repositories are kept, one folder per URL and ref
+fn remote_repos_dir(app_handle: &tauri::AppHandle) -> Result<PathBuf, AppError> {
+    let cache_dir = app_handle
+        .path()
+        .app_cache_dir()
+        .map_err(|e| AppError::Config(format!("Could not determine app cache directory: {}", e)))?;
+    Ok(cache_dir.join(REMOTE_REPOS_DIR))
+}
+
+// Clone `url` at `git_ref` into the cache, reusing an earlier clone of the same URL and ref
+// unless the ref has moved since
+fn clone_remote_repo(
+    app_handle: &tauri::AppHandle,
+    url: &str,
+    git_ref: Option<&str>,
+) -> Result<PathBuf, AppError> {
+    let repos_dir = remote_repos_dir(app_handle)?;
+    StdFs::create_dir_all(&repos_dir)?;
+    let key = fs::hash_content(&format!("{}#{}", url, git_ref.unwrap_or_default()));
+    let dest = repos_dir.join(&key[..16]);
+    if dest.join(".git").is_dir() {
+        // Branches move on; clone again when the remote's tip is no longer the cached one.
+        // When the remote can't be reached, the cached clone is better than nothing.
+        let is_current = git::remote_commit(&repos_dir, url, git_ref)
+            .is_none_or(|tip| git::head_commit(&dest).as_deref() == Some(tip.as_str()));
+        if is_current {
+            return Ok(dest);
         }
     }
 
-    Ok(CommandResult::success(true))
+    // Clone next to the final folder and move it into place, so an interrupted clone
+    // is never mistaken for a finished one
+    let partial = repos_dir.join(format!("{}.partial", &key[..16]));
+    if partial.exists() {
+        StdFs::remove_dir_all(&partial)?;
+    }
+    if let Err(e) = git::shallow_clone(url, git_ref, &partial) {
+        let _ = StdFs::remove_dir_all(&partial);
+        return Err(e);
+    }
+    if dest.exists() {
+        StdFs::remove_dir_all(&dest)?;
+    }
+    StdFs::rename(&partial, &dest)?;
+    Ok(dest)
 }
 
-// New command: get_configs
+// Command to shallow-clone a remote git repository (or reuse a cached clone that is still
+// current) and load it like a local folder, so a public repo can be prompted about without
+// cloning it by hand. The clone's own config can't set transforms or an external command.
 #[tauri::command]
-async fn get_configs(path: Option<String>) -> Result<CommandResult<serde_json::Value>, String> {
-    use serde_json::json;
-    let global = config::load_or_create_global_config().ok();
-    let local = if let Some(path) = path {
-        let p = Path::new(&path);
-        config::load_or_create_project_config(p).ok()
-    } else {
-        None
-    };
-    Ok(CommandResult::success(json!({
-        "global": global,
-        "local": local,
-    })))
+async fn load_remote_repo(
+    url: String,
+    git_ref: Option<String>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<DirectoryLoadResult>, String> {
+    load_remote_repo_result(url, git_ref, &state, app_handle, None).await
 }
 
-#[tauri::command]
-async fn pick_save_path(
-    app: tauri::AppHandle,
-    content: String,
-) -> Result<CommandResult<String>, String> {
-    use std::fs;
-    use std::path::PathBuf;
-    use tauri_plugin_dialog::DialogExt;
-
-    println!("Starting pick_save_path function");
-
-    // Add a default text extension filter
-    let file_path = app
-        .dialog()
-        .file()
-        .add_filter("Text Files", &["txt"])
-        .set_file_name("gptree-output.txt")
-        .blocking_save_file();
-
-    println!("File dialog returned: {:?}", file_path);
-
-    match file_path {
-        Some(path) => {
-            let path_str = path.to_string();
-            println!("Path to string: {}", path_str);
-
-            // Try to convert the FilePath to a PathBuf
-            let path_buf = PathBuf::from(path_str.clone());
-            println!("Created PathBuf: {:?}", path_buf);
+// Clone and load a remote repository. As a task, the clone and the scan are reported as two
+// steps, and a cancel between them stops before the scan starts.
+async fn load_remote_repo_result(
+    url: String,
+    git_ref: Option<String>,
+    state: &AppState,
+    app_handle: tauri::AppHandle,
+    task: Option<&tasks::TaskHandle>,
+) -> Result<CommandResult<DirectoryLoadResult>, String> {
+    let url = url.trim().to_string();
+    let git_ref = git_ref
+        .map(|git_ref| git_ref.trim().to_string())
+        .filter(|git_ref| !git_ref.is_empty());
 
-            // Write content to the selected file
-            match fs::write(&path_buf, content) {
-                Ok(_) => {
-                    println!("File written successfully to {:?}", path_buf);
-                    Ok(CommandResult::success(path_str))
-                }
-                Err(e) => {
-                    println!("Error writing file: {}", e);
-                    Ok(CommandResult::error(format!("Failed to write file: {}", e)))
-                }
-            }
-        }
-        None => {
-            println!("File save operation was cancelled");
-            Ok(CommandResult::error(
-                "File save operation was cancelled".to_string(),
+    if let Some(task) = task {
+        task.report(&state.tasks, 0, 2);
+    }
+    let clone_handle = app_handle.clone();
+    let cloned = tauri::async_runtime::spawn_blocking(move || {
+        clone_remote_repo(&clone_handle, &url, git_ref.as_deref())
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    match cloned {
+        Ok(_) if task.is_some_and(|task| task.is_cancelled()) => {
+            Ok(CommandResult::error_with_code(
+                CODE_SCAN_CANCELLED,
+                "Loading the repository was cancelled".to_string(),
             ))
         }
+        Ok(dest) => {
+            if let Some(task) = task {
+                task.report(&state.tasks, 1, 2);
+            }
+            load_directory_result(
+                dest.to_string_lossy().to_string(),
+                None,
+                Some(true),
+                None,
+                state,
+                app_handle,
+                task.map(|task| task.cancel_flag()),
+            )
+            .await
+        }
+        Err(e) => Ok(CommandResult::error(format!(
+            "Failed to clone repository: {}",
+            e
+        ))),
     }
 }
 
-// Command to get application settings
+// Command to delete every cached clone of a remote repository, unloading the current
+// project first if it is one of them
 #[tauri::command]
-async fn get_app_settings(
+async fn clear_remote_repos(
+    state: tauri::State<'_, AppState>,
     app_handle: tauri::AppHandle,
-) -> Result<CommandResult<AppSettings>, String> {
-    let settings_path = match get_settings_path(&app_handle) {
-        Ok(path) => path,
-        Err(e) => {
-            return Ok(CommandResult::error(format!(
-                "Error getting settings path: {}",
-                e
-            )))
-        }
+) -> Result<CommandResult<()>, String> {
+    let repos_dir = match remote_repos_dir(&app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(CommandResult::error(e.to_string())),
     };
-
-    if !settings_path.exists() {
-        println!(
-            "[GPTree] Settings file not found at {:?}, returning defaults.",
-            settings_path
-        );
-        return Ok(CommandResult::success(AppSettings::default()));
+    let current_dir = state.current_dir.lock().unwrap().clone();
+    if !current_dir.as_os_str().is_empty() && current_dir.starts_with(&repos_dir) {
+        forget_missing_project(&current_dir, &state, &app_handle);
     }
 
-    match StdFs::read_to_string(&settings_path) {
-        Ok(content) => match serde_json::from_str(&content) {
-            Ok(settings) => Ok(CommandResult::success(settings)),
-            Err(e) => {
-                eprintln!(
-                    "[GPTree] Warning: Failed to parse settings file {:?}: {}. Returning defaults.",
-                    settings_path, e
-                );
-                Ok(CommandResult::success(AppSettings::default())) // Return defaults on parse error
-            }
-        },
+    match StdFs::remove_dir_all(&repos_dir) {
+        Ok(()) => Ok(CommandResult::success(())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(CommandResult::success(())),
         Err(e) => Ok(CommandResult::error(format!(
-            "Failed to read settings file {:?}: {}",
-            settings_path, e
+            "Failed to remove cloned repositories: {}",
+            e
         ))),
     }
 }
 
-// Command to save application settings
+// Command to convert absolute paths to the form previous_files stores them in: relative to
+// the project with native separators, or under external/ for files outside it
 #[tauri::command]
-async fn save_app_settings(
+async fn to_project_paths(
+    paths: Vec<String>,
+    state: tauri::State<'_, AppState>,
     app_handle: tauri::AppHandle,
-    settings: AppSettings,
-) -> Result<CommandResult<bool>, String> {
-    let settings_path = match get_settings_path(&app_handle) {
-        Ok(path) => path,
-        Err(e) => {
-            return Ok(CommandResult::error(format!(
-                "Error getting settings path: {}",
-                e
-            )))
-        }
+) -> Result<CommandResult<Vec<String>>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
     };
+    Ok(CommandResult::success(
+        paths
+            .iter()
+            .map(|path| {
+                fs::project_relative_path(&current_dir, Path::new(path))
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect(),
+    ))
+}
 
-    match serde_json::to_string_pretty(&settings) {
-        Ok(content) => match StdFs::write(&settings_path, content) {
-            Ok(_) => Ok(CommandResult::success(true)),
-            Err(e) => Ok(CommandResult::error(format!(
-                "Failed to write settings file {:?}: {}",
-                settings_path, e
-            ))),
-        },
-        Err(e) => Ok(CommandResult::error(format!(
-            "Failed to serialize settings: {}",
-            e
-        ))),
-    }
+// Command to turn previous_files entries back into absolute paths
+#[tauri::command]
+async fn from_project_paths(
+    files: Vec<String>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<CommandResult<Vec<String>>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    Ok(CommandResult::success(
+        files
+            .iter()
+            .map(|file| {
+                fs::resolve_project_path(&current_dir, file)
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect(),
+    ))
 }
 
-// Command to set the last used config mode preference
+// Command to render the current selection and options as a gptree CLI command
 #[tauri::command]
-async fn set_last_config_mode(
+async fn export_cli_command(
+    selected_files: Vec<String>,
+    state: tauri::State<'_, AppState>,
     app_handle: tauri::AppHandle,
-    mode: String,
-) -> Result<CommandResult<bool>, String> {
-    // Validate mode
-    if mode != "global" && mode != "local" {
-        return Ok(CommandResult::error(format!(
-            "Invalid config mode '{}' provided. Must be 'global' or 'local'.",
-            mode
-        )));
-    }
+) -> Result<CommandResult<CliCommand>, String> {
+    let current_dir = match current_project_dir(&state, &app_handle) {
+        Ok(dir) => dir,
+        Err(e) => return Ok(project_missing(e)),
+    };
+    let config_mode = *state.config_mode.lock().unwrap();
 
-    // Load current session state or defaults
-    let mut current_state = match config::load_session_state(&app_handle) {
-        Ok(state) => state,
+    let config = match load_active_config(&current_dir, config_mode, &app_handle) {
+        Ok(cfg) => cfg,
         Err(e) => {
-            eprintln!(
-                "[GPTree] Warning: Failed to load session state to update mode: {}. Using default.",
+            return Ok(CommandResult::error(format!(
+                "Failed to load active config: {}",
                 e
-            );
-            SessionState::default()
+            )))
         }
     };
 
-    // Update the mode
-    current_state.last_config_mode = Some(mode);
-
-    // Save the updated state
-    match config::save_session_state(&app_handle, &current_state) {
-        Ok(_) => Ok(CommandResult::success(true)),
-        Err(e) => Ok(CommandResult::error(format!(
-            "Error saving updated session state: {}",
-            e
-        ))),
-    }
-}
-
-// New command: diagnose_config_file
-#[tauri::command]
-async fn diagnose_config_file(
-    mode: String,
-    state: tauri::State<'_, AppState>,
-) -> Result<CommandResult<String>, String> {
-    let current_dir = state.current_dir.lock().unwrap().clone();
+    // The CLI reads the selection from the project config's previousFiles, so `--previous`
+    // only reproduces the selection when that is what's saved there. Nothing is written here.
+    let saved_selection: HashSet<String> = config::project_config_exists(&current_dir)
+        .then(|| config::load_config(&config::project_config_path(&current_dir)).ok())
+        .flatten()
+        .map(|saved| saved.previous_files.into_iter().collect())
+        .unwrap_or_default();
+    let selection: HashSet<String> = selected_files
+        .iter()
+        .map(|path| {
+            fs::project_relative_path(&current_dir, Path::new(path))
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect();
+    let is_saved = !selection.is_empty() && selection == saved_selection;
 
-    let config_path = if mode == "local" {
-        current_dir.join(".gptree_config")
+    // The gptree CLI only knows the flat .gptree_config, so projects in the .gptree/ layout
+    // get this app's headless mode instead, which reads the config wherever it lives
+    let mut command = if config::uses_project_dir(&current_dir) {
+        match std::env::current_exe() {
+            Ok(executable) => cli::build_headless_command(&executable, &current_dir, is_saved),
+            Err(e) => {
+                return Ok(CommandResult::error(format!(
+                    "Could not locate the GPTree executable: {}",
+                    e
+                )))
+            }
+        }
     } else {
-        dirs::home_dir()
-            .ok_or_else(|| "Could not find home directory".to_string())?
-            .join(".gptreerc")
+        cli::build_cli_command(&current_dir, &config, is_saved)
     };
-
-    let diagnosis = config::diagnose_config_file_access(&config_path);
-    Ok(CommandResult::success(diagnosis))
+    command.unsaved_selection = !selection.is_empty() && !is_saved;
+    Ok(CommandResult::success(command))
 }
 
 // Main run function
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // `--headless` generates from the command line and exits without opening a window
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "--headless") {
+        std::process::exit(headless::run(&args));
+    }
+
     let initial_state = AppState {
         current_dir: std::sync::Mutex::new(PathBuf::new()),
         config_mode: std::sync::Mutex::new(ConfigMode::Global), // Default to global
+        load_generation: AtomicU64::new(0),
+        cancelled_scan: AtomicU64::new(0),
+        config_watcher: std::sync::Mutex::new(None),
+        tree_watcher: std::sync::Mutex::new(None),
+        skip_worktree: std::sync::Mutex::new(None),
+        live_watcher: std::sync::Mutex::new(None),
+        over_budget: std::sync::Mutex::new(None),
+        last_profile: std::sync::Mutex::new(None),
+        output_history: std::sync::Mutex::new(VecDeque::new()),
+        saved_outputs: std::sync::Mutex::new(HashSet::new()),
+        next_history_id: AtomicU64::new(1),
+        session_log: std::sync::Mutex::new(Vec::new()),
+        tasks: tasks::TaskRegistry::default(),
+        stats: stats::StatsRegistry::default(),
+        open_dialogs: std::sync::Mutex::new(HashSet::new()),
     };
 
     tauri::Builder::default()
@@ -613,23 +4393,117 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
         .manage(initial_state)
+        .setup(move |app| {
+            // --reset starts without reopening the last folder, for when reopening it is what
+            // goes wrong; --reset-settings also puts the app settings back to their defaults
+            let reset_settings = args.iter().any(|arg| arg == "--reset-settings");
+            if reset_settings || args.iter().any(|arg| arg == "--reset") {
+                if let Err(e) = reset_saved_state(app.handle(), reset_settings) {
+                    eprintln!("[GPTree] Failed to reset: {}", e);
+                }
+            }
+
+            // `gptree-gui /path/to/project` opens straight into that project
+            if let Some(dir) = launch_directory(&args) {
+                open_launch_directory(app.handle(), &dir);
+            }
+
+            // A bad shortcut in the settings file shouldn't stop the app from starting
+            if let Err(e) = refresh_app_menu(app.handle()) {
+                eprintln!("[GPTree] Failed to build application menu: {}", e);
+            }
+            Ok(())
+        })
+        .on_menu_event(|app_handle, event| handle_menu_event(app_handle, event.id().as_ref()))
+        .on_window_event(|window, event| {
+            // Let the UI follow the OS theme while it's running, not just at startup
+            if let tauri::WindowEvent::ThemeChanged(_) = event {
+                let app_handle = window.app_handle();
+                let payload = current_system_theme(app_handle);
+                if let Err(e) = app_handle.emit("system-theme-changed", payload) {
+                    eprintln!("[GPTree] Failed to emit theme change: {}", e);
+                }
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             select_directory,
             load_directory,
+            load_directory_packed,
+            load_directory_children,
+            explain_ignore,
+            cancel_scan,
+            start_live_mode,
+            stop_live_mode,
+            get_runtime_stats,
+            probe_last_directory,
+            get_recent_directories,
+            remove_recent_directory,
+            detect_changes,
+            describe_config_schema,
+            get_default_ignores,
+            set_default_ignores,
+            list_favorites,
+            add_favorite,
+            remove_favorite,
+            reset_session,
             get_config,
             update_config,
             generate_output,
+            generate_output_packed,
+            approve_external_command,
+            generate_batch,
             copy_to_clipboard,
+            copy_node_path,
+            get_clipboard_history,
+            copy_history_item,
             open_output_file,
+            open_in_editor,
+            trash_output_file,
+            save_output_to_app_data,
             get_session_state,
+            get_session_history,
             set_config_mode,
             get_configs,
             pick_save_path,
             get_app_settings,
             save_app_settings,
             set_last_config_mode,
-            diagnose_config_file
+            diagnose_config_file,
+            install_git_hook,
+            get_git_changed_files,
+            load_remote_repo,
+            clear_remote_repos,
+            suggest_exclusions,
+            export_cli_command,
+            to_project_paths,
+            from_project_paths,
+            suggest_key_files,
+            get_infra_context_files,
+            get_selection_drift,
+            set_shortcut,
+            get_system_theme,
+            set_window_theme,
+            get_directory_heatmap,
+            migrate_project_dir,
+            list_presets,
+            save_preset,
+            delete_preset,
+            add_gptree_to_gitignore,
+            record_token_usage,
+            get_token_calibrations,
+            quick_size_histogram,
+            load_file_list,
+            start_load_directory,
+            start_generate_output,
+            start_load_remote_repo,
+            cancel_task,
+            list_tasks,
+            get_task_result,
+            pause_watcher,
+            resume_watcher,
+            profile_last_operation
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
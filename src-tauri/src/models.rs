@@ -1,11 +1,14 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 /// Current version of the configuration file format
 pub const CONFIG_VERSION: u32 = 3;
 
 /// Default constants
-pub const DEFAULT_IGNORES: [&str; 7] = [
+/// Names hidden from the tree unless a config sets its own `default_ignores`
+pub const DEFAULT_IGNORES: [&str; 8] = [
     ".git",
     ".vscode",
     "__pycache__",
@@ -13,12 +16,42 @@ pub const DEFAULT_IGNORES: [&str; 7] = [
     ".idea",
     ".gitignore",
     ".gptree_config",
+    ".gptree",
 ];
 
 pub const SAFE_MODE_MAX_FILES: usize = 30;
+/// Outputs past this size make the webview struggle to render the preview
+pub const DEFAULT_MAX_OUTPUT_SIZE_MB: u64 = 50;
+/// How many of the largest files an over-size error lists
+pub const OUTPUT_TOO_LARGE_TOP_FILES: usize = 5;
 pub const SAFE_MODE_MAX_LENGTH: usize = 100_000; // ~25K tokens
+/// Lines longer than this usually mean minified or serialized data
+pub const LONG_LINE_WARNING_CHARS: usize = 1000;
+
+/// Tokenizer used for token counts: "o200k_base", "cl100k_base" or "approximate"
+/// (4 characters per token, fast but rough)
+pub const DEFAULT_TOKEN_ENCODING: &str = "o200k_base";
+
+/// Models an output can be sized for: (id, tokenizer, context window in tokens).
+/// Claude's and Llama's tokenizers aren't in tiktoken, so cl100k_base stands in as the
+/// closest match; Gemini falls back to the character approximation.
+pub const TARGET_MODELS: &[(&str, &str, usize)] = &[
+    ("gpt-4o", "o200k_base", 128_000),
+    ("claude", "cl100k_base", 200_000),
+    ("gemini", "approximate", 1_000_000),
+    ("llama", "cl100k_base", 128_000),
+];
+
+/// Default section markers used in the combined output
+pub const DEFAULT_TREE_HEADER: &str = "# Project Directory Structure:";
+pub const DEFAULT_CONTENTS_HEADER: &str = "# BEGIN FILE CONTENTS";
+pub const DEFAULT_FILE_HEADER_TEMPLATE: &str = "# File: {path}";
+pub const LOC_SUMMARY_HEADER: &str = "# Line Count Summary:";
+pub const SYMBOL_INDEX_HEADER: &str = "# Symbol Index:";
+pub const DEPENDENCY_SUMMARY_HEADER: &str = "# Dependency Summary:";
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
 pub struct Config {
     pub version: u32,
     pub use_git_ignore: bool,
@@ -30,9 +63,66 @@ pub struct Config {
     pub copy_to_clipboard: bool,
     pub safe_mode: bool,
     pub store_files_chosen: bool,
+    /// Drop `previous_files` entries that no longer exist when the project config is saved
+    pub prune_missing_previous_files: bool,
     pub line_numbers: bool,
+    /// Comma-separated steps run over each file's content in order, e.g.
+    /// `strip_comments, redact, truncate:400, line_numbers`; see transforms::Transform
+    pub transforms: String,
     pub show_ignored_in_tree: bool,
     pub show_default_ignored_in_tree: bool,
+    /// File and folder names hidden wherever they appear, unless default-ignored entries are shown
+    pub default_ignores: Vec<String>,
+    pub tree_header: String,
+    pub contents_header: String,
+    /// Template for the line above each file; supports `{path}`, `{lines}` and `{tokens}`
+    pub file_header_template: String,
+    pub header_include_size: bool,
+    /// Tokenizer used for token counts, see DEFAULT_TOKEN_ENCODING
+    pub token_encoding: String,
+    /// Model the output is meant for, one of TARGET_MODELS; empty for none
+    pub target_model: String,
+    /// "text" for the plain format with `# File:` headers, "markdown" for the same with each
+    /// file in a fenced code block, "xml" to wrap each file in `<document>` tags as Claude's
+    /// prompting guides recommend, or "json" for a JsonOutput
+    pub output_format: String,
+    /// Start markdown outputs with a YAML front-matter block describing the output
+    pub markdown_front_matter: bool,
+    pub header_include_modified: bool,
+    pub header_include_git_commit: bool,
+    pub include_file_hash: bool,
+    pub write_manifest: bool,
+    /// Hide paths marked `linguist-generated` or `linguist-vendored` in .gitattributes.
+    /// Off by default, so existing projects' trees don't lose files without the user opting in.
+    pub respect_linguist_attributes: bool,
+    /// Decode files using the charset and line endings declared in .editorconfig.
+    /// Off by default, so existing outputs don't change encoding without the user opting in.
+    pub respect_editorconfig: bool,
+    /// Save to `name (2).ext`, `name (3).ext`, ... instead of replacing an existing output file
+    pub never_overwrite_output: bool,
+    /// Append a per-extension table of file, blank and code line counts to the output
+    pub include_loc_summary: bool,
+    /// List the functions, types and classes defined in each included file before the contents
+    pub include_symbol_index: bool,
+    /// Append the direct dependencies from the project's manifests, and leave out lockfiles
+    pub include_dependency_summary: bool,
+    /// Show excluded directories in the tree as one line with their file count and size
+    pub stub_excluded_dirs: bool,
+    /// Descend into symlinked directories; when off, symlinks show as `name -> target`
+    pub follow_symlinks: bool,
+    /// Stop generating once the combined output passes this many MB (0 disables the check)
+    pub max_output_size_mb: u64,
+    /// Read no more than this many MB of any one file (0 reads files whole)
+    pub max_file_read_mb: u64,
+    /// Program the `command` transform pipes each file through, e.g. `prettier --stdin-filepath {path}`
+    pub external_command: String,
+    /// Seconds the external command may take per file before it's stopped
+    pub external_command_timeout_secs: u64,
+    /// Files larger than this many KB skip the external command, and longer output is rejected
+    pub external_command_max_kb: u64,
+    /// Find/replace rules applied by the `replace` transform, in order
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub replacement_rules: Vec<ReplacementRule>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub previous_files: Vec<String>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
@@ -52,9 +142,41 @@ impl Default for Config {
             copy_to_clipboard: false,
             safe_mode: true,
             store_files_chosen: true,
+            prune_missing_previous_files: false,
             line_numbers: false,
+            transforms: String::new(),
             show_ignored_in_tree: false,
             show_default_ignored_in_tree: false,
+            default_ignores: DEFAULT_IGNORES
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            tree_header: DEFAULT_TREE_HEADER.to_string(),
+            contents_header: DEFAULT_CONTENTS_HEADER.to_string(),
+            file_header_template: DEFAULT_FILE_HEADER_TEMPLATE.to_string(),
+            token_encoding: DEFAULT_TOKEN_ENCODING.to_string(),
+            target_model: String::new(),
+            output_format: "text".to_string(),
+            markdown_front_matter: false,
+            header_include_size: false,
+            header_include_modified: false,
+            header_include_git_commit: false,
+            include_file_hash: false,
+            write_manifest: false,
+            respect_linguist_attributes: false,
+            respect_editorconfig: false,
+            never_overwrite_output: false,
+            include_loc_summary: false,
+            include_symbol_index: false,
+            include_dependency_summary: false,
+            stub_excluded_dirs: false,
+            follow_symlinks: true,
+            max_output_size_mb: DEFAULT_MAX_OUTPUT_SIZE_MB,
+            max_file_read_mb: 20,
+            external_command: String::new(),
+            external_command_timeout_secs: 30,
+            external_command_max_kb: 1024,
+            replacement_rules: Vec::new(),
             previous_files: Vec::new(),
             exclude_dirs: Vec::new(),
         }
@@ -63,19 +185,379 @@ impl Default for Config {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DirectoryItem {
-    pub name: String,
-    pub path: String,
+    /// Shared between all nodes with the same name (mod.rs, index.ts, ...) to keep big trees small
+    pub name: Arc<str>,
+    pub path: Arc<str>,
     pub is_dir: bool,
     pub is_selected: bool,
     pub children: Vec<DirectoryItem>,
     #[serde(default)]
     pub is_excluded_by_config: bool,
+    /// Matches the config's default ignores, so it's only in the tree because default-ignored
+    /// entries are shown
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_default_ignored: bool,
+    /// Where a symlink points, set when the config doesn't follow symlinks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symlink_target: Option<String>,
+    /// A directory whose children haven't been loaded yet; fetch them with load_directory_children
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub children_pending: bool,
+}
+
+/// A user-defined regex find/replace applied to file contents, e.g. to mask internal hostnames
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplacementRule {
+    pub pattern: String,
+    /// May refer to capture groups as `$1` or `${name}`
+    pub replacement: String,
+}
+
+/// Quick look at the remembered directory before it's loaded at startup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartupProbe {
+    pub path: String,
+    pub exists: bool,
+    /// Entries found, counted no further than one past the large-scan limit
+    pub entry_estimate: usize,
+    /// More entries than the large-scan limit, so a full scan may take a long time
+    pub is_large: bool,
+    /// The probe didn't finish in time, e.g. on a slow network drive
+    pub timed_out: bool,
+    /// A project index exists, so the tree can be shown without waiting for a walk
+    pub has_index: bool,
+}
+
+/// Metadata for one config field, from describe_config_schema
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigFieldSchema {
+    /// Field name in `Config`
+    pub field: String,
+    /// Key in the config file
+    pub key: String,
+    /// "boolean", "number", "string" or "array"
+    pub value_type: String,
+    pub default: serde_json::Value,
+    pub description: Option<String>,
+    /// Config modes the field is saved in: "global", "local" or both
+    pub modes: Vec<String>,
+    /// Read only by the GUI; the gptree CLI skips it
+    pub gui_only: bool,
+}
+
+/// A project pinned for reopening in one click, however long ago it was last opened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FavoriteProject {
+    pub path: String,
+    /// Shown instead of the path; the folder name unless one was given
+    pub name: String,
+    pub added_at: String,
+}
+
+/// One rule keeping a path out of the tree, from explain_ignore
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IgnoreReason {
+    /// "gitignore", "git_exclude", "gitattributes", "default_ignore", "config_exclude",
+    /// "extension_filter", "sparse_checkout" or "unfollowed_symlink"
+    pub kind: String,
+    /// The path the rule matched: the path itself or one of its parent directories
+    pub matched_path: String,
+    /// File the rule comes from, e.g. the .gitignore
+    pub source: Option<String>,
+    /// 1-based line of the rule in `source`, when it could be found
+    pub line: Option<usize>,
+    pub pattern: Option<String>,
+    pub message: String,
+}
+
+/// Filters suggested for a freshly opened project based on its detected stack
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterSuggestion {
+    pub project_types: Vec<String>,
+    pub include_file_types: String,
+    pub exclude_dirs: Vec<String>,
+}
+
+/// Options for a single generate_output call, on top of the saved config
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerationOptions {
+    /// Only include the regions of each file matching this regex; files without a match are left out
+    pub search_pattern: Option<String>,
+    /// Lines of context kept above and below each match
+    pub context_lines: Option<usize>,
+    /// Include only these definitions from the given files, keyed by absolute or project-relative path
+    pub symbols: HashMap<String, Vec<String>>,
+    /// Files included exactly as they are on disk, skipping search snippets, symbol scoping
+    /// and line numbers; absolute or project-relative paths
+    pub verbatim: HashSet<String>,
+    /// Render the tree from the selected files alone instead of walking the disk with the
+    /// config's filters, e.g. when the selection comes from another tool's file list
+    pub tree_from_selection: bool,
+    /// Embed each file's unified diff against this ref (e.g. "HEAD" or "main") instead of its
+    /// content; files without changes are left out
+    pub diff_base: Option<String>,
+    /// With diff_base, diff against this ref instead of the working tree
+    pub diff_target: Option<String>,
+    /// How many files to read at once; filled in from the app settings, not the frontend
+    #[serde(skip)]
+    pub read_concurrency: usize,
+    /// Set when the generation runs as a background task and should stop
+    #[serde(skip)]
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// External commands the user has approved for this project; a `command` transform
+    /// whose externalCommand isn't one of them is refused. Never taken from the frontend.
+    #[serde(skip)]
+    pub approved_commands: Vec<String>,
+}
+
+impl GenerationOptions {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::SeqCst))
+    }
+}
+
+/// One named selection to generate in a generate_batch run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchPreset {
+    /// Fills `{name}` in the output file name, e.g. "api" or "frontend"
+    pub name: String,
+    pub selected_files: Vec<String>,
+    pub excluded_dirs: Vec<String>,
+    pub options: GenerationOptions,
+}
+
+/// How one preset of a generate_batch run went
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchResult {
+    pub name: String,
+    pub saved_path: Option<String>,
+    pub file_count: usize,
+    pub token_estimate: usize,
+    /// Set when this preset failed; the rest of the batch still runs
+    pub error: Option<String>,
+}
+
+/// Output file name used for each preset of a batch unless another template is given
+pub const DEFAULT_BATCH_FILE_TEMPLATE: &str = "gptree-{name}.txt";
+
+/// Limits on how hard scans and generations may push the machine, resolved from the app settings
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceLimits {
+    pub scan_threads: usize,
+    pub read_concurrency: usize,
+}
+
+/// Size of a file considered for the output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileSize {
+    pub path: String,
+    pub bytes: u64,
+}
+
+/// A selected file that is never included because it holds credentials or keys
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockedFile {
+    pub path: String,
+    /// What kind of secret the file is, e.g. "SSH private key"
+    pub reason: String,
+}
+
+/// Why a generation was stopped for exceeding the output size ceiling
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputTooLargeDetails {
+    pub limit_mb: u64,
+    /// Size the output had reached when it was stopped
+    pub size_bytes: u64,
+    /// The largest selected files, biggest first
+    pub top_files: Vec<FileSize>,
+}
+
+impl OutputTooLargeDetails {
+    pub fn describe_top_files(&self) -> String {
+        self.top_files
+            .iter()
+            .map(|file| format!("{} ({} KB)", file.path, file.bytes / 1024))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// Why a selection goes over the safe mode limits, and which files to drop so it fits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FitSuggestion {
+    pub reason: String,
+    /// Files to deselect, largest first, with paths exactly as they were selected
+    pub drop_files: Vec<FileSize>,
+    pub drop_bytes: u64,
+    pub drop_tokens: usize,
+}
+
+impl FitSuggestion {
+    pub fn describe(&self) -> String {
+        format!(
+            "{}. Drop these {} files totalling ~{} tokens to fit",
+            self.reason,
+            self.drop_files.len(),
+            self.drop_tokens
+        )
+    }
+}
+
+/// Time spent in one phase of an operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhaseTiming {
+    pub phase: String,
+    pub ms: f64,
+}
+
+/// Timing breakdown of the last scan or generation, for diagnosing slow projects
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationProfile {
+    /// "scan" or "generate"
+    pub operation: String,
+    pub finished_at: String,
+    pub total_ms: f64,
+    pub phases: Vec<PhaseTiming>,
+}
+
+/// A file worth including first when building context for an unfamiliar project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyFileSuggestion {
+    pub path: String,
+    pub reason: String,
+    pub score: u32,
+}
+
+/// A CI, container, Terraform or deployment file gathered for the infra context group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InfraFile {
+    pub path: String,
+    /// "ci", "container", "terraform" or "deployment"
+    pub category: String,
+}
+
+/// Bit flags stored per node in a `FlatTree`
+pub const FLAT_NODE_DIR: u8 = 1;
+pub const FLAT_NODE_EXCLUDED_BY_CONFIG: u8 = 1 << 1;
+pub const FLAT_NODE_CHILDREN_PENDING: u8 = 1 << 2;
+pub const FLAT_NODE_DEFAULT_IGNORED: u8 = 1 << 3;
+
+/// Compact column-oriented form of a `DirectoryItem` tree for large payloads
+/// Node 0 is the root (its path is `root_path`); every other node's path is
+/// `prefixes[prefix[i]] + separator + names[i]`, and `parents[i]` is its parent's index.
+/// Nodes are in depth-first order, so a parent always comes before its children.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlatTree {
+    pub root_path: Arc<str>,
+    pub separator: String,
+    /// Interned parent directory paths
+    pub prefixes: Vec<Arc<str>>,
+    pub names: Vec<Arc<str>>,
+    pub parents: Vec<i32>,
+    pub prefix: Vec<u32>,
+    pub flags: Vec<u8>,
+    /// Where each unfollowed symlink points, keyed by node index
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub symlink_targets: BTreeMap<u32, String>,
+}
+
+/// Files that changed since detect_changes last ran on the project.
+/// Paths are relative to the project root.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChangeReport {
+    /// False on the first check, when there was nothing to compare with; the lists are then empty
+    pub had_baseline: bool,
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub removed: Vec<String>,
+    /// Files whose content was hashed this time
+    pub hashed: usize,
+    /// Files whose hash was reused because their size and modified time were unchanged
+    pub reused: usize,
+    pub elapsed_ms: u64,
+}
+
+/// Per-directory totals for drawing a treemap or heatmap of where a project's weight lives.
+/// Directories are stored as parallel arrays with parents before their children; index 0 is
+/// the project root and `parents` holds -1 for it. Directories deeper than the requested depth
+/// are counted in their ancestor at that depth.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryHeatmap {
+    pub root_path: String,
+    pub names: Vec<String>,
+    pub parents: Vec<i32>,
+    pub files: Vec<u32>,
+    pub bytes: Vec<u64>,
+    pub tokens: Vec<u64>,
+}
+
+/// Files in one size range, from quick_size_histogram
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SizeBucket {
+    pub label: String,
+    pub min_bytes: u64,
+    /// Exclusive upper bound; None for the last bucket
+    pub max_bytes: Option<u64>,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Totals for one directory, relative to the sampled root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirSize {
+    pub path: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// A cheap look at a directory's size before loading it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SizeHistogram {
+    pub buckets: Vec<SizeBucket>,
+    /// Largest directories up to a few levels deep, biggest first
+    pub largest_dirs: Vec<DirSize>,
+    pub files_sampled: usize,
+    pub bytes_sampled: u64,
+    /// False when sampling stopped at the file limit before seeing everything
+    pub complete: bool,
+}
+
+/// Tree payload returned by `load_directory`, either nested or flat
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TreePayload {
+    Nested(DirectoryItem),
+    Flat(FlatTree),
+}
+
+/// Result of loading a directory: the tree itself plus hints for the frontend
+/// The tree is flattened so the payload stays compatible with a plain `DirectoryItem`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryLoadResult {
+    #[serde(flatten)]
+    pub tree: TreePayload,
+    pub suggested_filters: Option<FilterSuggestion>,
+    /// Root of the enclosing git repository when the loaded directory is nested inside one
+    pub suggested_root: Option<String>,
+    /// `previous_files` entries that no longer exist on disk
+    #[serde(default)]
+    pub missing_previous_files: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FileDetail {
     pub path: String,
     pub tokens: usize,
+    /// SHA-256 of the file contents as read from disk, when hashing is enabled
+    #[serde(default)]
+    pub hash: Option<String>,
+    /// Number of lines in the file
+    #[serde(default)]
+    pub lines: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -84,6 +566,38 @@ pub struct TreeStructure {
     pub file_list: Vec<String>,
 }
 
+/// The active config, plus where each of its values comes from
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigWithSources {
+    #[serde(flatten)]
+    pub config: Config,
+    /// Keyed by field name: "default", "global" (set in ~/.gptreerc), "local" (set in the
+    /// project config), "env" (set by a `GPTREE_*` variable) or "session" (overridden in the
+    /// UI for this session only)
+    pub sources: HashMap<String, String>,
+}
+
+/// How the current selection differs from the `previous_files` saved in the project config.
+/// All paths are relative to the project root.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SelectionDrift {
+    /// Selected now but not last time
+    pub added: Vec<String>,
+    /// Selected last time, still on disk, but not selected now
+    pub removed: Vec<String>,
+    /// Selected last time but no longer on disk
+    pub missing: Vec<String>,
+}
+
+/// Paths read from a file list, split by whether they exist
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileListInput {
+    /// Absolute paths of the listed files that exist
+    pub files: Vec<String>,
+    /// Listed paths that aren't files on disk, as written in the list
+    pub missing: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct OutputContent {
     pub tree_structure: String,
@@ -91,6 +605,128 @@ pub struct OutputContent {
     pub file_details: Vec<FileDetail>,
     pub token_estimate: usize,
     pub saved_path: Option<String>,
+    /// Included files that look likely to waste tokens or confuse a model
+    #[serde(default)]
+    pub warnings: Vec<FileWarning>,
+    /// How the whole output measures up against the config's target model, if one is set
+    #[serde(default)]
+    pub model_fit: Option<ModelFit>,
+}
+
+/// The output's size in a target model's tokens, against that model's context window
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelFit {
+    pub model: String,
+    /// Estimate scaled by the model's calibration factor
+    pub tokens: usize,
+    /// Estimate straight from the tokenizer; report usage against this one
+    pub uncalibrated_tokens: usize,
+    /// 1.0 until actual usage has been reported for the model
+    pub calibration_factor: f64,
+    pub context_window: usize,
+    pub exceeds_context_window: bool,
+}
+
+/// A directory to exclude, learned from what was excluded in similar projects after a
+/// generation went over budget
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExclusionSuggestion {
+    /// Relative to the project root
+    pub dir: String,
+    /// The project type it was learned from, e.g. "rust" or "node"
+    pub project_type: String,
+    /// How many times it was excluded to bring an output under budget
+    pub times_excluded: u32,
+}
+
+/// How a model's real token usage compares to GPTree's estimates, from reported usage
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenCalibration {
+    /// Actual tokens per estimated token
+    pub factor: f64,
+    /// How many usage reports the factor is based on
+    pub samples: u32,
+}
+
+/// The whole output in the "json" output format, for scripts and agents to consume
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonOutput {
+    pub tree: String,
+    pub files: Vec<JsonOutputFile>,
+    pub token_estimate: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol_index: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_count_summary: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dependency_summary: Option<String>,
+}
+
+/// One included file in the "json" output format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonOutputFile {
+    pub path: String,
+    pub lines: usize,
+    pub tokens: usize,
+    /// The extra header details enabled in the config, e.g. "size", "commit" or "sha256"
+    pub metadata: BTreeMap<String, String>,
+    pub content: String,
+}
+
+/// Something unusual about an included file's content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileWarning {
+    pub path: String,
+    /// "long_lines", "crlf_line_endings", "cr_line_endings", "mixed_line_endings"
+    /// "lockfile_skipped" or "synced_output_folder"
+    pub kind: String,
+    pub message: String,
+}
+
+/// Something done during the current session, as listed by get_session_history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityEntry {
+    /// "load_directory" or "generate"
+    pub kind: String,
+    pub root_dir: String,
+    pub started_at: String,
+    pub duration_ms: u64,
+    pub file_count: Option<usize>,
+    pub token_estimate: Option<usize>,
+}
+
+/// A generated output kept in the in-app clipboard history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputHistoryItem {
+    pub id: u64,
+    pub root_dir: String,
+    pub generated_at: String,
+    pub file_count: usize,
+    pub token_estimate: usize,
+}
+
+/// Machine-readable provenance written next to a saved output file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OutputManifest {
+    pub generated_at: String,
+    pub root_dir: String,
+    pub output_file: String,
+    pub git_branch: Option<String>,
+    pub git_commit: Option<String>,
+    pub files: Vec<FileDetail>,
+    pub token_estimate: usize,
+    pub config: Config,
+}
+
+/// A gptree CLI invocation equivalent to the current GUI selection and options
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CliCommand {
+    pub command: String,
+    pub args: Vec<String>,
+    pub uses_previous_selection: bool,
+    /// The selection isn't the one saved in the project config, which is all `--previous`
+    /// can read; generate with it stored first for the command to reproduce it
+    pub unsaved_selection: bool,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -98,8 +734,8 @@ pub enum AppError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
-    #[error("Safe mode error: {0}")]
-    SafeMode(String),
+    #[error("Safe mode error: {}", .0.describe())]
+    SafeMode(FitSuggestion),
 
     #[error("Config error: {0}")]
     Config(String),
@@ -109,6 +745,30 @@ pub enum AppError {
 
     #[error("JSON error: {0}")]
     Json(String),
+
+    #[error("Git error: {0}")]
+    Git(String),
+
+    #[error("Operation cancelled")]
+    Cancelled,
+
+    #[error("Output is larger than the {} MB limit; largest selected files: {}", .0.limit_mb, .0.describe_top_files())]
+    OutputTooLarge(OutputTooLargeDetails),
+
+    #[error("Refusing to include secret files: {}", .0.iter().map(|file| format!("{} ({})", file.path, file.reason)).collect::<Vec<_>>().join(", "))]
+    SecretFiles(Vec<BlockedFile>),
+
+    #[error("Invalid pattern: {0}")]
+    Pattern(String),
+
+    #[error("Watcher error: {0}")]
+    Watch(String),
+
+    #[error("Project directory no longer exists: {0}")]
+    ProjectMissing(String),
+
+    #[error("The project's config runs an external command that hasn't been approved: {0}")]
+    CommandNotApproved(String),
 }
 
 impl Serialize for AppError {
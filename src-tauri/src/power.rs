@@ -0,0 +1,47 @@
+use std::fs;
+use std::path::Path;
+
+/// Whether the machine is running on battery power.
+/// Returns false when it can't be determined, so callers fall back to normal behaviour.
+pub fn on_battery() -> bool {
+    #[cfg(target_os = "linux")]
+    {
+        linux_on_battery(Path::new("/sys/class/power_supply"))
+    }
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("pmset")
+            .args(["-g", "batt"])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+            .unwrap_or(false)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        false
+    }
+}
+
+/// On battery if no mains supply is online and some battery is discharging
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn linux_on_battery(power_supply_dir: &Path) -> bool {
+    let Ok(supplies) = fs::read_dir(power_supply_dir) else {
+        return false;
+    };
+
+    let read = |dir: &Path, name: &str| {
+        fs::read_to_string(dir.join(name))
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    };
+
+    let mut discharging = false;
+    for supply in supplies.filter_map(Result::ok).map(|entry| entry.path()) {
+        match read(&supply, "type").as_str() {
+            "Mains" if read(&supply, "online") == "1" => return false,
+            "Battery" if read(&supply, "status") == "Discharging" => discharging = true,
+            _ => {}
+        }
+    }
+    discharging
+}
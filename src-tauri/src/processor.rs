@@ -1,11 +1,32 @@
+use crate::config;
+use crate::deps;
 use crate::fs::{
-    add_line_numbers, estimate_tokens, generate_tree_structure, read_file_content, save_to_file,
+    estimate_tokens, estimate_tokens_from_size, file_identity, format_size,
+    generate_tree_structure, hash_content, project_relative_path, read_file_content, read_files,
+    save_to_file, secret_file_reason, tree_from_file_list, FileIdentity, FileRead,
 };
+use crate::git;
 use crate::models::{
-    AppError, Config, FileDetail, OutputContent, SAFE_MODE_MAX_FILES, SAFE_MODE_MAX_LENGTH,
+    AppError, BlockedFile, Config, FileDetail, FileSize, FileWarning, FitSuggestion,
+    GenerationOptions, JsonOutput, JsonOutputFile, ModelFit, OutputContent, OutputManifest,
+    OutputTooLargeDetails, DEPENDENCY_SUMMARY_HEADER, LOC_SUMMARY_HEADER, LONG_LINE_WARNING_CHARS,
+    OUTPUT_TOO_LARGE_TOP_FILES, SAFE_MODE_MAX_FILES, SAFE_MODE_MAX_LENGTH, SYMBOL_INDEX_HEADER,
+    TARGET_MODELS,
 };
-use std::collections::HashSet;
-use std::path::{Path, PathBuf};
+use crate::profile::Profiler;
+use crate::symbols;
+use crate::transforms::{self, Transform};
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
+
+/// Lines of context kept around each search match unless the caller asks otherwise
+const DEFAULT_SNIPPET_CONTEXT_LINES: usize = 3;
+
+/// Files each reader thread reads ahead of the loop. Reading in small batches keeps memory
+/// bounded and lets a cancellation or the output limit stop the reading within one batch.
+const READ_AHEAD_PER_THREAD: usize = 2;
 
 /// Combine the file contents with the directory structure
 pub fn combine_files_with_structure(
@@ -13,58 +34,200 @@ pub fn combine_files_with_structure(
     config: &Config,
     selected_files: &[String],
     current_excluded_dirs: &[String],
+    options: &GenerationOptions,
+    on_progress: &dyn Fn(usize, usize),
+    profiler: &mut Profiler,
 ) -> Result<OutputContent, AppError> {
+    // Compile the search pattern up front so a typo fails fast instead of matching nothing
+    let search = match &options.search_pattern {
+        Some(pattern) if !pattern.is_empty() => Some(
+            Regex::new(pattern).map_err(|e| AppError::Pattern(format!("{}: {}", pattern, e)))?,
+        ),
+        _ => None,
+    };
+    let context_lines = options
+        .context_lines
+        .unwrap_or(DEFAULT_SNIPPET_CONTEXT_LINES);
+    // Likewise the transform pipeline, so a misspelled step fails before any files are read
+    let pipeline = transforms::pipeline(config, &options.approved_commands)?;
+    let numbered = pipeline
+        .iter()
+        .any(|transform| matches!(transform, Transform::LineNumbers));
+    let line_preserving: Vec<Transform> = pipeline
+        .iter()
+        .filter(|transform| transform.keeps_lines())
+        .cloned()
+        .collect();
+
     let mut combined_content = Vec::new();
     let mut file_details = Vec::new();
+    let mut line_counts = Vec::new();
+    let mut symbol_index = Vec::new();
+    let mut warnings = Vec::new();
 
     // Convert current_excluded_dirs to HashSet for efficient lookup
     let excluded_dirs_set: HashSet<String> = current_excluded_dirs.iter().cloned().collect();
 
     // Generate tree structure
-    let tree_structure = generate_tree_structure(
-        root_dir,
-        config.use_git_ignore,
-        config.show_ignored_in_tree,
-        config.show_default_ignored_in_tree,
-        &config.include_file_types,
-        &config.exclude_file_types,
-        &excluded_dirs_set,
-    )?;
-
-    combined_content.push("# Project Directory Structure:".to_string());
-    combined_content.push(tree_structure.tree_text.clone());
-    combined_content.push("\n# BEGIN FILE CONTENTS".to_string());
+    let mut tree_structure = profiler.time("walk", || {
+        if options.tree_from_selection {
+            Ok(tree_from_file_list(root_dir, selected_files))
+        } else {
+            generate_tree_structure(root_dir, config, &excluded_dirs_set)
+        }
+    })?;
+    // The walk never reaches files from outside the root, so list those after it
+    let external_files: Vec<String> = selected_files
+        .iter()
+        .filter(|file| !Path::new(file).starts_with(root_dir))
+        .cloned()
+        .collect();
+    if !options.tree_from_selection && !external_files.is_empty() {
+        let listing = tree_from_file_list(root_dir, &external_files);
+        // Skip the listing's own "." line, leaving its external/ folder as a second root
+        for line in listing.tree_text.lines().skip(1) {
+            tree_structure.tree_text.push('\n');
+            tree_structure.tree_text.push_str(line);
+        }
+    }
+
+    let xml = config.output_format == "xml";
+    let json = config.output_format == "json";
+    let markdown = config.output_format == "markdown";
+    let mut json_files = Vec::new();
+    if xml {
+        combined_content.push(format!(
+            "<directory_structure>\n{}\n</directory_structure>",
+            tree_structure.tree_text
+        ));
+    } else {
+        combined_content.push(config.tree_header.clone());
+        // The tree's indentation and box-drawing lines only survive markdown rendering in a fence
+        combined_content.push(if markdown {
+            fence("", &tree_structure.tree_text)
+        } else {
+            tree_structure.tree_text.clone()
+        });
+    }
+    let contents_header_index = combined_content.len();
+    combined_content.push(if xml {
+        "<documents>".to_string()
+    } else {
+        format!("\n{}", config.contents_header)
+    });
+
+    // Key and credential files are refused outright, whatever the settings
+    let blocked: Vec<BlockedFile> = selected_files
+        .iter()
+        .filter_map(|file_path| {
+            secret_file_reason(Path::new(file_path)).map(|reason| BlockedFile {
+                path: file_path.clone(),
+                reason: reason.to_string(),
+            })
+        })
+        .collect();
+    if !blocked.is_empty() {
+        return Err(AppError::SecretFiles(blocked));
+    }
 
     // Safe mode checks
     if config.safe_mode {
-        if selected_files.len() > SAFE_MODE_MAX_FILES {
-            return Err(AppError::SafeMode(format!(
+        let sizes: Vec<FileSize> = selected_files
+            .iter()
+            .map(|file_path| FileSize {
+                path: file_path.clone(),
+                bytes: std::fs::metadata(file_path).map(|m| m.len()).unwrap_or(0),
+            })
+            .collect();
+        let total_size: u64 = sizes.iter().map(|file| file.bytes).sum();
+
+        let reason = if selected_files.len() > SAFE_MODE_MAX_FILES {
+            Some(format!(
                 "Safe mode: Too many files selected ({} > {})",
                 selected_files.len(),
                 SAFE_MODE_MAX_FILES
-            )));
-        }
-
-        let mut total_size = 0;
-        for file_path in selected_files {
-            let path = PathBuf::from(file_path);
-            if let Ok(metadata) = std::fs::metadata(&path) {
-                total_size += metadata.len() as usize;
-                if total_size > SAFE_MODE_MAX_LENGTH {
-                    return Err(AppError::SafeMode(format!(
-                        "Safe mode: Combined file size too large (> {} bytes)",
-                        SAFE_MODE_MAX_LENGTH
-                    )));
-                }
-            }
+            ))
+        } else if total_size > SAFE_MODE_MAX_LENGTH as u64 {
+            Some(format!(
+                "Safe mode: Combined file size too large ({} > {} bytes)",
+                total_size, SAFE_MODE_MAX_LENGTH
+            ))
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            return Err(AppError::SafeMode(suggest_safe_mode_fit(reason, sizes)));
         }
     }
 
     // Combine contents of selected files
     let mut total_tokens = 0;
-    for file_path in selected_files {
+    let size_limit_bytes = config.max_output_size_mb * 1024 * 1024;
+    let mut output_bytes: u64 = combined_content.iter().map(|part| part.len() as u64).sum();
+
+    let read_limit_bytes = config.max_file_read_mb * 1024 * 1024;
+
+    // Read ahead in parallel when allowed; otherwise each file is read when the loop reaches it
+    let read_ahead_batch = options.read_concurrency * READ_AHEAD_PER_THREAD;
+    let mut prefetched: VecDeque<Result<FileRead, AppError>> = VecDeque::new();
+
+    // The same file reached through several paths is included once, under its first path
+    let (alias_of, aliases) = find_aliases(root_dir, selected_files);
+
+    // Look up the last commits for the whole selection at once, rather than a git call per file
+    let last_commits = if config.header_include_git_commit {
+        let files: HashSet<String> = selected_files
+            .iter()
+            .map(|file| git_path(&project_relative_path(root_dir, Path::new(file))))
+            .collect();
+        profiler.time("git", || git::last_commit_hashes(root_dir, &files))
+    } else {
+        HashMap::new()
+    };
+
+    for (file_index, file_path) in selected_files.iter().enumerate() {
+        if options.is_cancelled() {
+            return Err(AppError::Cancelled);
+        }
+        on_progress(file_index, selected_files.len());
+        if options.read_concurrency > 1 && prefetched.is_empty() {
+            let batch_end = (file_index + read_ahead_batch).min(selected_files.len());
+            prefetched = profiler
+                .time("read", || {
+                    read_files(
+                        &selected_files[file_index..batch_end],
+                        options.read_concurrency,
+                        config.respect_editorconfig,
+                        read_limit_bytes,
+                    )
+                })
+                .into();
+        }
+        let prefetched_content = prefetched.pop_front();
         let path = PathBuf::from(file_path);
 
+        if alias_of.contains(&file_index) {
+            continue;
+        }
+
+        // The dependency summary stands in for lockfiles, which cost a lot of tokens
+        if config.include_dependency_summary
+            && path
+                .file_name()
+                .is_some_and(|name| deps::LOCKFILE_NAMES.contains(&&*name.to_string_lossy()))
+        {
+            let rel_path = project_relative_path(root_dir, &path);
+            warnings.push(FileWarning {
+                path: rel_path.to_string_lossy().to_string(),
+                kind: "lockfile_skipped".to_string(),
+                message: format!(
+                    "{} was left out; the dependency summary covers it",
+                    rel_path.to_string_lossy()
+                ),
+            });
+            continue;
+        }
+
         // Skip if path doesn't exist or is not a file
         if !path.exists() || !path.is_file() {
             eprintln!(
@@ -74,30 +237,215 @@ pub fn combine_files_with_structure(
             continue;
         }
 
-        match read_file_content(&path) {
-            Ok(mut content) => {
-                // Add line numbers if requested
-                if config.line_numbers {
-                    content = add_line_numbers(&content);
-                }
+        let read_result = match prefetched_content {
+            Some(result) => result,
+            None => profiler.time("read", || {
+                read_file_content(&path, config.respect_editorconfig, read_limit_bytes)
+            }),
+        };
+        match read_result {
+            Ok(FileRead {
+                mut content,
+                truncated,
+            }) => {
+                let transform_started = Instant::now();
 
-                // Estimate tokens for this file
-                let file_tokens = estimate_tokens(&content);
-                total_tokens += file_tokens;
+                // Hash the content as it is on disk, before any output formatting
+                let hash = if config.include_file_hash || config.write_manifest {
+                    Some(hash_content(&content))
+                } else {
+                    None
+                };
 
                 // Convert absolute path to relative path for display
-                let rel_path = match path.strip_prefix(root_dir) {
-                    Ok(rel) => rel.to_string_lossy().to_string(),
-                    Err(_) => path.to_string_lossy().to_string(), // Fallback if stripping fails
+                let rel_path = project_relative_path(root_dir, &path)
+                    .to_string_lossy()
+                    .to_string();
+
+                warnings.extend(content_warnings(&rel_path, &content));
+                if truncated {
+                    warnings.push(FileWarning {
+                        path: rel_path.clone(),
+                        kind: "read_truncated".to_string(),
+                        message: format!(
+                            "{} is larger than the {} MB read limit; only its start is included",
+                            rel_path, config.max_file_read_mb
+                        ),
+                    });
+                    content.push_str(&format!(
+                        "\n[truncated at the {} MB read limit]",
+                        config.max_file_read_mb
+                    ));
+                }
+
+                // Symbols come from the whole file, but only files that make it into the
+                // output are listed in the index
+                let symbol_entry = config
+                    .include_symbol_index
+                    .then(|| symbols::extract_symbols(&path, &content))
+                    .flatten()
+                    .filter(|file_symbols| !file_symbols.is_empty())
+                    .map(|file_symbols| render_symbol_entry(&rel_path, &file_symbols));
+
+                // In diff mode a file is represented by its changes, passed through untouched;
+                // unchanged files are dropped
+                let diff_base = options.diff_base.as_deref().filter(|base| !base.is_empty());
+                if let Some(base) = diff_base {
+                    match git::file_diff(root_dir, &path, base, options.diff_target.as_deref()) {
+                        Ok(Some(diff)) => content = diff,
+                        Ok(None) => continue,
+                        Err(e) => {
+                            warnings.push(FileWarning {
+                                path: rel_path.clone(),
+                                kind: "diff_failed".to_string(),
+                                message: format!(
+                                    "{}: couldn't diff against {}: {}",
+                                    rel_path, base, e
+                                ),
+                            });
+                            continue;
+                        }
+                    }
+                }
+
+                // Symbol-scoped files keep just the requested definitions; in search mode only the
+                // matching regions are kept. Files left with nothing to show are dropped.
+                let marked_verbatim =
+                    options.verbatim.contains(file_path) || options.verbatim.contains(&rel_path);
+                let verbatim = diff_base.is_some() || marked_verbatim;
+                let requested_symbols = options
+                    .symbols
+                    .get(file_path)
+                    .or_else(|| options.symbols.get(&rel_path))
+                    .filter(|_| !verbatim);
+                let regions = match requested_symbols {
+                    Some(requested) => Some(symbol_regions(&path, &content, requested)),
+                    None => search
+                        .as_ref()
+                        .filter(|_| !verbatim)
+                        .map(|re| matching_regions(&content, re, context_lines)),
                 };
+                if regions.as_ref().is_some_and(|r| r.is_empty()) {
+                    if requested_symbols.is_some() {
+                        eprintln!(
+                            "Warning: None of the requested symbols were found in {}",
+                            rel_path
+                        );
+                    }
+                    continue;
+                }
+
+                let line_count = if let Some(regions) = regions {
+                    // Snippets number their lines themselves so the numbers match the file,
+                    // so only transforms that leave lines in place run on them
+                    let line_count = count_lines(&regions_text(&content, &regions));
+                    content = render_regions(&content, &regions, numbered);
+                    let (transformed, failures) =
+                        transforms::apply_all(&line_preserving, &path, content);
+                    content = transformed;
+                    warnings.extend(transform_warnings(&rel_path, failures));
+                    line_count
+                } else {
+                    // Count lines before line numbers make every line non-blank
+                    let line_count = count_lines(&content);
+
+                    // Diffs still get redactions and replacements, but nothing that would move
+                    // lines out from under the hunk headers
+                    let file_transforms = if marked_verbatim {
+                        None
+                    } else if diff_base.is_some() {
+                        Some(&line_preserving)
+                    } else {
+                        Some(&pipeline)
+                    };
+                    if let Some(file_transforms) = file_transforms {
+                        let (transformed, failures) =
+                            transforms::apply_all(file_transforms, &path, content);
+                        content = transformed;
+                        warnings.extend(transform_warnings(&rel_path, failures));
+                    }
+                    line_count
+                };
+                line_counts.push((extension_label(&path), line_count));
+
+                profiler.add("transform", transform_started.elapsed());
+
+                // Estimate tokens for this file
+                let file_tokens = profiler.time("tokenize", || {
+                    estimate_tokens(&content, &config.token_encoding)
+                });
+                total_tokens += file_tokens;
 
                 file_details.push(FileDetail {
                     path: rel_path.clone(),
                     tokens: file_tokens,
+                    hash: hash.clone(),
+                    lines: line_count.total,
                 });
+                symbol_index.extend(symbol_entry);
+
+                let header_hash = hash.as_deref().filter(|_| config.include_file_hash);
+                let last_commit = last_commits
+                    .get(&git_path(Path::new(&rel_path)))
+                    .map(String::as_str);
+                let mut metadata = file_header_metadata(config, &path, last_commit, header_hash);
+                if let Some(other_paths) = aliases.get(&file_index) {
+                    metadata.push(format!("also at: {}", other_paths.join(", ")));
+                }
+                let added_bytes = if json {
+                    let added_bytes = rel_path.len() + content.len();
+                    json_files.push(JsonOutputFile {
+                        path: rel_path.clone(),
+                        lines: content.lines().count(),
+                        tokens: file_tokens,
+                        metadata: metadata
+                            .iter()
+                            .filter_map(|entry| entry.split_once(": "))
+                            .map(|(key, value)| (key.replace(' ', "_"), value.to_string()))
+                            .collect(),
+                        content,
+                    });
+                    added_bytes
+                } else if xml {
+                    let header = render_document_open(
+                        file_details.len(),
+                        &rel_path,
+                        &content,
+                        file_tokens,
+                        &metadata,
+                    );
+                    let added_bytes = header.len() + content.len();
+                    combined_content.push(header);
+                    combined_content.push(format!("{}\n</document_content>\n</document>", content));
+                    added_bytes
+                } else {
+                    let mut header = render_file_header(
+                        &config.file_header_template,
+                        &rel_path,
+                        content.lines().count(),
+                        file_tokens,
+                    );
+                    if !metadata.is_empty() {
+                        header.push_str(&format!(" ({})", metadata.join(", ")));
+                    }
+                    if markdown {
+                        content = fence_code_block(&path, &content);
+                    }
+                    let added_bytes = header.len() + content.len();
+                    combined_content.push(format!("\n{}\n", header));
+                    combined_content.push(content);
+                    added_bytes
+                };
 
-                combined_content.push(format!("\n# File: {}\n", rel_path));
-                combined_content.push(content);
+                // Bail out before the output grows big enough to exhaust memory or freeze the preview
+                output_bytes += added_bytes as u64;
+                if size_limit_bytes > 0 && output_bytes > size_limit_bytes {
+                    return Err(AppError::OutputTooLarge(OutputTooLargeDetails {
+                        limit_mb: config.max_output_size_mb,
+                        size_bytes: output_bytes,
+                        top_files: largest_files(root_dir, selected_files),
+                    }));
+                }
                 // combined_content.push("\n# END FILE CONTENTS\n".to_string()); // Removed redundant end marker
             }
             Err(e) => {
@@ -108,47 +456,589 @@ pub fn combine_files_with_structure(
         }
     }
 
-    let combined_content_str = combined_content.join("\n");
+    let symbol_index = (config.include_symbol_index && !symbol_index.is_empty())
+        .then(|| symbol_index.join("\n\n"));
+    let line_count_summary = config
+        .include_loc_summary
+        .then(|| render_loc_summary(&line_counts));
+    let dependency_summary = if config.include_dependency_summary {
+        deps::dependency_summary(root_dir, &config.default_ignores)
+    } else {
+        None
+    };
     // Use the sum of file tokens as the estimate
     let estimated_tokens = total_tokens;
 
+    let combined_content_str = if json {
+        let output = JsonOutput {
+            tree: tree_structure.tree_text.clone(),
+            files: json_files,
+            token_estimate: estimated_tokens,
+            symbol_index,
+            line_count_summary,
+            dependency_summary,
+        };
+        profiler
+            .time("assemble", || serde_json::to_string_pretty(&output))
+            .map_err(|e| AppError::Json(e.to_string()))?
+    } else {
+        if xml {
+            combined_content.push("</documents>".to_string());
+        }
+
+        // The symbol index goes ahead of the contents so it reads as a map of what follows
+        if let Some(symbol_index) = symbol_index {
+            combined_content.insert(
+                contents_header_index,
+                render_section(
+                    &config.output_format,
+                    SYMBOL_INDEX_HEADER,
+                    "symbol_index",
+                    &symbol_index,
+                ),
+            );
+        }
+        if let Some(summary) = line_count_summary {
+            combined_content.push(render_section(
+                &config.output_format,
+                LOC_SUMMARY_HEADER,
+                "line_count_summary",
+                &summary,
+            ));
+        }
+        if let Some(summary) = dependency_summary {
+            combined_content.push(render_section(
+                &config.output_format,
+                DEPENDENCY_SUMMARY_HEADER,
+                "dependency_summary",
+                &summary,
+            ));
+        }
+
+        if markdown && config.markdown_front_matter {
+            combined_content.insert(
+                0,
+                render_front_matter(root_dir, file_details.len(), estimated_tokens),
+            );
+        }
+
+        profiler.time("assemble", || combined_content.join("\n"))
+    };
+    let model_fit = profiler.time("tokenize", || {
+        model_fit(&config.target_model, &combined_content_str)
+    });
+
     Ok(OutputContent {
         tree_structure: tree_structure.tree_text,
         combined_content: combined_content_str,
         file_details,
         token_estimate: estimated_tokens,
         saved_path: None, // Will be filled after saving
+        warnings,
+        model_fit,
     })
 }
 
-/// Save the output and copy to clipboard if requested
-/// Returns the absolute path where the file was saved, or None if saving was disabled.
-pub fn process_output(
-    output_content: &OutputContent,
+/// A summary section after the file contents: under a `#` header in text mode (fenced in
+/// markdown, which would otherwise reflow it), or wrapped in `tag` in XML mode
+fn render_section(output_format: &str, header: &str, tag: &str, body: &str) -> String {
+    match output_format {
+        "xml" => format!("<{tag}>\n{}\n</{tag}>", body),
+        "markdown" => format!("\n{}\n{}", header, fence("", body)),
+        _ => format!("\n{}\n{}", header, body),
+    }
+}
+
+/// The opening tags of a file in XML mode, up to where its content starts. Header metadata like
+/// `size: 3.0 KB` becomes attributes (`size="3.0 KB"`). File contents are left unescaped, so the
+/// code reads exactly as it is on disk.
+fn render_document_open(
+    index: usize,
+    rel_path: &str,
+    content: &str,
+    tokens: usize,
+    metadata: &[String],
+) -> String {
+    let mut attributes = vec![
+        format!("index=\"{}\"", index),
+        format!("path=\"{}\"", escape_xml_attribute(rel_path)),
+        format!("lines=\"{}\"", content.lines().count()),
+        format!("tokens=\"{}\"", tokens),
+    ];
+    for entry in metadata {
+        if let Some((key, value)) = entry.split_once(": ") {
+            attributes.push(format!(
+                "{}=\"{}\"",
+                key.replace(' ', "_"),
+                escape_xml_attribute(value)
+            ));
+        }
+    }
+    format!(
+        "<document {}>\n<source>{}</source>\n<document_content>",
+        attributes.join(" "),
+        escape_xml_attribute(rel_path)
+    )
+}
+
+fn escape_xml_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Count the whole output in the target model's tokens and check it against its context window.
+/// Returns None when no target model, or an unknown one, is configured.
+fn model_fit(target_model: &str, combined_content: &str) -> Option<ModelFit> {
+    let &(model, encoding, context_window) = TARGET_MODELS
+        .iter()
+        .find(|(model, _, _)| model.eq_ignore_ascii_case(target_model.trim()))?;
+    let tokens = estimate_tokens(combined_content, encoding);
+    Some(ModelFit {
+        model: model.to_string(),
+        tokens,
+        uncalibrated_tokens: tokens,
+        calibration_factor: 1.0,
+        context_window,
+        exceeds_context_window: tokens > context_window,
+    })
+}
+
+/// Flag minified or serialized files with huge lines, and line endings other than LF
+fn content_warnings(rel_path: &str, content: &str) -> Vec<FileWarning> {
+    let mut warnings = Vec::new();
+    let warn = |kind: &str, message: String| FileWarning {
+        path: rel_path.to_string(),
+        kind: kind.to_string(),
+        message,
+    };
+
+    let longest_line = content.lines().map(|line| line.len()).max().unwrap_or(0);
+    if longest_line > LONG_LINE_WARNING_CHARS {
+        warnings.push(warn(
+            "long_lines",
+            format!(
+                "{} has a line of {} characters; it may be minified or generated",
+                rel_path, longest_line
+            ),
+        ));
+    }
+
+    let crlf = content.matches("\r\n").count();
+    let cr = content.matches('\r').count() - crlf;
+    let lf = content.matches('\n').count() - crlf;
+    let line_ending_warning = match (crlf > 0, cr > 0, lf > 0) {
+        (true, false, false) => Some(("crlf_line_endings", "uses CRLF line endings")),
+        (false, true, false) => Some(("cr_line_endings", "uses CR-only line endings")),
+        (false, false, _) => None,
+        _ => Some(("mixed_line_endings", "mixes different line endings")),
+    };
+    if let Some((kind, description)) = line_ending_warning {
+        warnings.push(warn(kind, format!("{} {}", rel_path, description)));
+    }
+
+    warnings
+}
+
+/// Wrap a file's content in a fenced code block tagged with its extension, using a fence
+/// longer than any run of backticks in the content so it can't be closed early
+fn fence_code_block(path: &Path, content: &str) -> String {
+    let language = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    fence(&language, content)
+}
+
+/// `content` in a code fence longer than any backtick run inside it
+fn fence(language: &str, content: &str) -> String {
+    let longest_run = content.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat((longest_run + 1).max(3));
+    let newline = if content.ends_with('\n') { "" } else { "\n" };
+    format!("{}{}\n{}{}{}", fence, language, content, newline, fence)
+}
+
+/// YAML front matter describing a markdown output, for static-site and knowledge-base tools.
+/// Strings are written JSON-quoted, which YAML reads as double-quoted scalars.
+fn render_front_matter(root_dir: &Path, file_count: usize, token_estimate: usize) -> String {
+    let quote = |value: &str| serde_json::to_string(value).unwrap_or_default();
+    let project = root_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| root_dir.to_string_lossy().to_string());
+    let mut lines = vec![
+        "---".to_string(),
+        format!("project: {}", quote(&project)),
+        format!(
+            "generated_at: {}",
+            quote(&chrono::Local::now().to_rfc3339())
+        ),
+    ];
+    if let Some(branch) = git::current_branch(root_dir) {
+        lines.push(format!("git_branch: {}", quote(&branch)));
+    }
+    if let Some(commit) = git::head_commit(root_dir) {
+        lines.push(format!("git_commit: {}", quote(&commit)));
+    }
+    lines.push(format!("file_count: {}", file_count));
+    lines.push(format!("token_estimate: {}", token_estimate));
+    lines.push("---".to_string());
+    lines.join("\n")
+}
+
+/// Report transform steps that failed on a file; the file is included without their changes
+fn transform_warnings(rel_path: &str, failures: Vec<String>) -> Vec<FileWarning> {
+    failures
+        .into_iter()
+        .map(|message| FileWarning {
+            path: rel_path.to_string(),
+            kind: "transform_failed".to_string(),
+            message: format!("{}: {}", rel_path, message),
+        })
+        .collect()
+}
+
+/// Find the lines matching `pattern` and widen each by `context` lines, merging regions
+/// that overlap or touch. Returns inclusive, 0-based line ranges.
+fn matching_regions(content: &str, pattern: &Regex, context: usize) -> Vec<(usize, usize)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut regions: Vec<(usize, usize)> = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        if !pattern.is_match(line) {
+            continue;
+        }
+        let start = index.saturating_sub(context);
+        let end = (index + context).min(lines.len() - 1);
+        match regions.last_mut() {
+            Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+            _ => regions.push((start, end)),
+        }
+    }
+
+    regions
+}
+
+/// Line ranges of the requested definitions in a file, in the same form as `matching_regions`
+fn symbol_regions(path: &Path, content: &str, requested: &[String]) -> Vec<(usize, usize)> {
+    let Some(file_symbols) = symbols::extract_symbols(path, content) else {
+        return Vec::new();
+    };
+
+    let mut regions: Vec<(usize, usize)> = file_symbols
+        .iter()
+        .filter(|symbol| requested.iter().any(|name| symbol.matches(name)))
+        .map(|symbol| (symbol.line - 1, symbol.end_line - 1))
+        .collect();
+
+    // Drop definitions nested inside another requested one (a method inside a requested impl)
+    regions.sort();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in regions {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// The raw lines covered by the regions, without markers
+fn regions_text(content: &str, regions: &[(usize, usize)]) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    regions
+        .iter()
+        .flat_map(|&(start, end)| lines[start..=end].iter().copied())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+/// Render the regions of a file, each under a `[lines a-b]` marker
+fn render_regions(content: &str, regions: &[(usize, usize)], line_numbers: bool) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let width = lines.len().to_string().len();
+
+    regions
+        .iter()
+        .map(|&(start, end)| {
+            let mut block = vec![format!("[lines {}-{}]", start + 1, end + 1)];
+            block.extend((start..=end).map(|i| {
+                if line_numbers {
+                    format!("{:>width$} | {}", i + 1, lines[i], width = width)
+                } else {
+                    lines[i].to_string()
+                }
+            }));
+            block.join("\n")
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+/// Render one file's entry in the symbol index
+fn render_symbol_entry(rel_path: &str, file_symbols: &[symbols::Symbol]) -> String {
+    let mut lines = vec![rel_path.to_string()];
+    lines.extend(
+        file_symbols
+            .iter()
+            .map(|symbol| format!("  {} {} (line {})", symbol.kind, symbol.name, symbol.line)),
+    );
+    lines.join("\n")
+}
+
+/// Line counts for a single file
+#[derive(Debug, Clone, Copy, Default)]
+struct LineCount {
+    total: usize,
+    blank: usize,
+}
+
+fn count_lines(content: &str) -> LineCount {
+    let mut count = LineCount::default();
+    for line in content.lines() {
+        count.total += 1;
+        if line.trim().is_empty() {
+            count.blank += 1;
+        }
+    }
+    count
+}
+
+/// Label used to group files in the line count summary
+fn extension_label(path: &Path) -> String {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "(none)".to_string())
+}
+
+/// Render a cloc-style table of files, blank and code lines per extension
+fn render_loc_summary(line_counts: &[(String, LineCount)]) -> String {
+    let mut by_extension: BTreeMap<&str, (usize, LineCount)> = BTreeMap::new();
+    for (extension, count) in line_counts {
+        let entry = by_extension.entry(extension.as_str()).or_default();
+        entry.0 += 1;
+        entry.1.total += count.total;
+        entry.1.blank += count.blank;
+    }
+
+    let mut rows: Vec<(&str, usize, LineCount)> = by_extension
+        .into_iter()
+        .map(|(extension, (files, count))| (extension, files, count))
+        .collect();
+    // Largest languages first, like cloc
+    rows.sort_by(|a, b| b.2.total.cmp(&a.2.total).then(a.0.cmp(b.0)));
+
+    let total_files: usize = rows.iter().map(|row| row.1).sum();
+    let total = LineCount {
+        total: rows.iter().map(|row| row.2.total).sum(),
+        blank: rows.iter().map(|row| row.2.blank).sum(),
+    };
+
+    let format_row = |label: &str, files: usize, count: LineCount| {
+        format!(
+            "{:<12} {:>8} {:>8} {:>8} {:>8}",
+            label,
+            files,
+            count.blank,
+            count.total - count.blank,
+            count.total
+        )
+    };
+
+    let rule = "-".repeat(48);
+    let mut table = vec![
+        format!(
+            "{:<12} {:>8} {:>8} {:>8} {:>8}",
+            "Extension", "Files", "Blank", "Code", "Total"
+        ),
+        rule.clone(),
+    ];
+    table.extend(
+        rows.iter()
+            .map(|(extension, files, count)| format_row(extension, *files, *count)),
+    );
+    table.push(rule);
+    table.push(format_row("TOTAL", total_files, total));
+    table.join("\n")
+}
+
+/// The largest of the selected files on disk, biggest first
+fn largest_files(root_dir: &Path, selected_files: &[String]) -> Vec<FileSize> {
+    let mut sizes: Vec<FileSize> = selected_files
+        .iter()
+        .filter_map(|file_path| {
+            let path = Path::new(file_path);
+            let bytes = std::fs::metadata(path).ok()?.len();
+            let rel_path = project_relative_path(root_dir, path);
+            Some(FileSize {
+                path: rel_path.to_string_lossy().to_string(),
+                bytes,
+            })
+        })
+        .collect();
+    sizes.sort_by_key(|file| std::cmp::Reverse(file.bytes));
+    sizes.truncate(OUTPUT_TOO_LARGE_TOP_FILES);
+    sizes
+}
+
+/// Pick the fewest files to drop for a selection to fit the safe mode limits.
+/// Dropping the largest files first frees the most space per file.
+fn suggest_safe_mode_fit(reason: String, mut sizes: Vec<FileSize>) -> FitSuggestion {
+    sizes.sort_by_key(|file| std::cmp::Reverse(file.bytes));
+    let mut remaining_files = sizes.len();
+    let mut remaining_bytes: u64 = sizes.iter().map(|file| file.bytes).sum();
+
+    let mut drop_files = Vec::new();
+    for file in sizes {
+        if remaining_files <= SAFE_MODE_MAX_FILES && remaining_bytes <= SAFE_MODE_MAX_LENGTH as u64
+        {
+            break;
+        }
+        remaining_files -= 1;
+        remaining_bytes -= file.bytes;
+        drop_files.push(file);
+    }
+
+    let drop_bytes = drop_files.iter().map(|file| file.bytes).sum();
+    FitSuggestion {
+        reason,
+        drop_tokens: estimate_tokens_from_size(drop_bytes),
+        drop_bytes,
+        drop_files,
+    }
+}
+
+/// Render the per-file header line from the configured template
+fn render_file_header(template: &str, rel_path: &str, lines: usize, tokens: usize) -> String {
+    template
+        .replace("{path}", rel_path)
+        .replace("{lines}", &lines.to_string())
+        .replace("{tokens}", &tokens.to_string())
+}
+
+/// A project-relative path the way git prints it, with `/` separators
+fn git_path(rel_path: &Path) -> String {
+    rel_path.to_string_lossy().replace('\\', "/")
+}
+
+/// Collect the optional metadata entries (size, modified time, last commit, hash) for a file header
+fn file_header_metadata(
     config: &Config,
-    root_dir: &Path, // Ensure this is the absolute path to the project
-) -> Result<Option<String>, AppError> {
-    // Check if saving is disabled
-    if !config.save_output_file {
-        return Ok(None); // Return None if saving is disabled
+    path: &Path,
+    last_commit: Option<&str>,
+    hash: Option<&str>,
+) -> Vec<String> {
+    let mut entries = Vec::new();
+
+    if config.header_include_size || config.header_include_modified {
+        if let Ok(metadata) = std::fs::metadata(path) {
+            if config.header_include_size {
+                entries.push(format!("size: {}", format_size(metadata.len())));
+            }
+            if config.header_include_modified {
+                if let Ok(modified) = metadata.modified() {
+                    let modified: chrono::DateTime<chrono::Local> = modified.into();
+                    entries.push(format!(
+                        "modified: {}",
+                        modified.format("%Y-%m-%d %H:%M:%S %z")
+                    ));
+                }
+            }
+        }
+    }
+
+    if config.header_include_git_commit {
+        if let Some(commit) = last_commit {
+            entries.push(format!("commit: {}", commit));
+        }
+    }
+
+    if let Some(hash) = hash {
+        entries.push(format!("sha256: {}", hash));
+    }
+
+    entries
+}
+
+/// Find selected files that are hard links, bind mounts or junctions to an earlier selected
+/// file. Returns the indexes to skip, and for each file kept, the other paths reaching it.
+fn find_aliases(
+    root_dir: &Path,
+    selected_files: &[String],
+) -> (HashSet<usize>, HashMap<usize, Vec<String>>) {
+    let mut first_seen: HashMap<FileIdentity, usize> = HashMap::new();
+    let mut alias_of = HashSet::new();
+    let mut aliases: HashMap<usize, Vec<String>> = HashMap::new();
+
+    for (index, file_path) in selected_files.iter().enumerate() {
+        let path = Path::new(file_path);
+        let Some(identity) = file_identity(path) else {
+            continue;
+        };
+        match first_seen.get(&identity) {
+            Some(&original) => {
+                alias_of.insert(index);
+                let rel_path = project_relative_path(root_dir, path);
+                aliases
+                    .entry(original)
+                    .or_default()
+                    .push(rel_path.to_string_lossy().to_string());
+            }
+            None => {
+                first_seen.insert(identity, index);
+            }
+        }
+    }
+    (alias_of, aliases)
+}
+
+/// Absolute path the output file is saved to under `config`. The output file has to be a
+/// relative path that stays inside the folder it's saved to, since a project's config could
+/// otherwise point it at any file the user can write.
+pub fn resolve_output_path(config: &Config, root_dir: &Path) -> Result<PathBuf, AppError> {
+    let output_file = Path::new(&config.output_file);
+    let stays_inside = output_file
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if !stays_inside {
+        return Err(AppError::Config(format!(
+            "The output file {} must be a relative path without '..'",
+            config.output_file
+        )));
     }
 
-    // Determine absolute output file path
-    let output_file_path = if config.output_file_locally {
-        // Save relative to the project directory
-        root_dir.join(&config.output_file)
+    Ok(if config.output_file_locally {
+        // Save relative to the project directory (its .gptree/outputs folder if it has one)
+        config::project_output_dir(root_dir).join(output_file)
     } else {
         // Save to user's Documents directory
         if let Some(docs_dir) = dirs::document_dir() {
-            docs_dir.join(&config.output_file)
+            docs_dir.join(output_file)
         } else {
             // Fallback: Save relative to the project dir if Documents isn't available
             eprintln!(
                 "Warning: Could not find Documents directory. Saving to project directory instead."
             );
-            root_dir.join(&config.output_file)
+            root_dir.join(output_file)
         }
-    };
+    })
+}
+
+/// Save the output and copy to clipboard if requested
+/// Returns the absolute path where the file was saved, or None if saving was disabled.
+pub fn process_output(
+    output_content: &OutputContent,
+    config: &Config,
+    root_dir: &Path, // Ensure this is the absolute path to the project
+) -> Result<Option<String>, AppError> {
+    // Check if saving is disabled
+    if !config.save_output_file {
+        return Ok(None); // Return None if saving is disabled
+    }
+
+    let output_file_path = resolve_output_path(config, root_dir)?;
 
     // Ensure parent directory exists
     if let Some(parent) = output_file_path.parent() {
@@ -157,12 +1047,73 @@ pub fn process_output(
         }
     }
 
+    // Keep earlier outputs around if requested
+    let output_file_path = if config.never_overwrite_output {
+        unique_output_path(&output_file_path)
+    } else {
+        output_file_path
+    };
+
     // Log the path we are saving to
     println!("[GPTree] Saving output to: {:?}", output_file_path);
 
     // Save to file
     save_to_file(&output_file_path, &output_content.combined_content)?;
 
+    // Write the provenance sidecar if requested; a failure here shouldn't lose the output
+    if config.write_manifest {
+        if let Err(e) = write_manifest(&output_file_path, output_content, config, root_dir) {
+            eprintln!("Warning: Failed to write output manifest: {}", e);
+        }
+    }
+
     // Return the absolute path as a string wrapped in Some
     Ok(Some(output_file_path.to_string_lossy().to_string())) // Wrap in Some()
 }
+
+/// Find a free file name next to `path` by appending ` (2)`, ` (3)`, ... to its stem
+fn unique_output_path(path: &Path) -> PathBuf {
+    if !path.exists() {
+        return path.to_path_buf();
+    }
+
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    (2..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .expect("ran out of output file names")
+}
+
+/// Write `<output>.manifest.json` describing what went into a saved output
+fn write_manifest(
+    output_file_path: &Path,
+    output_content: &OutputContent,
+    config: &Config,
+    root_dir: &Path,
+) -> Result<(), AppError> {
+    let manifest = OutputManifest {
+        generated_at: chrono::Local::now().to_rfc3339(),
+        root_dir: root_dir.to_string_lossy().to_string(),
+        output_file: output_file_path.to_string_lossy().to_string(),
+        git_branch: git::current_branch(root_dir),
+        git_commit: git::head_commit(root_dir),
+        files: output_content.file_details.clone(),
+        token_estimate: output_content.token_estimate,
+        config: config.clone(),
+    };
+
+    let mut manifest_path = output_file_path.as_os_str().to_owned();
+    manifest_path.push(".manifest.json");
+
+    let content =
+        serde_json::to_string_pretty(&manifest).map_err(|e| AppError::Json(e.to_string()))?;
+    save_to_file(Path::new(&manifest_path), &content)
+}
@@ -0,0 +1,52 @@
+use crate::models::{OperationProfile, PhaseTiming};
+use std::time::{Duration, Instant};
+
+/// Accumulates how long each phase of a scan or generation took.
+/// Phases recorded more than once (e.g. reading each file) are summed.
+pub struct Profiler {
+    operation: &'static str,
+    started: Instant,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Profiler {
+    pub fn new(operation: &'static str) -> Self {
+        Self {
+            operation,
+            started: Instant::now(),
+            phases: Vec::new(),
+        }
+    }
+
+    /// Add `duration` to `phase`, keeping phases in the order they were first seen
+    pub fn add(&mut self, phase: &'static str, duration: Duration) {
+        match self.phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += duration,
+            None => self.phases.push((phase, duration)),
+        }
+    }
+
+    /// Run `f` and record its duration under `phase`
+    pub fn time<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        self.add(phase, started.elapsed());
+        result
+    }
+
+    pub fn finish(self) -> OperationProfile {
+        OperationProfile {
+            operation: self.operation.to_string(),
+            finished_at: chrono::Local::now().to_rfc3339(),
+            total_ms: self.started.elapsed().as_secs_f64() * 1000.0,
+            phases: self
+                .phases
+                .into_iter()
+                .map(|(phase, duration)| PhaseTiming {
+                    phase: phase.to_string(),
+                    ms: duration.as_secs_f64() * 1000.0,
+                })
+                .collect(),
+        }
+    }
+}
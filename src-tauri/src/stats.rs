@@ -0,0 +1,78 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often an operation ran and how long it took in total
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationStats {
+    pub count: u64,
+    pub total_ms: u64,
+    pub max_ms: u64,
+}
+
+/// Snapshot returned by get_runtime_stats
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeStats {
+    pub uptime_secs: u64,
+    /// Keyed by operation, e.g. "load_directory" or "generate"
+    pub operations: BTreeMap<String, OperationStats>,
+    /// Event counts, e.g. "index_cache_hit" or "scan_cancelled"
+    pub counters: BTreeMap<String, u64>,
+}
+
+#[derive(Default)]
+struct StatsInner {
+    operations: BTreeMap<String, OperationStats>,
+    counters: BTreeMap<String, u64>,
+}
+
+/// Per-operation counts and durations since launch, for tuning performance settings.
+/// Kept in memory only; nothing is written to disk or sent anywhere.
+pub struct StatsRegistry {
+    started: Instant,
+    inner: Mutex<StatsInner>,
+}
+
+impl Default for StatsRegistry {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            inner: Mutex::new(StatsInner::default()),
+        }
+    }
+}
+
+impl StatsRegistry {
+    /// Count one run of `operation` that took `elapsed`
+    pub fn record(&self, operation: &str, elapsed: Duration) {
+        let elapsed_ms = elapsed.as_millis() as u64;
+        let mut inner = self.inner.lock().unwrap();
+        let stats = inner.operations.entry(operation.to_string()).or_default();
+        stats.count += 1;
+        stats.total_ms += elapsed_ms;
+        stats.max_ms = stats.max_ms.max(elapsed_ms);
+    }
+
+    /// Bump the counter `name` by one
+    pub fn increment(&self, name: &str) {
+        *self
+            .inner
+            .lock()
+            .unwrap()
+            .counters
+            .entry(name.to_string())
+            .or_default() += 1;
+    }
+
+    pub fn snapshot(&self) -> RuntimeStats {
+        let inner = self.inner.lock().unwrap();
+        RuntimeStats {
+            uptime_secs: self.started.elapsed().as_secs(),
+            operations: inner.operations.clone(),
+            counters: inner.counters.clone(),
+        }
+    }
+}
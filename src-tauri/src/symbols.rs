@@ -0,0 +1,174 @@
+use std::path::Path;
+use tree_sitter::{Language, Node, Parser};
+
+/// A named definition found in a source file
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    pub kind: &'static str,
+    pub name: String,
+    /// 1-based line where the definition starts
+    pub line: usize,
+    /// 1-based line where the definition ends
+    pub end_line: usize,
+}
+
+impl Symbol {
+    /// Whether a requested name like `process_output` or `fn process_output` refers to this symbol
+    pub fn matches(&self, requested: &str) -> bool {
+        let requested = requested.trim();
+        requested == self.name || requested == format!("{} {}", self.kind, self.name)
+    }
+}
+
+/// Node kinds treated as definitions for a language, with the label shown for each
+struct LanguageSpec {
+    language: Language,
+    definitions: &'static [(&'static str, &'static str)],
+}
+
+const RUST_DEFINITIONS: &[(&str, &str)] = &[
+    ("function_item", "fn"),
+    ("struct_item", "struct"),
+    ("enum_item", "enum"),
+    ("union_item", "union"),
+    ("trait_item", "trait"),
+    ("impl_item", "impl"),
+    ("mod_item", "mod"),
+    ("const_item", "const"),
+    ("static_item", "static"),
+    ("type_item", "type"),
+    ("macro_definition", "macro"),
+];
+
+const PYTHON_DEFINITIONS: &[(&str, &str)] = &[
+    ("function_definition", "def"),
+    ("class_definition", "class"),
+];
+
+const JAVASCRIPT_DEFINITIONS: &[(&str, &str)] = &[
+    ("function_declaration", "function"),
+    ("generator_function_declaration", "function"),
+    ("class_declaration", "class"),
+    ("method_definition", "method"),
+    ("variable_declarator", "function"),
+];
+
+const TYPESCRIPT_DEFINITIONS: &[(&str, &str)] = &[
+    ("function_declaration", "function"),
+    ("generator_function_declaration", "function"),
+    ("class_declaration", "class"),
+    ("abstract_class_declaration", "class"),
+    ("method_definition", "method"),
+    ("variable_declarator", "function"),
+    ("interface_declaration", "interface"),
+    ("type_alias_declaration", "type"),
+    ("enum_declaration", "enum"),
+];
+
+const GO_DEFINITIONS: &[(&str, &str)] = &[
+    ("function_declaration", "func"),
+    ("method_declaration", "method"),
+    ("type_spec", "type"),
+];
+
+fn language_for(path: &Path) -> Option<LanguageSpec> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    let (language, definitions) = match extension.as_str() {
+        "rs" => (tree_sitter_rust::LANGUAGE.into(), RUST_DEFINITIONS),
+        "py" | "pyi" => (tree_sitter_python::LANGUAGE.into(), PYTHON_DEFINITIONS),
+        "js" | "jsx" | "mjs" | "cjs" => (
+            tree_sitter_javascript::LANGUAGE.into(),
+            JAVASCRIPT_DEFINITIONS,
+        ),
+        "ts" | "mts" | "cts" => (
+            tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            TYPESCRIPT_DEFINITIONS,
+        ),
+        "tsx" => (
+            tree_sitter_typescript::LANGUAGE_TSX.into(),
+            TYPESCRIPT_DEFINITIONS,
+        ),
+        "go" => (tree_sitter_go::LANGUAGE.into(), GO_DEFINITIONS),
+        _ => return None,
+    };
+    Some(LanguageSpec {
+        language,
+        definitions,
+    })
+}
+
+/// Extract the functions, types and classes defined in a file, in source order.
+/// Returns None for unsupported languages or if parsing fails.
+pub fn extract_symbols(path: &Path, content: &str) -> Option<Vec<Symbol>> {
+    let spec = language_for(path)?;
+    let mut parser = Parser::new();
+    parser.set_language(&spec.language).ok()?;
+    let tree = parser.parse(content, None)?;
+
+    let mut symbols = Vec::new();
+    collect_symbols(tree.root_node(), content, spec.definitions, &mut symbols);
+    Some(symbols)
+}
+
+fn collect_symbols(
+    node: Node,
+    source: &str,
+    definitions: &[(&str, &'static str)],
+    symbols: &mut Vec<Symbol>,
+) {
+    if let Some(&(_, kind)) = definitions.iter().find(|(k, _)| *k == node.kind()) {
+        if let Some(name) = definition_name(node, source) {
+            symbols.push(Symbol {
+                kind,
+                name,
+                line: node.start_position().row + 1,
+                end_line: node.end_position().row + 1,
+            });
+        }
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_symbols(child, source, definitions, symbols);
+    }
+}
+
+/// The name of a definition node, or None if it isn't one worth listing
+fn definition_name(node: Node, source: &str) -> Option<String> {
+    match node.kind() {
+        // impl blocks are named after the type they implement, plus the trait if any
+        "impl_item" => {
+            let type_name = node
+                .child_by_field_name("type")?
+                .utf8_text(source.as_bytes())
+                .ok()?;
+            match node.child_by_field_name("trait") {
+                Some(trait_node) => Some(format!(
+                    "{} for {}",
+                    trait_node.utf8_text(source.as_bytes()).ok()?,
+                    type_name
+                )),
+                None => Some(type_name.to_string()),
+            }
+        }
+        // Only variables holding functions count, e.g. `const handler = () => {}`
+        "variable_declarator" => {
+            let value = node.child_by_field_name("value")?;
+            if !matches!(
+                value.kind(),
+                "arrow_function" | "function_expression" | "function"
+            ) {
+                return None;
+            }
+            node.child_by_field_name("name")?
+                .utf8_text(source.as_bytes())
+                .ok()
+                .map(str::to_string)
+        }
+        _ => node
+            .child_by_field_name("name")?
+            .utf8_text(source.as_bytes())
+            .ok()
+            .map(str::to_string),
+    }
+}
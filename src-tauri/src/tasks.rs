@@ -0,0 +1,195 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+/// How long a finished task's result waits to be collected before it's dropped
+const RESULT_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Where a background task is in its life
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TaskStatus {
+    Running,
+    Completed,
+    Cancelled,
+}
+
+/// A background task as reported to the frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskInfo {
+    pub id: u64,
+    /// The operation the task runs, e.g. "load_directory" or "generate"
+    pub kind: String,
+    pub status: TaskStatus,
+    pub done: usize,
+    pub total: usize,
+}
+
+/// Payload of the "task-progress" and "task-finished" events
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TaskEvent {
+    id: u64,
+    status: TaskStatus,
+    done: usize,
+    total: usize,
+}
+
+struct TaskEntry {
+    info: TaskInfo,
+    cancelled: Arc<AtomicBool>,
+    /// The command result the task finished with, kept until it's collected or expires
+    result: Option<serde_json::Value>,
+    finished_at: Option<Instant>,
+}
+
+/// Long operations started with a `start_*` command run as tasks: they return an id at once,
+/// report progress through events, can be cancelled, and leave their result to be collected.
+#[derive(Default)]
+pub struct TaskRegistry {
+    next_id: AtomicU64,
+    tasks: Mutex<HashMap<u64, TaskEntry>>,
+}
+
+/// Given to the code running a task, to report progress and check for cancellation
+#[derive(Clone)]
+pub struct TaskHandle {
+    id: u64,
+    cancelled: Arc<AtomicBool>,
+    app_handle: AppHandle,
+}
+
+impl TaskRegistry {
+    /// Register a new running task
+    pub fn start(&self, kind: &str, app_handle: &AppHandle) -> TaskHandle {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let mut tasks = self.tasks.lock().unwrap();
+        evict_expired(&mut tasks);
+        tasks.insert(
+            id,
+            TaskEntry {
+                info: TaskInfo {
+                    id,
+                    kind: kind.to_string(),
+                    status: TaskStatus::Running,
+                    done: 0,
+                    total: 0,
+                },
+                cancelled: cancelled.clone(),
+                result: None,
+                finished_at: None,
+            },
+        );
+        TaskHandle {
+            id,
+            cancelled,
+            app_handle: app_handle.clone(),
+        }
+    }
+
+    /// Record a task's result and tell the frontend it can be collected
+    pub fn finish(&self, task: &TaskHandle, result: serde_json::Value) {
+        let event = {
+            let mut tasks = self.tasks.lock().unwrap();
+            let Some(entry) = tasks.get_mut(&task.id) else {
+                return;
+            };
+            entry.info.status = if task.is_cancelled() {
+                TaskStatus::Cancelled
+            } else {
+                TaskStatus::Completed
+            };
+            entry.result = Some(result);
+            entry.finished_at = Some(Instant::now());
+            TaskEvent {
+                id: task.id,
+                status: entry.info.status,
+                done: entry.info.done,
+                total: entry.info.total,
+            }
+        };
+        if let Err(e) = task.app_handle.emit("task-finished", event) {
+            eprintln!("[GPTree] Failed to emit task result: {}", e);
+        }
+    }
+
+    /// Ask a running task to stop. Returns false if there's no such running task.
+    pub fn cancel(&self, id: u64) -> bool {
+        match self.tasks.lock().unwrap().get(&id) {
+            Some(entry) if entry.info.status == TaskStatus::Running => {
+                entry.cancelled.store(true, Ordering::SeqCst);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// All known tasks, oldest first
+    pub fn list(&self) -> Vec<TaskInfo> {
+        let mut tasks = self.tasks.lock().unwrap();
+        evict_expired(&mut tasks);
+        let mut tasks: Vec<TaskInfo> = tasks.values().map(|entry| entry.info.clone()).collect();
+        tasks.sort_by_key(|info| info.id);
+        tasks
+    }
+
+    /// Take a finished task's result, forgetting the task.
+    /// Returns None if the task doesn't exist or is still running.
+    pub fn take_result(&self, id: u64) -> Option<serde_json::Value> {
+        let mut tasks = self.tasks.lock().unwrap();
+        if tasks.get(&id)?.info.status == TaskStatus::Running {
+            return None;
+        }
+        tasks.remove(&id)?.result
+    }
+
+    fn set_progress(&self, id: u64, done: usize, total: usize) {
+        if let Some(entry) = self.tasks.lock().unwrap().get_mut(&id) {
+            entry.info.done = done;
+            entry.info.total = total;
+        }
+    }
+}
+
+/// Drop finished tasks whose results were never collected
+fn evict_expired(tasks: &mut HashMap<u64, TaskEntry>) {
+    tasks.retain(|_, entry| {
+        entry
+            .finished_at
+            .is_none_or(|finished| finished.elapsed() < RESULT_TTL)
+    });
+}
+
+impl TaskHandle {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// The flag `cancel` sets, for code that polls it directly
+    pub fn cancel_flag(&self) -> Arc<AtomicBool> {
+        self.cancelled.clone()
+    }
+
+    /// Record progress and send a "task-progress" event
+    pub fn report(&self, registry: &TaskRegistry, done: usize, total: usize) {
+        registry.set_progress(self.id, done, total);
+        let event = TaskEvent {
+            id: self.id,
+            status: TaskStatus::Running,
+            done,
+            total,
+        };
+        if let Err(e) = self.app_handle.emit("task-progress", event) {
+            eprintln!("[GPTree] Failed to emit task progress: {}", e);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use serde::Serialize;
+use std::process::Command;
+
+/// The OS appearance settings the UI follows
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemTheme {
+    /// "dark" or "light"
+    pub theme: String,
+    /// Accent color as `#rrggbb`, when the OS exposes one
+    pub accent_color: Option<String>,
+}
+
+/// Read the OS dark/light preference and accent color.
+/// `fallback_dark` is used when the preference can't be read, typically the window's own theme.
+pub fn system_theme(fallback_dark: bool) -> SystemTheme {
+    let dark = prefers_dark().unwrap_or(fallback_dark);
+    SystemTheme {
+        theme: if dark { "dark" } else { "light" }.to_string(),
+        accent_color: accent_color(),
+    }
+}
+
+/// Run a command and return its trimmed stdout, or None if it couldn't run or failed
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let mut command = Command::new(program);
+    command.args(args);
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        // CREATE_NO_WINDOW, so no console flashes up
+        command.creation_flags(0x0800_0000);
+    }
+    let output = command.output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Whether the OS is set to dark mode, or None if it can't be determined
+fn prefers_dark() -> Option<bool> {
+    #[cfg(target_os = "macos")]
+    {
+        // The key only exists while dark mode is on
+        Some(
+            command_output("defaults", &["read", "-g", "AppleInterfaceStyle"])
+                .is_some_and(|style| style == "Dark"),
+        )
+    }
+    #[cfg(target_os = "windows")]
+    {
+        let value = registry_dword(
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize",
+            "AppsUseLightTheme",
+        )?;
+        Some(value == 0)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let scheme = command_output(
+            "gsettings",
+            &["get", "org.gnome.desktop.interface", "color-scheme"],
+        )?;
+        Some(scheme.contains("prefer-dark"))
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    {
+        None
+    }
+}
+
+/// The accent color chosen in the OS settings, as `#rrggbb`
+fn accent_color() -> Option<String> {
+    #[cfg(target_os = "macos")]
+    {
+        // No value means the default blue
+        let index = command_output("defaults", &["read", "-g", "AppleAccentColor"])
+            .and_then(|value| value.parse::<i32>().ok())
+            .unwrap_or(4);
+        let color = match index {
+            -1 => "#8e8e93",
+            0 => "#ff3b30",
+            1 => "#ff9500",
+            2 => "#ffcc00",
+            3 => "#28cd41",
+            5 => "#af52de",
+            6 => "#ff2d55",
+            _ => "#007aff",
+        };
+        Some(color.to_string())
+    }
+    #[cfg(target_os = "windows")]
+    {
+        // Stored as 0xAABBGGRR
+        let value = registry_dword(r"HKCU\Software\Microsoft\Windows\DWM", "AccentColor")?;
+        Some(format!(
+            "#{:02x}{:02x}{:02x}",
+            value & 0xff,
+            (value >> 8) & 0xff,
+            (value >> 16) & 0xff
+        ))
+    }
+    #[cfg(target_os = "linux")]
+    {
+        // GNOME 47+ names its accent colors rather than storing a value
+        let name = command_output(
+            "gsettings",
+            &["get", "org.gnome.desktop.interface", "accent-color"],
+        )?;
+        let color = match name.trim_matches('\'') {
+            "blue" => "#3584e4",
+            "teal" => "#2190a4",
+            "green" => "#3a944a",
+            "yellow" => "#c88800",
+            "orange" => "#ed5b00",
+            "red" => "#e62d42",
+            "pink" => "#d56199",
+            "purple" => "#9141ac",
+            "slate" => "#6f8396",
+            _ => return None,
+        };
+        Some(color.to_string())
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    {
+        None
+    }
+}
+
+/// Read a REG_DWORD value with `reg query`
+#[cfg(target_os = "windows")]
+fn registry_dword(key: &str, value_name: &str) -> Option<u32> {
+    let output = command_output("reg", &["query", key, "/v", value_name])?;
+    // e.g. "    AccentColor    REG_DWORD    0xffd77800"
+    let hex = output
+        .lines()
+        .find(|line| line.contains("REG_DWORD"))?
+        .split_whitespace()
+        .last()?
+        .trim_start_matches("0x");
+    u32::from_str_radix(hex, 16).ok()
+}
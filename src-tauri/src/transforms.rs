@@ -0,0 +1,414 @@
+use crate::cli::split_command_template;
+use crate::fs::add_line_numbers;
+use crate::models::{AppError, Config};
+use regex::{Captures, Regex};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+/// One content-shaping step applied to each included file. The project's `transforms`
+/// config key lists them by name, and they run in that order.
+#[derive(Debug, Clone)]
+pub enum Transform {
+    /// `line_numbers`: prefix each line with its number
+    LineNumbers,
+    /// `truncate:N`: keep the first N lines and note how many were cut
+    Truncate(usize),
+    /// `strip_comments`: drop lines that hold nothing but a comment
+    StripComments,
+    /// `redact`: mask values that look like credentials
+    Redact,
+    /// `replace`: apply the project's `replacementRules`, in order
+    Replace(Vec<(Regex, String)>),
+    /// `command`: pipe the content through the project's `externalCommand`. Only runs once
+    /// the user has approved that exact command for the project, since the config file
+    /// ships with the repository and could otherwise run anything on Generate.
+    Command(ExternalCommand),
+}
+
+/// An external program a file's content is piped through, stdin to stdout
+#[derive(Debug, Clone)]
+pub struct ExternalCommand {
+    /// Program and arguments; `{path}` is replaced with the file's path
+    args: Vec<String>,
+    timeout: Duration,
+    /// Largest input, and largest output, the command is given or accepted from
+    max_bytes: usize,
+}
+
+/// A step that couldn't run on a file. The file carries on with its content from before the step.
+pub struct TransformFailure {
+    pub content: String,
+    pub message: String,
+}
+
+impl Transform {
+    /// Parse a single entry of the `transforms` list, e.g. `truncate:200`.
+    /// `rules` are the compiled replacement rules the `replace` step uses, and
+    /// `approved_commands` the external commands the user has allowed for this project.
+    pub fn parse(
+        spec: &str,
+        config: &Config,
+        rules: &[(Regex, String)],
+        approved_commands: &[String],
+    ) -> Result<Self, AppError> {
+        let (name, argument) = match spec.split_once(':') {
+            Some((name, argument)) => (name.trim(), Some(argument.trim())),
+            None => (spec.trim(), None),
+        };
+        match (name, argument) {
+            ("line_numbers", None) => Ok(Transform::LineNumbers),
+            ("truncate", Some(max_lines)) => max_lines
+                .parse()
+                .map(Transform::Truncate)
+                .map_err(|_| AppError::Config(format!("Invalid line count in {}", spec))),
+            ("strip_comments", None) => Ok(Transform::StripComments),
+            ("redact", None) => Ok(Transform::Redact),
+            ("replace", None) => Ok(Transform::Replace(rules.to_vec())),
+            ("command", None) => {
+                let args = split_command_template(&config.external_command);
+                if args.is_empty() {
+                    return Err(AppError::Config(
+                        "The command transform needs externalCommand to be set".to_string(),
+                    ));
+                }
+                if !approved_commands.contains(&config.external_command) {
+                    return Err(AppError::CommandNotApproved(
+                        config.external_command.clone(),
+                    ));
+                }
+                Ok(Transform::Command(ExternalCommand {
+                    args,
+                    timeout: Duration::from_secs(config.external_command_timeout_secs),
+                    max_bytes: config.external_command_max_kb as usize * 1024,
+                }))
+            }
+            _ => Err(AppError::Config(format!("Unknown transform: {}", spec))),
+        }
+    }
+
+    /// Whether the transform only rewrites text within lines, leaving their number and order
+    /// alone. Only these run on search snippets and symbol regions, whose line numbers
+    /// have to match the file. Replacement rules are expected to stay within a line.
+    pub fn keeps_lines(&self) -> bool {
+        matches!(self, Transform::Redact | Transform::Replace(_))
+    }
+
+    pub fn apply(&self, path: &Path, content: String) -> Result<String, TransformFailure> {
+        Ok(match self {
+            Transform::LineNumbers => add_line_numbers(&content),
+            Transform::Truncate(max_lines) => truncate_lines(content, *max_lines),
+            Transform::StripComments => match comment_syntax(path) {
+                Some(syntax) => strip_comment_lines(&content, syntax),
+                None => content,
+            },
+            Transform::Redact => redact_secrets(&content),
+            Transform::Replace(rules) => {
+                rules
+                    .iter()
+                    .fold(content, |content, (pattern, replacement)| {
+                        pattern
+                            .replace_all(&content, replacement.as_str())
+                            .into_owned()
+                    })
+            }
+            Transform::Command(command) => match run_external_command(command, path, &content) {
+                Ok(output) => output,
+                Err(message) => return Err(TransformFailure { content, message }),
+            },
+        })
+    }
+}
+
+/// The project's transform pipeline. The older `lineNumbers` setting still works: it adds a
+/// final `line_numbers` step when the list doesn't have one. Replacement rules run first
+/// unless the list places a `replace` step somewhere.
+pub fn pipeline(config: &Config, approved_commands: &[String]) -> Result<Vec<Transform>, AppError> {
+    let rules = config
+        .replacement_rules
+        .iter()
+        .map(|rule| {
+            Regex::new(&rule.pattern)
+                .map(|pattern| (pattern, rule.replacement.clone()))
+                .map_err(|e| AppError::Pattern(format!("{}: {}", rule.pattern, e)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut transforms = config
+        .transforms
+        .split(',')
+        .filter(|spec| !spec.trim().is_empty())
+        .map(|spec| Transform::parse(spec, config, &rules, approved_commands))
+        .collect::<Result<Vec<_>, _>>()?;
+    if !rules.is_empty()
+        && !transforms
+            .iter()
+            .any(|transform| matches!(transform, Transform::Replace(_)))
+    {
+        transforms.insert(0, Transform::Replace(rules));
+    }
+    if config.line_numbers
+        && !transforms
+            .iter()
+            .any(|transform| matches!(transform, Transform::LineNumbers))
+    {
+        transforms.push(Transform::LineNumbers);
+    }
+    Ok(transforms)
+}
+
+/// Run each transform over the content in turn. Steps that fail are skipped, and their
+/// messages returned alongside the content.
+pub fn apply_all(transforms: &[Transform], path: &Path, content: String) -> (String, Vec<String>) {
+    let mut failures = Vec::new();
+    let content = transforms.iter().fold(content, |content, transform| {
+        match transform.apply(path, content) {
+            Ok(content) => content,
+            Err(failure) => {
+                failures.push(failure.message);
+                failure.content
+            }
+        }
+    });
+    (content, failures)
+}
+
+/// Pipe `content` through an external command, enforcing its time and size limits
+fn run_external_command(
+    command: &ExternalCommand,
+    path: &Path,
+    content: &str,
+) -> Result<String, String> {
+    if content.len() > command.max_bytes {
+        return Err(format!(
+            "{} is over the {} KB limit for the external command",
+            path.to_string_lossy(),
+            command.max_bytes / 1024
+        ));
+    }
+    let path = path.to_string_lossy();
+    let args: Vec<String> = command
+        .args
+        .iter()
+        .map(|arg| arg.replace("{path}", &path))
+        .collect();
+    let Some((program, program_args)) = args.split_first() else {
+        return Err("The external command is empty".to_string());
+    };
+
+    let mut process = Command::new(program);
+    process
+        .args(program_args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        // CREATE_NO_WINDOW, so no console flashes up for every file
+        process.creation_flags(0x0800_0000);
+    }
+    let mut child = process
+        .spawn()
+        .map_err(|e| format!("Failed to start '{}': {}", program, e))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+
+    // Feed and drain the pipes on their own threads so neither side blocks the other
+    let (status, output) = std::thread::scope(|scope| {
+        scope.spawn(move || {
+            // A command that doesn't read all its input closes the pipe early; that's fine
+            let _ = stdin.write_all(content.as_bytes());
+        });
+        let reader = scope.spawn(move || {
+            let mut output = Vec::new();
+            let mut buffer = [0; 8192];
+            while let Ok(read) = stdout.read(&mut buffer) {
+                if read == 0 {
+                    break;
+                }
+                // Keep draining past the limit so the command can finish writing
+                if output.len() <= command.max_bytes {
+                    output.extend_from_slice(&buffer[..read]);
+                }
+            }
+            output
+        });
+
+        let deadline = Instant::now() + command.timeout;
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break Ok(status),
+                Ok(None) if Instant::now() >= deadline => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    break Err(format!(
+                        "'{}' timed out after {} s",
+                        program,
+                        command.timeout.as_secs()
+                    ));
+                }
+                Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+                Err(e) => break Err(format!("Failed to wait for '{}': {}", program, e)),
+            }
+        };
+        (status, reader.join().unwrap_or_default())
+    });
+
+    let status = status?;
+    if !status.success() {
+        return Err(format!("'{}' exited with {}", program, status));
+    }
+    if output.len() > command.max_bytes {
+        return Err(format!(
+            "'{}' produced more than {} KB",
+            program,
+            command.max_bytes / 1024
+        ));
+    }
+    String::from_utf8(output).map_err(|_| format!("'{}' didn't output valid UTF-8", program))
+}
+
+fn truncate_lines(content: String, max_lines: usize) -> String {
+    let total = content.lines().count();
+    if total <= max_lines {
+        return content;
+    }
+    let mut kept: Vec<&str> = content.lines().take(max_lines).collect();
+    let note = format!("... ({} more lines truncated)", total - max_lines);
+    kept.push(&note);
+    kept.join("\n")
+}
+
+/// Line comment markers and whether the language has `/* */` block comments
+struct CommentSyntax {
+    line: &'static [&'static str],
+    block: bool,
+}
+
+const C_LIKE: CommentSyntax = CommentSyntax {
+    line: &["//"],
+    block: true,
+};
+const HASH: CommentSyntax = CommentSyntax {
+    line: &["#"],
+    block: false,
+};
+const DASH_DASH: CommentSyntax = CommentSyntax {
+    line: &["--"],
+    block: false,
+};
+
+fn comment_syntax(path: &Path) -> Option<&'static CommentSyntax> {
+    let file_name = path.file_name()?.to_string_lossy();
+    if matches!(&*file_name, "Dockerfile" | "Makefile" | "CMakeLists.txt") {
+        return Some(&HASH);
+    }
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    match extension.as_str() {
+        "rs" | "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "c" | "h" | "cc" | "cpp" | "hpp"
+        | "cs" | "java" | "kt" | "kts" | "go" | "swift" | "scala" | "dart" | "php" | "css"
+        | "scss" | "less" | "zig" => Some(&C_LIKE),
+        "py" | "pyi" | "rb" | "sh" | "bash" | "zsh" | "yaml" | "yml" | "toml" | "pl" | "r"
+        | "ps1" | "cmake" | "tf" | "nix" => Some(&HASH),
+        "sql" | "lua" | "hs" => Some(&DASH_DASH),
+        _ => None,
+    }
+}
+
+/// Drop lines that are only a comment. Lines mixing code and a comment are kept whole, and
+/// `#!` shebangs are kept.
+fn strip_comment_lines(content: &str, syntax: &CommentSyntax) -> String {
+    let mut kept = Vec::new();
+    let mut in_block = false;
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if in_block {
+            if let Some(end) = trimmed.find("*/") {
+                in_block = false;
+                if !trimmed[end + 2..].trim().is_empty() {
+                    kept.push(line);
+                }
+            }
+            continue;
+        }
+        if syntax.block && trimmed.starts_with("/*") {
+            match trimmed[2..].find("*/") {
+                Some(end) if !trimmed[end + 4..].trim().is_empty() => kept.push(line),
+                Some(_) => {}
+                None => in_block = true,
+            }
+            continue;
+        }
+        let is_line_comment = syntax.line.iter().any(|marker| trimmed.starts_with(marker))
+            && !trimmed.starts_with("#!");
+        if !is_line_comment {
+            kept.push(line);
+        }
+    }
+    kept.join("\n")
+}
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Names like `api_key`, `DB_PASSWORD` or `clientSecret`
+const SECRET_NAME: &str = r"[A-Za-z0-9_-]*(?:api[_-]?key|secret|token|passw(?:or)?d|pwd|access[_-]?key|private[_-]?key)[A-Za-z0-9_-]*";
+
+/// String literals assigned to a secret-sounding name, in code or config
+static QUOTED_SECRET: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r#"(?i)({}["']?\s*[:=]\s*["'])([^"'\s]{{4,}})(["'])"#,
+        SECRET_NAME
+    ))
+    .expect("quoted secret pattern is valid")
+});
+
+/// `.env`, YAML or INI lines setting a secret-sounding name to a bare value. Values with
+/// parentheses are left alone, since those are code like `token = get_token()`.
+static BARE_SECRET: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r#"(?im)^([ \t]*(?:export[ \t]+)?{}[ \t]*[:=][ \t]*)([^\s"'#()]{{4,}})()[ \t]*$"#,
+        SECRET_NAME
+    ))
+    .expect("bare secret pattern is valid")
+});
+
+/// Credentials recognisable by their shape alone
+static SECRET_TOKENS: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    [
+        r"\bAKIA[0-9A-Z]{16}\b",
+        r"\bgh[pousr]_[A-Za-z0-9]{36,}\b",
+        r"\bxox[abposr]-[A-Za-z0-9-]{10,}",
+        r"\bsk-[A-Za-z0-9_-]{20,}",
+        r"(?i)\bbearer\s+[A-Za-z0-9._~+/-]{16,}=*",
+    ]
+    .iter()
+    .map(|pattern| Regex::new(pattern).expect("secret token pattern is valid"))
+    .collect()
+});
+
+static PRIVATE_KEY_BLOCK: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"-----BEGIN [A-Z ]*PRIVATE KEY-----[\s\S]*?-----END [A-Z ]*PRIVATE KEY-----")
+        .expect("private key pattern is valid")
+});
+
+/// Mask credentials, keeping every line where it was
+fn redact_secrets(content: &str) -> String {
+    let content = PRIVATE_KEY_BLOCK.replace_all(content, |caps: &Captures| {
+        let newlines = caps[0].matches('\n').count();
+        format!("[REDACTED PRIVATE KEY]{}", "\n".repeat(newlines))
+    });
+    let mut content = content.into_owned();
+    for pattern in [&*QUOTED_SECRET, &*BARE_SECRET] {
+        content = pattern
+            .replace_all(&content, |caps: &Captures| {
+                format!("{}{}{}", &caps[1], REDACTED, &caps[3])
+            })
+            .into_owned();
+    }
+    for pattern in SECRET_TOKENS.iter() {
+        content = pattern.replace_all(&content, REDACTED).into_owned();
+    }
+    content
+}
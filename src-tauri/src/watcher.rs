@@ -0,0 +1,606 @@
+use crate::config;
+use crate::fs::{tree_entry, within_visible_dirs, PathFilter};
+use crate::index;
+use crate::models::{AppError, DirectoryItem};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+/// Quiet period the tree watcher waits for, so a burst of changes (a checkout, an unzip)
+/// goes out as one event
+const TREE_EVENT_DEBOUNCE_MS: u64 = 300;
+
+/// Quiet period after a selected file changes before live mode regenerates, so a burst of
+/// saves (format on save, a refactor touching many files) regenerates once
+const LIVE_DEBOUNCE_MS: u64 = 500;
+
+/// Longest a watcher holds changes back while they keep coming
+const MAX_EVENT_DELAY_MS: u64 = 2000;
+
+/// Payload for the config file events sent to the frontend
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigFileEvent {
+    pub path: String,
+}
+
+/// Payload of the "tree-changed" event: entries to add to and remove from the loaded tree
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TreeChangePayload {
+    pub root: String,
+    pub separator: String,
+    /// New or renamed-to entries; directories come with their subtrees
+    pub added: Vec<DirectoryItem>,
+    /// Absolute paths of entries that are gone or no longer shown
+    pub removed: Vec<String>,
+    /// Entries renamed or moved within the project, for carrying the selection over
+    pub renamed: Vec<RenamedPath>,
+}
+
+/// An entry renamed or moved within the project, by absolute path
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenamedPath {
+    pub from: String,
+    pub to: String,
+}
+
+/// What the tree watcher passes to its debounce thread
+enum TreeEvent {
+    Changed(PathBuf),
+    Renamed(PathBuf, PathBuf),
+    /// Old name of a rename reported in two halves (Windows)
+    RenamedFrom(PathBuf),
+    /// New name of a rename reported in two halves
+    RenamedTo(PathBuf),
+    /// Wakes the debounce thread to send what it held back while paused
+    Resumed,
+}
+
+/// Watches the project and global config files and puts them back if they get deleted
+/// while the app is running.
+///
+/// Dropping this stops the watch.
+pub struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+    shared: Arc<WatchState>,
+}
+
+/// State shared between the watcher callback, the ConfigWatcher and any pause guards
+struct WatchState {
+    app_handle: AppHandle,
+    config_paths: Vec<PathBuf>,
+    // Last known contents of each file, kept so a deleted file can be written back verbatim
+    snapshots: Mutex<HashMap<PathBuf, String>>,
+    paused_by_user: AtomicBool,
+    // Operations (like a generation) currently holding a WatchPauseGuard
+    operation_pauses: AtomicUsize,
+}
+
+impl WatchState {
+    fn is_paused(&self) -> bool {
+        self.paused_by_user.load(Ordering::SeqCst)
+            || self.operation_pauses.load(Ordering::SeqCst) > 0
+    }
+
+    /// Check every file once, to pick up anything that happened while events were ignored
+    fn catch_up(&self) {
+        if self.is_paused() {
+            return;
+        }
+        for path in &self.config_paths {
+            handle_config_change(&self.app_handle, path, &self.snapshots);
+        }
+    }
+}
+
+impl ConfigWatcher {
+    /// Ignore change events until `resume` is called
+    pub fn pause(&self) {
+        self.shared.paused_by_user.store(true, Ordering::SeqCst);
+    }
+
+    /// Handle change events again, after catching up on what was missed
+    pub fn resume(&self) {
+        self.shared.paused_by_user.store(false, Ordering::SeqCst);
+        self.shared.catch_up();
+    }
+
+    pub fn is_paused_by_user(&self) -> bool {
+        self.shared.paused_by_user.load(Ordering::SeqCst)
+    }
+
+    /// Ignore change events until the returned guard is dropped
+    pub fn pause_for_operation(&self) -> WatchPauseGuard {
+        self.shared.operation_pauses.fetch_add(1, Ordering::SeqCst);
+        WatchPauseGuard {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// Keeps a ConfigWatcher paused while a bulk operation runs
+pub struct WatchPauseGuard {
+    shared: Arc<WatchState>,
+}
+
+impl Drop for WatchPauseGuard {
+    fn drop(&mut self) {
+        self.shared.operation_pauses.fetch_sub(1, Ordering::SeqCst);
+        self.shared.catch_up();
+    }
+}
+
+/// Start watching the project config in `project_dir` (if any) and, unless the user
+/// protects it from being written, the global `.gptreerc`
+pub fn watch_config_files(
+    app_handle: AppHandle,
+    project_dir: Option<&Path>,
+    watch_global: bool,
+) -> Result<ConfigWatcher, AppError> {
+    let mut config_paths: Vec<PathBuf> = Vec::new();
+    if let Some(dir) = project_dir {
+        config_paths.push(config::project_config_path(dir));
+    }
+    if let Some(global_path) = config::global_config_path().filter(|_| watch_global) {
+        config_paths.push(global_path);
+    }
+
+    let snapshots = config_paths
+        .iter()
+        .filter_map(|path| {
+            fs::read_to_string(path)
+                .ok()
+                .map(|content| (path.clone(), content))
+        })
+        .collect();
+    let shared = Arc::new(WatchState {
+        app_handle,
+        config_paths,
+        snapshots: Mutex::new(snapshots),
+        paused_by_user: AtomicBool::new(false),
+        operation_pauses: AtomicUsize::new(0),
+    });
+
+    let state = shared.clone();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        let event = match res {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("[GPTree] Config watcher error: {}", e);
+                return;
+            }
+        };
+
+        if state.is_paused() {
+            return;
+        }
+        for path in event
+            .paths
+            .iter()
+            .filter(|p| state.config_paths.contains(p))
+        {
+            handle_config_change(&state.app_handle, path, &state.snapshots);
+        }
+    })
+    .map_err(|e| AppError::Watch(e.to_string()))?;
+
+    // Watch the parent directories rather than the files, since a watch on a
+    // deleted file is gone along with it
+    for path in &shared.config_paths {
+        if let Some(parent) = path.parent().filter(|p| p.is_dir()) {
+            watcher
+                .watch(parent, RecursiveMode::NonRecursive)
+                .map_err(|e| AppError::Watch(e.to_string()))?;
+        }
+    }
+
+    Ok(ConfigWatcher {
+        _watcher: watcher,
+        shared,
+    })
+}
+
+fn handle_config_change(
+    app_handle: &AppHandle,
+    path: &Path,
+    snapshots: &Mutex<HashMap<PathBuf, String>>,
+) {
+    let mut snapshots = snapshots.lock().unwrap();
+
+    if path.exists() {
+        // Skip the empty file seen between a save truncating it and writing it out
+        if let Ok(content) = fs::read_to_string(path) {
+            if !content.is_empty() {
+                snapshots.insert(path.to_path_buf(), content);
+            }
+        }
+        return;
+    }
+
+    // The whole project went away, which load_directory reports on its own
+    if !path.parent().is_some_and(Path::is_dir) {
+        return;
+    }
+
+    let payload = ConfigFileEvent {
+        path: path.to_string_lossy().to_string(),
+    };
+
+    match snapshots.get(path) {
+        Some(content) => match fs::write(path, content) {
+            Ok(()) => {
+                eprintln!("[GPTree] Recreated deleted config file {:?}", path);
+                let _ = app_handle.emit("config-file-recreated", payload);
+            }
+            Err(e) => {
+                eprintln!("[GPTree] Failed to recreate config file {:?}: {}", path, e);
+                let _ = app_handle.emit("config-file-missing", payload);
+            }
+        },
+        // Nothing to restore from, so let the user decide what to do
+        None => {
+            let _ = app_handle.emit("config-file-missing", payload);
+        }
+    }
+}
+
+/// Watches a loaded project for entries being created, deleted or renamed, and sends the
+/// frontend "tree-changed" events so the tree stays current without a full reload.
+///
+/// Only directories the tree shows are watched, one watch each, so ignored folders like
+/// node_modules don't use up the system's watch limit.
+///
+/// Dropping this stops the watch.
+pub struct TreeWatcher {
+    _watcher: Arc<Mutex<RecommendedWatcher>>,
+    paused: Arc<AtomicBool>,
+    sender: mpsc::Sender<TreeEvent>,
+}
+
+impl TreeWatcher {
+    /// Hold changes back until `resume` is called
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Send the changes held back while paused
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        let _ = self.sender.send(TreeEvent::Resumed);
+    }
+}
+
+/// Start watching the tree under `root_dir`, judging changed entries with the same filter
+/// and excluded directories the tree was loaded with
+pub fn watch_tree(
+    app_handle: AppHandle,
+    root_dir: &Path,
+    filter: PathFilter,
+    excluded_dirs_config: HashSet<String>,
+) -> Result<TreeWatcher, AppError> {
+    let (sender, receiver) = mpsc::channel::<TreeEvent>();
+    let resume_sender = sender.clone();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        let event = match res {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("[GPTree] Tree watcher error: {}", e);
+                return;
+            }
+        };
+        // inotify pairs up the old and new names of a rename, Windows reports them as two
+        // events, and FSEvents only says a name changed (matched up after the burst)
+        match (event.kind, event.paths.as_slice()) {
+            (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) => {
+                let _ = sender.send(TreeEvent::Renamed(from.clone(), to.clone()));
+            }
+            (EventKind::Modify(ModifyKind::Name(RenameMode::From)), [from]) => {
+                let _ = sender.send(TreeEvent::RenamedFrom(from.clone()));
+            }
+            (EventKind::Modify(ModifyKind::Name(RenameMode::To)), [to]) => {
+                let _ = sender.send(TreeEvent::RenamedTo(to.clone()));
+            }
+            _ => {}
+        }
+        // Content changes don't affect the tree
+        if matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))
+        ) {
+            for path in event.paths {
+                let _ = sender.send(TreeEvent::Changed(path));
+            }
+        }
+    })
+    .map_err(|e| AppError::Watch(e.to_string()))?;
+    for dir in visible_dirs(root_dir, &filter) {
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(|e| AppError::Watch(e.to_string()))?;
+    }
+    let watcher = Arc::new(Mutex::new(watcher));
+    let paused = Arc::new(AtomicBool::new(false));
+
+    // The senders live in the watcher's callback and the TreeWatcher, so this thread ends
+    // once the TreeWatcher is dropped
+    let root_dir = root_dir.to_path_buf();
+    let thread_watcher = Arc::downgrade(&watcher);
+    let thread_paused = paused.clone();
+    std::thread::spawn(move || {
+        let mut changed = BTreeSet::new();
+        let mut renames = Vec::new();
+        while let Ok(first) = receiver.recv() {
+            let mut pending_from = None;
+            let mut burst = BTreeSet::new();
+            for event in collect_burst(&receiver, first, TREE_EVENT_DEBOUNCE_MS) {
+                match event {
+                    TreeEvent::Changed(path) => {
+                        burst.insert(path);
+                    }
+                    TreeEvent::Renamed(from, to) => renames.push((from, to)),
+                    TreeEvent::RenamedFrom(from) => pending_from = Some(from),
+                    TreeEvent::RenamedTo(to) => {
+                        if let Some(from) = pending_from.take() {
+                            renames.push((from, to));
+                        }
+                    }
+                    TreeEvent::Resumed => {}
+                }
+            }
+            // New folders need watches of their own, paused or not, so nothing inside them is missed
+            update_watches(&thread_watcher, &root_dir, &filter, &burst);
+            changed.extend(burst);
+            if thread_paused.load(Ordering::SeqCst) {
+                continue;
+            }
+
+            let mut renames = std::mem::take(&mut renames);
+            let changed = std::mem::take(&mut changed);
+            let moved = match_moved_entries(&app_handle, &root_dir, &changed, &renames);
+            renames.extend(moved);
+            if !renames.is_empty() {
+                if let Err(e) = config::remap_previous_files(&root_dir, &renames) {
+                    eprintln!("[GPTree] Failed to update renamed previous files: {}", e);
+                }
+            }
+            emit_tree_changes(
+                &app_handle,
+                &root_dir,
+                &filter,
+                &excluded_dirs_config,
+                &changed,
+                &renames,
+            );
+        }
+    });
+
+    Ok(TreeWatcher {
+        _watcher: watcher,
+        paused,
+        sender: resume_sender,
+    })
+}
+
+/// `dir` and every directory below it that the tree shows, without following symlinks
+fn visible_dirs(dir: &Path, filter: &PathFilter) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        if let Ok(entries) = fs::read_dir(&current) {
+            pending.extend(
+                entries
+                    .filter_map(Result::ok)
+                    .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+                    .map(|entry| entry.path())
+                    .filter(|path| filter.should_show(path, true)),
+            );
+        }
+        dirs.push(current);
+    }
+    dirs
+}
+
+/// Watch directories that appeared in a burst and drop the watches of ones that went away
+fn update_watches(
+    watcher: &Weak<Mutex<RecommendedWatcher>>,
+    root_dir: &Path,
+    filter: &PathFilter,
+    changed: &BTreeSet<PathBuf>,
+) {
+    let Some(watcher) = watcher.upgrade() else {
+        return;
+    };
+    let mut watcher = watcher.lock().unwrap();
+    for path in changed {
+        if !path.exists() {
+            // Most backends drop these on their own, so a failure here means nothing
+            let _ = watcher.unwatch(path);
+        } else if path.is_dir()
+            && !path.is_symlink()
+            && within_visible_dirs(root_dir, path, filter)
+            && filter.should_show(path, true)
+        {
+            for dir in visible_dirs(path, filter) {
+                if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                    eprintln!("[GPTree] Failed to watch {:?}: {}", dir, e);
+                }
+            }
+        }
+    }
+}
+
+/// Renames the platform didn't pair up: each entry that vanished in the burst is matched
+/// with the one new entry that has the size and modified time the index recorded for it
+fn match_moved_entries(
+    app_handle: &AppHandle,
+    root_dir: &Path,
+    changed: &BTreeSet<PathBuf>,
+    renames: &[(PathBuf, PathBuf)],
+) -> Vec<(PathBuf, PathBuf)> {
+    let paired: HashSet<&PathBuf> = renames.iter().flat_map(|(from, to)| [from, to]).collect();
+    let (appeared, vanished): (Vec<&PathBuf>, Vec<&PathBuf>) = changed
+        .iter()
+        .filter(|path| !paired.contains(path))
+        .partition(|path| path.exists());
+    if appeared.is_empty() || vanished.is_empty() {
+        return Vec::new();
+    }
+    // Without an index there's nothing to tell what the vanished entries looked like
+    let Some(project_index) = index::load_index(app_handle, root_dir) else {
+        return Vec::new();
+    };
+    let recorded: HashMap<&str, &index::IndexEntry> = project_index
+        .entries
+        .iter()
+        .map(|entry| (entry.path.as_str(), entry))
+        .collect();
+    let appeared: Vec<(&PathBuf, bool, (u64, u64))> = appeared
+        .into_iter()
+        .filter_map(|path| Some((path, path.is_dir(), index::entry_stamp(path)?)))
+        .collect();
+
+    let mut used = HashSet::new();
+    let mut moved = Vec::new();
+    for from in vanished {
+        let Some(entry) = from
+            .strip_prefix(root_dir)
+            .ok()
+            .and_then(|relative| recorded.get(&*relative.to_string_lossy()))
+        else {
+            continue;
+        };
+        let mut candidates = appeared.iter().filter(|(path, is_dir, stamp)| {
+            !used.contains(path)
+                && *is_dir == entry.is_dir
+                && *stamp == (entry.size, entry.modified)
+        });
+        // Two lookalikes (say, empty files) could be either, so leave those as remove + add
+        if let (Some((to, _, _)), None) = (candidates.next(), candidates.next()) {
+            used.insert(*to);
+            moved.push((from.clone(), (*to).clone()));
+        }
+    }
+    moved
+}
+
+fn emit_tree_changes(
+    app_handle: &AppHandle,
+    root_dir: &Path,
+    filter: &PathFilter,
+    excluded_dirs_config: &HashSet<String>,
+    changed: &BTreeSet<PathBuf>,
+    renames: &[(PathBuf, PathBuf)],
+) {
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+    for path in changed {
+        // An entry inside a directory that's in this batch comes along with that directory
+        let covered = path
+            .ancestors()
+            .skip(1)
+            .any(|ancestor| changed.contains(ancestor) && ancestor.exists());
+        // Entries under hidden directories (.git, node_modules, ...) were never in the tree
+        if covered || path == root_dir || !within_visible_dirs(root_dir, path, filter) {
+            continue;
+        }
+        match tree_entry(root_dir, path, filter, excluded_dirs_config) {
+            Some(item) => added.push(item),
+            None => removed.push(path.to_string_lossy().to_string()),
+        }
+    }
+    let renamed: Vec<RenamedPath> = renames
+        .iter()
+        .map(|(from, to)| RenamedPath {
+            from: from.to_string_lossy().to_string(),
+            to: to.to_string_lossy().to_string(),
+        })
+        .collect();
+    if added.is_empty() && removed.is_empty() && renamed.is_empty() {
+        return;
+    }
+
+    let payload = TreeChangePayload {
+        root: root_dir.to_string_lossy().to_string(),
+        separator: std::path::MAIN_SEPARATOR.to_string(),
+        added,
+        removed,
+        renamed,
+    };
+    if let Err(e) = app_handle.emit("tree-changed", payload) {
+        eprintln!("[GPTree] Failed to emit tree change: {}", e);
+    }
+}
+
+/// `first` and whatever else arrives before the channel has been quiet for `debounce_ms`,
+/// giving up on quiet after MAX_EVENT_DELAY_MS
+fn collect_burst<T>(receiver: &mpsc::Receiver<T>, first: T, debounce_ms: u64) -> Vec<T> {
+    let started = Instant::now();
+    let mut burst = vec![first];
+    while started.elapsed() < Duration::from_millis(MAX_EVENT_DELAY_MS) {
+        match receiver.recv_timeout(Duration::from_millis(debounce_ms)) {
+            Ok(item) => burst.push(item),
+            Err(_) => break,
+        }
+    }
+    burst
+}
+
+/// Watches the selected files while live mode is on, calling back once edits to them settle.
+/// Callbacks run one at a time; changes made during one are picked up by the next.
+///
+/// Dropping this stops the watch.
+pub struct LiveWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+/// Start watching `files`, calling `on_change` after they've been edited
+pub fn watch_selection(
+    files: &[PathBuf],
+    on_change: impl Fn() + Send + 'static,
+) -> Result<LiveWatcher, AppError> {
+    let selected: HashSet<PathBuf> = files.iter().cloned().collect();
+    let (sender, receiver) = mpsc::channel::<()>();
+    let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+        let event = match res {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("[GPTree] Live mode watcher error: {}", e);
+                return;
+            }
+        };
+        if !matches!(event.kind, EventKind::Access(_))
+            && event.paths.iter().any(|path| selected.contains(path))
+        {
+            let _ = sender.send(());
+        }
+    })
+    .map_err(|e| AppError::Watch(e.to_string()))?;
+
+    // Watch the folders rather than the files, since editors often save by renaming a new
+    // file over the old one, which ends a watch on the file itself
+    let folders: BTreeSet<&Path> = files.iter().filter_map(|file| file.parent()).collect();
+    for folder in folders.into_iter().filter(|folder| folder.is_dir()) {
+        watcher
+            .watch(folder, RecursiveMode::NonRecursive)
+            .map_err(|e| AppError::Watch(e.to_string()))?;
+    }
+
+    // As with the tree watcher, this thread ends once the watcher is dropped
+    std::thread::spawn(move || {
+        while let Ok(first) = receiver.recv() {
+            collect_burst(&receiver, first, LIVE_DEBOUNCE_MS);
+            on_change();
+        }
+    });
+
+    Ok(LiveWatcher { _watcher: watcher })
+}
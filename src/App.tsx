@@ -1,22 +1,23 @@
 import { useState, useEffect, useCallback, useRef, useMemo } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import DirectoryTree from "./components/DirectoryTree";
 import ConfigPanel from "./components/ConfigPanel";
 import OutputPanel from "./components/OutputPanel";
 import SettingsModal from "./components/SettingsModal";
 // import GptreeLogo from './assets/gptree_logo.svg?react';
 import { Tooltip } from 'react-tooltip';
-import { ArrowClockwise, Funnel, Gear, Moon, Sun } from '@phosphor-icons/react';
+import { ArrowClockwise, Funnel, Gear, Moon, Star, Sun } from '@phosphor-icons/react';
 import { cn } from './lib/utils';
 import { HEADER_LINK, GITHUB_LINK, VERSION_NAME, DISPLAY_VERSION_RIBBON } from './lib/constants';
-import { DirectoryItem, Config, OutputContent, AppError, CommandResult, AppSettings, SessionState } from './lib/types';
+import { DirectoryItem, Config, OutputContent, AppError, CommandResult, AppSettings, SessionState, ScanProgress, StartupProbe, TreeChange, LiveOutput, ExclusionSuggestion, IgnoreReason, RecentDirectory, FavoriteProject, DirectoryLoadResult, CommandApprovalDetails, MenuAction, TreeUpdate } from './lib/types';
 import { motion, AnimatePresence } from 'motion/react';
 import { useTheme, ThemeProvider } from './components/ThemeProvider';
-import { truncatePathStart } from './lib/index';
+import { applyTreeChange, truncatePathStart } from './lib/index';
 import { useWindowSize } from './hooks/useWindowSize';
 import { sendSignal } from './hooks/signalEmitter';
-import { settingsAtom } from './lib/store/atoms';
-import { useAtom } from 'jotai';
+import { debugEnabledAtom, settingsAtom } from './lib/store/atoms';
+import { useAtom, useSetAtom } from 'jotai';
 
 const DEFAULT_DIRECTORY = import.meta.env.VITE_DEFAULT_DIRECTORY || '';
 
@@ -55,6 +56,8 @@ function App() {
   // -----------------------------------------
 
   const [output, setOutput] = useState<OutputContent | null>(null);
+  const [liveMode, setLiveMode] = useState(false); // Regenerate whenever a selected file changes
+  const [diffMode, setDiffMode] = useState(false); // Embed changes since HEAD instead of whole files
   const [loading, setLoading] = useState<boolean>(false);
   const [error, setError] = useState<string | null>(null);
   const [transientSuccess, setTransientSuccess] = useState<string | null>(null);
@@ -62,6 +65,7 @@ function App() {
 
   const [isSettingsModalOpen, setIsSettingsModalOpen] = useState(false);
   const [settings, setSettings] = useAtom(settingsAtom);
+  const setDebugEnabled = useSetAtom(debugEnabledAtom);
 
   const [initialConfigModePreference, setInitialConfigModePreference] = useState<'global' | 'local' | null>(null);
 
@@ -74,7 +78,17 @@ function App() {
 
   const [showLoadingIndicator, setShowLoadingIndicator] = useState<boolean>(false);
   const loadingTimerRef = useRef<number | null>(null);
+  // Set once the user confirms opening their home directory or a drive root; the backend
+  // refuses to scan or generate from those without it
+  const allowDangerousRootRef = useRef(false);
+  // Set when the startup probe already asked about reopening a large or slow folder, so the
+  // load that follows doesn't ask again
+  const scanConfirmedRef = useRef(false);
   const LOADING_DELAY = 300;
+  const [recentDirectories, setRecentDirectories] = useState<RecentDirectory[]>([]);
+  const [favorites, setFavorites] = useState<FavoriteProject[]>([]);
+  const [filesScanned, setFilesScanned] = useState<number | null>(null); // From 'scan-progress' while a directory is walked
+  const [remoteTaskId, setRemoteTaskId] = useState<number | null>(null); // Task cloning and loading a remote repository
 
   const { width: windowWidth } = useWindowSize();
 
@@ -123,8 +137,182 @@ function App() {
     clearTimeout(loadingTimerRef.current!); // Use ! only if sure it's not null
     setLoading(false);
     setShowLoadingIndicator(false);
+    setFilesScanned(null);
   }, []);
 
+  useEffect(() => {
+    const unlisten = listen<ScanProgress>('scan-progress', event => {
+      setFilesScanned(event.payload.files_scanned);
+    });
+    return () => {
+      unlisten.then(stop => stop());
+    };
+  }, []);
+
+  // Files created, deleted or renamed under the open directory
+  useEffect(() => {
+    const unlisten = listen<TreeChange>('tree-changed', event => {
+      const change = event.payload;
+      setDirectoryTree(prev => (prev && prev.path === change.root ? applyTreeChange(prev, change) : prev));
+      // Renamed files stay selected under their new paths; deleted files can't be generated
+      // from, so they leave the selection
+      if (change.renamed.length > 0 || change.removed.length > 0) {
+        setSelectedFiles(prev => prev
+          .map(file => change.renamed.reduce((current, { from, to }) => (
+            current === from ? to : current.startsWith(from + change.separator) ? to + current.slice(from.length) : current
+          ), file))
+          .filter(file =>
+            !change.removed.some(path => file === path || file.startsWith(path + change.separator))
+          ));
+      }
+    });
+    return () => {
+      unlisten.then(stop => stop());
+    };
+  }, []);
+
+  // A load answered from the index re-walks the directory in the background and sends the
+  // fresh tree when anything changed on disk since
+  useEffect(() => {
+    const unlisten = listen<TreeUpdate>('directory-tree-updated', event => {
+      const { root, tree } = event.payload;
+      setDirectoryTree(prev => (prev && prev.path === root ? tree : prev));
+    });
+    return () => {
+      unlisten.then(stop => stop());
+    };
+  }, []);
+
+  // Live mode follows the selection: restart the watch when it changes, stop it when turned off
+  useEffect(() => {
+    if (!liveMode) {
+      invoke("stop_live_mode").catch(err => log(`Error stopping live mode: ${err}`, 'error'));
+      return;
+    }
+    invoke<CommandResult<null>>("start_live_mode", {
+      selectedFiles,
+      excludedDirs: Array.from(getEffectiveExcludedDirs()),
+      allowDangerousRoot: allowDangerousRootRef.current,
+    }).then(result => {
+      if (!result.success) {
+        sendErrorMessage(String(result.error ?? "Failed to start live mode"));
+        setLiveMode(false);
+      }
+    }).catch(err => log(`Error starting live mode: ${err}`, 'error'));
+    // eslint-disable-next-line react-hooks/exhaustive-deps
+  }, [liveMode, selectedFiles]);
+
+  // Opening another directory ends live mode on the backend
+  useEffect(() => {
+    setLiveMode(false);
+  }, [currentDirectory]);
+
+  useEffect(() => {
+    const unlisten = listen<LiveOutput>('live-output', event => {
+      const live = event.payload;
+      if (live.error) {
+        sendErrorMessage(`Live regeneration failed: ${live.error}`);
+        return;
+      }
+      sendSuccessMessage(
+        `Regenerated ${live.file_count} files: ${live.token_estimate.toLocaleString()} tokens` +
+        (live.copied_to_clipboard ? ', copied to clipboard' : '')
+      );
+    });
+    return () => {
+      unlisten.then(stop => stop());
+    };
+    // eslint-disable-next-line react-hooks/exhaustive-deps
+  }, []);
+
+  // Refresh the recent directories list whenever the welcome screen is shown
+  useEffect(() => {
+    if (currentDirectory) return;
+    invoke<CommandResult<RecentDirectory[]>>("get_recent_directories")
+      .then(result => setRecentDirectories(result.data ?? []))
+      .catch(err => log(`Error loading recent directories: ${err}`, 'error'));
+    // eslint-disable-next-line react-hooks/exhaustive-deps
+  }, [currentDirectory]);
+
+  useEffect(() => {
+    invoke<CommandResult<FavoriteProject[]>>("list_favorites")
+      .then(result => setFavorites(result.data ?? []))
+      .catch(err => log(`Error loading favorites: ${err}`, 'error'));
+    // eslint-disable-next-line react-hooks/exhaustive-deps
+  }, []);
+
+  const isFavorite = favorites.some(favorite => favorite.path === currentDirectory);
+
+  const handleToggleFavorite = async () => {
+    if (!currentDirectory) return;
+    try {
+      const result = isFavorite
+        ? await invoke<CommandResult<FavoriteProject[]>>("remove_favorite", { path: currentDirectory })
+        : await invoke<CommandResult<FavoriteProject[]>>("add_favorite", { path: currentDirectory });
+      if (result.success && result.data) {
+        setFavorites(result.data);
+      } else {
+        sendErrorMessage(String(result.error ?? "Failed to update favorites"));
+      }
+    } catch (err) {
+      log(`Error updating favorites: ${err}`, 'error');
+    }
+  };
+
+  const handleReopenDirectory = async (path: string) => {
+    setCurrentDirectory(path);
+    await loadDirectory(path, settings, initialConfigModePreference);
+  };
+
+  const handleRemoveRecentDirectory = async (path: string) => {
+    try {
+      const result = await invoke<CommandResult<RecentDirectory[]>>("remove_recent_directory", { path });
+      if (result.success && result.data) {
+        setRecentDirectories(result.data);
+      }
+    } catch (err) {
+      log(`Error removing recent directory: ${err}`, 'error');
+    }
+  };
+
+  const handleCancelScan = async () => {
+    try {
+      // A remote repository loads as a task, which can also be stopped while it's cloning
+      if (remoteTaskId !== null) {
+        await invoke("cancel_task", { id: remoteTaskId });
+      }
+      await invoke("cancel_scan");
+    } catch (err) {
+      log(`Error cancelling scan: ${err}`, 'error');
+    }
+  };
+
+  // Start a background task and wait for the CommandResult its blocking command would return
+  const runTask = async <T,>(command: string, args: Record<string, unknown>, onStarted?: (id: number) => void): Promise<CommandResult<T>> => {
+    // Listen before starting, so a task that finishes right away isn't missed
+    const finished = new Set<number>();
+    let wake: (() => void) | null = null;
+    const unlisten = await listen<{ id: number }>('task-finished', event => {
+      finished.add(event.payload.id);
+      wake?.();
+    });
+    try {
+      const started = await invoke<CommandResult<number>>(command, args);
+      if (!started.success || started.data == null) {
+        return { success: false, error: started.error ?? `Failed to start ${command}` };
+      }
+      const id = started.data;
+      onStarted?.(id);
+      while (!finished.has(id)) {
+        await new Promise<void>(resolve => { wake = resolve; });
+      }
+      const result = await invoke<CommandResult<CommandResult<T>>>("get_task_result", { id });
+      return result.success && result.data ? result.data : { success: false, error: result.error };
+    } finally {
+      unlisten();
+    }
+  };
+
   const handleSettingsSaved = useCallback((newSettings: AppSettings) => {
     setSettings(newSettings);
     sendSuccessMessage("Settings saved", 2000);
@@ -178,6 +366,13 @@ function App() {
     }
   }, [log, setError, clearMessages, initialConfigModePreference]);
 
+  const handleOpenRemoteRepo = async () => {
+    const input = window.prompt("Git repository URL (optionally followed by a branch or tag, e.g. https://github.com/user/repo main)");
+    if (!input?.trim()) return;
+    const [url, gitRef] = input.trim().split(/\s+/, 2);
+    await loadDirectory("", settings, initialConfigModePreference, { url, gitRef });
+  };
+
   const fetchConfigs = async (dir: string, currentSettings: AppSettings | null, modePreference: 'global' | 'local' | null) => {
     log(`Fetching configs for: ${dir}`, 'debug');
     try {
@@ -288,24 +483,54 @@ function App() {
     }
   };
 
-  const loadDirectory = async (path: string, currentSettings: AppSettings | null, modePreference: 'global' | 'local' | null) => {
-    log(`Loading directory structure for: ${path}`, 'debug');
+  // With `remote`, the repository is cloned first and `path` is ignored; the clone's path
+  // comes back as the root of the loaded tree
+  const loadDirectory = async (path: string, currentSettings: AppSettings | null, modePreference: 'global' | 'local' | null, remote?: { url: string; gitRef?: string }) => {
+    log(`Loading directory structure for: ${remote ? remote.url : path}`, 'debug');
     clearMessages();
     startLoading(); // Start loading indicator
     setSessionOnlyExcludedDirs(new Set()); // Reset session exclusions on new directory load
     try {
-      const treeResult = await invoke<{ success: boolean; data?: DirectoryItem; error?: string }>(
-        "load_directory",
-        { path }
-      );
+      let confirmed = scanConfirmedRef.current;
+      scanConfirmedRef.current = false;
+      const loadLocal = () =>
+        invoke<{ success: boolean; data?: DirectoryItem; error?: string; code?: string }>(
+          "load_directory",
+          { path, confirmed, allowDangerousRoot: allowDangerousRootRef.current }
+        );
+      allowDangerousRootRef.current = false;
+      let treeResult = remote
+        ? await runTask<DirectoryItem>(
+          "start_load_remote_repo",
+          { url: remote.url, gitRef: remote.gitRef ?? null },
+          setRemoteTaskId
+        ) as { success: boolean; data?: DirectoryItem; error?: string; code?: string }
+        : await loadLocal();
+      if (!remote && treeResult.code === 'dangerous_root' && window.confirm(String(treeResult.error))) {
+        allowDangerousRootRef.current = true;
+        treeResult = await loadLocal();
+      }
+      // A first scan of a very large folder asks before walking it
+      if (!remote && treeResult.code === 'confirm_required' && window.confirm(String(treeResult.error))) {
+        confirmed = true;
+        treeResult = await loadLocal();
+      }
 
       if (treeResult.success && treeResult.data) {
         log('Directory tree loaded successfully', 'debug');
         const newTree = treeResult.data;
         setDirectoryTree(newTree);
+        const missingPrevious = (newTree as DirectoryLoadResult).missing_previous_files ?? [];
+        if (missingPrevious.length > 0) {
+          log(`${missingPrevious.length} remembered files no longer exist: ${missingPrevious.join(', ')}`, 'warn');
+        }
+        const rootPath = remote ? newTree.path : path;
+        if (remote) {
+          setCurrentDirectory(rootPath);
+        }
 
         // Fetch configs and update config state *before* finalizing selections
-        await fetchConfigs(path, currentSettings, modePreference);
+        await fetchConfigs(rootPath, currentSettings, modePreference);
 
         // Now that directoryTree and config state (via fetchConfigs) are updated,
         // proceed with selection logic.
@@ -338,6 +563,7 @@ function App() {
       setConfig(null);
       originalConfigRef.current = null;
     } finally {
+      setRemoteTaskId(null);
       stopLoading(); // Stop loading indicator
     }
   };
@@ -372,6 +598,22 @@ function App() {
     });
   }, [isConfigPanelDirty]); // originalConfigRef is a ref, its changes don't trigger re-render of this callback
 
+  // After GPTree first writes into a git project, offer to keep its files out of the repository
+  const offerGitignore = async () => {
+    try {
+      const offer = await invoke<CommandResult<string[]>>("add_gptree_to_gitignore", { offer: true });
+      if (offer.code !== 'confirm_required' || !window.confirm(String(offer.error))) return;
+      const added = await invoke<CommandResult<string[]>>("add_gptree_to_gitignore", { offer: true, confirmed: true });
+      if (added.success) {
+        sendSuccessMessage(`Added ${added.data?.join(', ')} to .gitignore`);
+      } else {
+        sendErrorMessage(String(added.error ?? "Failed to update .gitignore"));
+      }
+    } catch (err) {
+      log(`Error offering .gitignore entries: ${err}`, 'warn');
+    }
+  };
+
   // Handler for ConfigPanel save button
   const handleSaveConfig = async () => {
     if (!config || !isConfigPanelDirty) return;
@@ -394,7 +636,7 @@ function App() {
       // Store the original config *before* saving for comparison
       const originalConfigForComparison = originalConfigRef.current ? { ...originalConfigRef.current } : null;
 
-      const result = await invoke<{ success: boolean; error?: string | AppError }>(
+      const result = await invoke<{ success: boolean; error?: string | AppError; code?: string }>(
         "update_config",
         { config: configToSend } // Send the transformed config
       );
@@ -403,14 +645,28 @@ function App() {
         // Update the persistent stores (local/global) and original ref
         // The frontend state `config.exclude_dirs` should remain a CSV string
         // So, when we update localConfig/globalConfig, we use the original `config` state
-        if (configMode === 'local') {
+        // A protected global config sends the edit to the project's config, which is now the active one
+        const savedToProject = result.code === 'saved_to_project_config';
+        if (configMode === 'local' || savedToProject) {
           setLocalConfig({ ...config }); // Save the current state (with CSV string for exclude_dirs)
         } else {
           setGlobalConfig({ ...config }); // Save the current state
         }
+        if (savedToProject) {
+          setConfigMode('local');
+          setInitialConfigModePreference('local');
+        }
         originalConfigRef.current = { ...config }; // Update original ref to current saved state (with CSV string)
         setIsConfigPanelDirty(false); // Reset dirty state
-        sendSuccessMessage("Configuration saved", 2000);
+        sendSuccessMessage(
+          savedToProject
+            ? "The global config is protected, so this was saved to the project's config"
+            : "Configuration saved",
+          savedToProject ? 5000 : 2000
+        );
+        if (configMode === 'local' || savedToProject) {
+          offerGitignore();
+        }
 
         // Check if any file filtering settings have changed compared to the *original* config
         const fileFilteringChanged = originalConfigForComparison && (
@@ -428,7 +684,7 @@ function App() {
           log('File filtering settings changed, refreshing directory tree after 1s delay...', 'debug');
           setTimeout(async () => {
             try {
-              await loadDirectory(currentDirectory, settings, configMode as 'global' | 'local');
+              await loadDirectory(currentDirectory, settings, savedToProject ? 'local' : configMode as 'global' | 'local');
             } catch (err) {
               log(`Error refreshing directory tree: ${err}`, 'error');
               setError(`Error refreshing directory tree: ${err}`); // Show error to user
@@ -498,24 +754,41 @@ function App() {
     try {
       startLoading();
       clearMessages();
-      const result = await invoke<{ success: boolean; data?: OutputContent; error?: string }>(
+      const generate = () => invoke<{ success: boolean; data?: OutputContent; error?: string; code?: string; details?: unknown }>(
         "generate_output",
-        { selectedFiles, excludedDirs: effectiveDirsToExcludeArray } // Pass excludedDirs to backend
+        {
+          selectedFiles,
+          excludedDirs: effectiveDirsToExcludeArray, // Pass excludedDirs to backend
+          options: diffMode ? { diff_base: 'HEAD' } : null,
+          allowDangerousRoot: allowDangerousRootRef.current,
+        }
       );
+      let result = await generate();
+      // The project's config wants to run an external command; only run it once the user agrees
+      if (result.code === 'command_not_approved' && window.confirm(String(result.error))) {
+        const { command } = result.details as CommandApprovalDetails;
+        const approval = await invoke<CommandResult<null>>("approve_external_command", { command });
+        if (approval.success) {
+          result = await generate();
+        }
+      }
       if (result.success && result.data) {
         setOutput(result.data);
         if (config?.copy_to_clipboard) {
           setPendingClipboardCopy(true);
         }
+        if (result.data.saved_path) {
+          offerGitignore();
+        }
 
         // If store_files_chosen is true, update config with current selections and exclusions
         if (config.store_files_chosen && configMode === 'local') { // Only for local config as per original request
           const currentPreviousFiles = config.previous_files || [];
           const currentExcludeDirsCsv = config.exclude_dirs || "";
 
-          const newPreviousFiles = selectedFiles.map(sf =>
-            sf.startsWith(currentDirectory + '/') ? sf.substring(currentDirectory.length + 1) : sf
-          );
+          // The backend knows the platform's separators and the external/ layout for files outside the project
+          const projectPaths = await invoke<CommandResult<string[]>>("to_project_paths", { paths: selectedFiles });
+          const newPreviousFiles = projectPaths.success && projectPaths.data ? projectPaths.data : currentPreviousFiles;
           const newExcludedDirsCsv = effectiveDirsToExcludeArray.join(',');
 
           const previousFilesChanged = JSON.stringify(newPreviousFiles) !== JSON.stringify(currentPreviousFiles);
@@ -544,6 +817,16 @@ function App() {
 
       } else if (result.error) {
         setError(result.error);
+        // Point out what similar projects excluded to get back under budget
+        if (result.code === 'output_too_large' || result.code === 'safe_mode_limit') {
+          const suggestions = await invoke<CommandResult<ExclusionSuggestion[]>>(
+            "suggest_exclusions",
+            { excludedDirs: effectiveDirsToExcludeArray }
+          );
+          if (suggestions.success && suggestions.data?.length) {
+            setError(`${result.error}\n\nSimilar projects got under budget by excluding: ${suggestions.data.map(s => s.dir).join(', ')}`);
+          }
+        }
       }
     } catch (err) {
       setError(`Error generating output: ${err}`);
@@ -555,7 +838,29 @@ function App() {
   const handleCopyToClipboard = async () => {
     if (!output) return;
     try {
-      await invoke("copy_to_clipboard", { content: output.combined_content });
+      let result = await invoke<CommandResult<boolean>>("copy_to_clipboard", { content: output.combined_content });
+      // Over the clipboard size limit: copy anyway, or point at a saved file instead
+      if (!result.success && result.code === 'confirm_required') {
+        if (window.confirm(`${result.error}\n\nChoose Cancel to save it to a file instead.`)) {
+          result = await invoke<CommandResult<boolean>>("copy_to_clipboard", { content: output.combined_content, confirmed: true });
+        } else {
+          if (output.saved_path) {
+            sendSuccessMessage(`Not copied; the output is saved at ${output.saved_path}`);
+            return;
+          }
+          const saved = await invoke<CommandResult<string>>("save_output_to_app_data", { content: output.combined_content });
+          if (saved.success && saved.data) {
+            sendSuccessMessage(`Not copied; saved the output to ${saved.data}`);
+          } else {
+            sendErrorMessage(String(saved.error ?? "Failed to save output"));
+          }
+          return;
+        }
+      }
+      if (!result.success) {
+        sendErrorMessage(String(result.error ?? "Error copying to clipboard"));
+        return;
+      }
       sendSuccessMessage("Copied to clipboard!");
     } catch (err) {
       sendErrorMessage(`Error copying to clipboard: ${err}`);
@@ -681,6 +986,31 @@ function App() {
     // and its own filtering logic which uses effectiveExcludedDirs.
   };
 
+  // Check the remembered directory before scanning it, so a stale or huge path can be declined
+  const shouldReopenLastDirectory = async (): Promise<boolean> => {
+    try {
+      const result = await invoke<CommandResult<StartupProbe | null>>("probe_last_directory");
+      const probe = result.success ? result.data : null;
+      if (!probe) return result.success;
+      if (!probe.exists) {
+        log(`Last directory no longer exists: ${probe.path}`, 'info');
+        return false;
+      }
+      let question: string | null = null;
+      if (probe.timed_out) {
+        question = `${probe.path} is slow to respond (it may be on a disconnected drive). Open it anyway?`;
+      } else if (probe.is_large && !probe.has_index) {
+        question = `${probe.path} contains more than ${(probe.entry_estimate - 1).toLocaleString()} files and folders. Open it anyway?`;
+      }
+      if (!question) return true;
+      scanConfirmedRef.current = window.confirm(question);
+      return scanConfirmedRef.current;
+    } catch (e) {
+      log(`Error probing last directory: ${e}`, 'error');
+      return true;
+    }
+  };
+
   useEffect(() => {
     const initializeApp = async () => {
       startLoading();
@@ -705,7 +1035,7 @@ function App() {
               setInitialConfigModePreference(lastConfigMode);
               log(`Initialized with last config mode preference: ${lastConfigMode}`, 'debug');
             }
-            if (lastDirectory) {
+            if (lastDirectory && await shouldReopenLastDirectory()) {
               log(`Found last directory: ${lastDirectory}`, 'debug');
               setCurrentDirectory(lastDirectory);
               await loadDirectory(lastDirectory, loadedSettings, modePrefFromSession);
@@ -767,12 +1097,145 @@ function App() {
 
   const dynamicMaxLength = getDynamicMaxLength(windowWidth);
 
+  // Fill in a lazily loaded folder's children when it's expanded, or everything below it
+  // when it's selected before being expanded
+  const handleLoadChildren = async (path: string, recursive = false): Promise<DirectoryItem | null> => {
+    try {
+      const result = await invoke<CommandResult<DirectoryItem>>("load_directory_children", { path, recursive });
+      if (!result.success || !result.data) {
+        log(`Error loading folder ${path}: ${result.error}`, 'error');
+        return null;
+      }
+      const loaded = result.data;
+      const replaceNode = (item: DirectoryItem): DirectoryItem => {
+        if (item.path === loaded.path) return loaded;
+        if (!item.is_dir || !loaded.path.startsWith(item.path)) return item;
+        return { ...item, children: item.children.map(replaceNode) };
+      };
+      setDirectoryTree(prev => (prev ? replaceNode(prev) : prev));
+      return loaded;
+    } catch (err) {
+      log(`Error loading folder ${path}: ${err}`, 'error');
+      return null;
+    }
+  };
+
+  // Select exactly the files git reports as changed since HEAD, limited to those in the tree
+  const handleSelectChangedFiles = async () => {
+    try {
+      const result = await invoke<CommandResult<string[]>>("get_git_changed_files", { base: 'head' });
+      if (!result.success || !result.data) {
+        sendErrorMessage(String(result.error ?? "Failed to list changed files"));
+        return;
+      }
+      const inTree = new Set(allFilePathsInTree);
+      const changed = result.data.filter(file => inTree.has(file));
+      if (changed.length === 0) {
+        sendErrorMessage("No changed files in the tree");
+        return;
+      }
+      setSelectedFiles(changed);
+      const hidden = result.data.length - changed.length;
+      sendSuccessMessage(`Selected ${changed.length} changed files` + (hidden > 0 ? ` (${hidden} filtered out of the tree)` : ''));
+    } catch (err) {
+      log(`Error listing changed files: ${err}`, 'error');
+    }
+  };
+
+  const handleExplainIgnore = async () => {
+    const path = window.prompt("Path to explain (relative to the project)");
+    if (!path?.trim()) return;
+    try {
+      const result = await invoke<CommandResult<IgnoreReason[]>>("explain_ignore", {
+        path: path.trim(),
+        excludedDirs: Array.from(getEffectiveExcludedDirs()),
+      });
+      if (!result.success || !result.data) {
+        sendErrorMessage(String(result.error ?? "Failed to explain path"));
+        return;
+      }
+      if (result.data.length === 0) {
+        sendSuccessMessage(`${path.trim()} is not hidden by any rule`);
+        return;
+      }
+      const reasons = result.data.map(reason => reason.line ? `${reason.message} (line ${reason.line})` : reason.message);
+      sendErrorMessage(`Hidden because: ${reasons.join('; ')}`);
+    } catch (err) {
+      log(`Error explaining path: ${err}`, 'error');
+    }
+  };
+
   const handleRefreshDirectoryTree = async () => {
     if (!currentDirectory) return;
     // Use the current configMode, not the initial preference, for refresh
     await loadDirectory(currentDirectory, settings, configMode as 'global' | 'local');
   };
 
+  // Write the config file diagnosis and runtime stats to the log, and show it
+  const handleShowDiagnostics = async () => {
+    setDebugEnabled(true);
+    try {
+      if (currentDirectory) {
+        const diagnosis = await invoke<CommandResult<string>>("diagnose_config_file", { mode: configMode });
+        log(`Config file: ${diagnosis.data ?? diagnosis.error}`, 'info');
+      }
+      const stats = await invoke<CommandResult<unknown>>("get_runtime_stats");
+      log(`Runtime stats: ${JSON.stringify(stats.data, null, 2)}`, 'info');
+    } catch (err) {
+      log(`Error collecting diagnostics: ${err}`, 'error');
+    }
+  };
+
+  // Menu selections arrive as 'menu-action' events. The listener is registered once,
+  // so it goes through a ref that always holds this render's handlers.
+  const menuActionRef = useRef<(action: MenuAction) => void>(() => { });
+  menuActionRef.current = (action: MenuAction) => {
+    switch (action.action) {
+      case 'open_folder':
+        handleSelectDirectory(settings);
+        break;
+      case 'open_recent':
+        if (action.path) handleReopenDirectory(action.path);
+        break;
+      case 'generate':
+        handleGenerateOutput();
+        break;
+      case 'copy_output':
+        handleCopyToClipboard();
+        break;
+      case 'settings':
+        setIsSettingsModalOpen(true);
+        break;
+      case 'diagnostics':
+        handleShowDiagnostics();
+        break;
+      default:
+        log(`Unknown menu action: ${action.action}`, 'warn');
+    }
+  };
+
+  // The backend asks for a directory to be opened, e.g. one passed on the command line.
+  // Like menu actions, this goes through a ref so it opens with the current settings.
+  const openDirectoryRef = useRef<(path: string) => void>(() => { });
+  openDirectoryRef.current = (path: string) => {
+    log(`Opening directory from launch argument: ${path}`, 'debug');
+    handleReopenDirectory(path);
+  };
+
+  useEffect(() => {
+    const unlisten = listen<{ path: string }>('open-directory', event => openDirectoryRef.current(event.payload.path));
+    return () => {
+      unlisten.then(stop => stop());
+    };
+  }, []);
+
+  useEffect(() => {
+    const unlisten = listen<MenuAction>('menu-action', event => menuActionRef.current(event.payload));
+    return () => {
+      unlisten.then(stop => stop());
+    };
+  }, []);
+
 
   // Check if file type filtering is active
   const isFilterActive = config && (
@@ -850,6 +1313,13 @@ function App() {
             data-tooltip-id="app-tooltip"
             data-tooltip-content={currentDirectory}>
             <span className="font-medium mr-1">Current:</span> {truncatePathStart(currentDirectory, dynamicMaxLength)}
+            <button
+              onClick={handleToggleFavorite}
+              className="ml-2 p-0.5 align-middle bg-transparent border-none"
+              title={isFavorite ? "Remove from favorites" : "Add to favorites"}
+            >
+              <Star weight={isFavorite ? "fill" : "regular"} />
+            </button>
           </div>
         )}
 
@@ -879,6 +1349,15 @@ function App() {
           >
             Select Directory
           </button>
+          <button
+            onClick={handleOpenRemoteRepo}
+            disabled={loading}
+            className="button text-sm px-3 py-1.5"
+            data-tooltip-id="small-tooltip"
+            data-tooltip-content="Shallow-clone a git repository and load it"
+          >
+            Open Git URL
+          </button>
         </div>
       </header>
 
@@ -937,6 +1416,24 @@ function App() {
           <div className="text-xl font-semibold">Loading</div>
 
           <div className="loader"></div>
+
+          {filesScanned === null && remoteTaskId !== null && (
+            <div className="flex flex-col items-center gap-2">
+              <div className="text-sm">Cloning repository</div>
+              <button className="button text-sm px-3 py-1.5" onClick={handleCancelScan}>
+                Cancel
+              </button>
+            </div>
+          )}
+
+          {filesScanned !== null && (
+            <div className="flex flex-col items-center gap-2">
+              <div className="text-sm">{filesScanned.toLocaleString()} files scanned</div>
+              <button className="button text-sm px-3 py-1.5" onClick={handleCancelScan}>
+                Cancel scan
+              </button>
+            </div>
+          )}
         </div>
       )}
 
@@ -953,6 +1450,55 @@ function App() {
             >
               Select Project Directory
             </button>
+            {favorites.length > 0 && (
+              <div className="mt-6 w-full max-w-lg text-left">
+                <h3 className="text-sm font-semibold mb-2">Favorites</h3>
+                <ul className="flex flex-col gap-1">
+                  {favorites.map(favorite => (
+                    <li key={favorite.path} className="flex items-center gap-2 text-sm">
+                      <Star weight="fill" className="flex-shrink-0" />
+                      <button
+                        onClick={() => handleReopenDirectory(favorite.path)}
+                        disabled={loading}
+                        className="flex-1 truncate text-left hover:underline bg-transparent border-none p-0"
+                        title={favorite.path}
+                      >
+                        {favorite.name}
+                      </button>
+                    </li>
+                  ))}
+                </ul>
+              </div>
+            )}
+            {recentDirectories.length > 0 && (
+              <div className="mt-6 w-full max-w-lg text-left">
+                <h3 className="text-sm font-semibold mb-2">Recent</h3>
+                <ul className="flex flex-col gap-1">
+                  {recentDirectories.map(recent => (
+                    <li key={recent.path} className="group flex items-center gap-2 text-sm">
+                      <button
+                        onClick={() => handleReopenDirectory(recent.path)}
+                        disabled={loading}
+                        className="flex-1 truncate text-left hover:underline bg-transparent border-none p-0"
+                        title={`Last opened ${new Date(recent.lastOpened).toLocaleString()}`}
+                      >
+                        {recent.path}
+                      </button>
+                      {recent.fileCount !== null && (
+                        <span className="text-xs opacity-60">{recent.fileCount.toLocaleString()} files</span>
+                      )}
+                      <button
+                        onClick={() => handleRemoveRecentDirectory(recent.path)}
+                        className="text-xs opacity-0 group-hover:opacity-60 hover:opacity-100 bg-transparent border-none p-0"
+                        title="Remove from recent"
+                      >
+                        ✕
+                      </button>
+                    </li>
+                  ))}
+                </ul>
+              </div>
+            )}
           </div>
         )}
 
@@ -993,6 +1539,8 @@ function App() {
                 configDefinedExclusions={configStringToSet(config?.exclude_dirs)}
                 onToggleUIDirectoryExclusion={handleToggleUIDirectoryExclusion}
                 currentDirectory={currentDirectory}
+                onLoadChildren={handleLoadChildren}
+                onLoadSubtree={path => handleLoadChildren(path, true)}
               />
             </div>
             <div className="flex flex-col gap-2 pt-3 border-t border-border flex-shrink-0">
@@ -1000,11 +1548,16 @@ function App() {
                 <span className="text-xs text-muted-foreground">{selectedFiles?.length ?? 0} of {allFilePathsInTree.length} files selected</span>
                 {config?.store_files_chosen && localConfig?.previous_files?.length && localConfig.previous_files.length > 0 && (
                   <button
-                    onClick={() => {
-                      const absolutePaths = localConfig.previous_files.map(
-                        file => `${currentDirectory}/${file}`
+                    onClick={async () => {
+                      const result = await invoke<CommandResult<string[]>>(
+                        "from_project_paths",
+                        { files: localConfig.previous_files }
                       );
-                      setSelectedFiles(absolutePaths);
+                      if (result.success && result.data) {
+                        setSelectedFiles(result.data);
+                      } else {
+                        sendErrorMessage(String(result.error ?? "Failed to restore the previous selection"));
+                      }
                     }}
                     disabled={loading}
                     className="button text-xs px-2 py-1"
@@ -1012,6 +1565,22 @@ function App() {
                     Use Previous Selection
                   </button>
                 )}
+                <button
+                  onClick={handleSelectChangedFiles}
+                  disabled={loading}
+                  className="button text-xs px-2 py-1"
+                  title="Select the files changed since the last commit"
+                >
+                  Select Changed
+                </button>
+                <button
+                  onClick={handleExplainIgnore}
+                  disabled={loading}
+                  className="button text-xs px-2 py-1"
+                  title="Explain why a file or folder doesn't show in the tree"
+                >
+                  Why Hidden?
+                </button>
               </div>
               <button
                 onClick={handleGenerateOutput}
@@ -1020,6 +1589,23 @@ function App() {
               >
                 Generate Output
               </button>
+              <label className="flex items-center gap-2 text-xs text-muted-foreground" title="Regenerate the output whenever a selected file changes">
+                <input
+                  type="checkbox"
+                  checked={liveMode}
+                  onChange={e => setLiveMode(e.target.checked)}
+                  disabled={selectedFiles.length === 0}
+                />
+                Live mode
+              </label>
+              <label className="flex items-center gap-2 text-xs text-muted-foreground" title="Include each file's changes since the last commit instead of its full contents">
+                <input
+                  type="checkbox"
+                  checked={diffMode}
+                  onChange={e => setDiffMode(e.target.checked)}
+                />
+                Diffs only
+              </label>
             </div>
           </div>
         )}
@@ -156,6 +156,18 @@ const ConfigPanel: React.FC<ConfigPanelProps> = ({
               Show default ignored files (respects .gitignore)
             </label>
           </div>
+          <div className="config-option flex flex-col sm:flex-row sm:items-center gap-2 mb-2">
+            <label htmlFor="default-ignores" className="flex-shrink-0 mb-1 sm:mb-0">Default ignores:</label>
+            <Input
+              type="text"
+              id="default-ignores"
+              value={(config.default_ignores ?? []).join(',')}
+              onChange={(e) => handleChange('default_ignores', e.target.value.split(',').map(s => s.trim()))}
+              placeholder="node_modules,.git,..."
+              disabled={disabled}
+              className="w-full"
+            />
+          </div>
         </div>
 
         <div className="config-section mb-4 pb-2">
@@ -289,6 +301,17 @@ const ConfigPanel: React.FC<ConfigPanelProps> = ({
             />
             <label htmlFor="store-files-chosen" className="cursor-pointer">Remember file selection</label>
           </div>
+          <div className="config-option flex items-center gap-2 mb-2">
+            <input
+              type="checkbox"
+              id="prune-missing-previous-files"
+              checked={config.prune_missing_previous_files}
+              onChange={(e) => handleChange('prune_missing_previous_files', e.target.checked)}
+              disabled={disabled || !config.store_files_chosen}
+              className="cursor-pointer disabled:opacity-50"
+            />
+            <label htmlFor="prune-missing-previous-files" className="cursor-pointer">Forget remembered files that no longer exist</label>
+          </div>
         </div>
       </div>
     </div>
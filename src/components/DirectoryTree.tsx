@@ -20,6 +20,8 @@ interface DirectoryTreeProps {
   configDefinedExclusions: Set<string>; // Added: from saved config
   onToggleUIDirectoryExclusion: (path: string) => void; // Renamed from onToggleTemporaryExcludeDir
   currentDirectory: string; // Added to help with relative pathing
+  onLoadChildren?: (path: string) => void; // Called when a folder whose children are pending is expanded
+  onLoadSubtree?: (path: string) => Promise<DirectoryItemType | null>; // Loads everything below a folder before it's selected
 }
 
 const DirectoryTree: React.FC<DirectoryTreeProps> = ({
@@ -31,6 +33,8 @@ const DirectoryTree: React.FC<DirectoryTreeProps> = ({
   configDefinedExclusions,
   onToggleUIDirectoryExclusion, // Renamed
   currentDirectory,
+  onLoadChildren,
+  onLoadSubtree,
 }) => {
   const [expandedFolders, setExpandedFolders] = useState<Set<string>>(() => {
     const initial = new Set<string>();
@@ -102,12 +106,14 @@ const DirectoryTree: React.FC<DirectoryTreeProps> = ({
     setLocalSelectedFiles(newLocalSelected);
   }, [selectedFiles, effectiveExcludedDirs, currentDirectory]);
 
+  // Collapse back to the root only when a different directory is loaded, not when
+  // lazily loaded children are filled into the current tree
+  const rootPath = tree?.is_dir ? tree.path : null;
+  useEffect(() => {
+    setExpandedFolders(rootPath ? new Set([rootPath]) : new Set());
+  }, [rootPath]);
+
   useEffect(() => {
-    if (tree && tree.is_dir) {
-      setExpandedFolders(new Set([tree.path]));
-    } else {
-      setExpandedFolders(new Set());
-    }
     // When tree changes (new directory loaded), parent clears selections.
     // We should reflect that by clearing localSelectedFiles as well.
     // The `selectedFiles` prop will be empty from App.tsx during loadDirectory.
@@ -128,7 +134,17 @@ const DirectoryTree: React.FC<DirectoryTreeProps> = ({
   }, [getDescendantFiles, effectiveExcludedDirs]);
 
 
-  const toggleFolderSelection = (folder: DirectoryItemType) => {
+  // Folders that haven't been expanded in a lazily loaded tree hide files that selecting them should include
+  const hasPendingChildren = (item: DirectoryItemType): boolean =>
+    !!item.children_pending || item.children.some(hasPendingChildren);
+
+  const withLoadedChildren = async (folder: DirectoryItemType): Promise<DirectoryItemType> => {
+    if (!onLoadSubtree || !hasPendingChildren(folder)) return folder;
+    return (await onLoadSubtree(folder.path)) ?? folder;
+  };
+
+  const toggleFolderSelection = async (target: DirectoryItemType) => {
+    const folder = await withLoadedChildren(target);
     const descendantFiles = getDescendantFiles(folder, effectiveExcludedDirs); // Respect exclusions
     if (descendantFiles.length === 0) return;
 
@@ -148,12 +164,13 @@ const DirectoryTree: React.FC<DirectoryTreeProps> = ({
     });
   };
 
-  const toggleSelectAll = () => {
-    const currentSelectableFiles = getDescendantFiles(tree, effectiveExcludedDirs); // Respect exclusions
+  const toggleSelectAll = async () => {
     if (selectAllChecked) { // If all are checked, uncheck all
       setLocalSelectedFiles(new Set());
       onFileSelection([]);
     } else { // Check all selectable
+      const loadedTree = await withLoadedChildren(tree);
+      const currentSelectableFiles = getDescendantFiles(loadedTree, effectiveExcludedDirs); // Respect exclusions
       setLocalSelectedFiles(new Set(currentSelectableFiles));
       onFileSelection(currentSelectableFiles);
     }
@@ -176,11 +193,15 @@ const DirectoryTree: React.FC<DirectoryTreeProps> = ({
     setExpandedFolders(new Set());
   };
 
-  const toggleFolder = (path: string) => {
+  const toggleFolder = (folder: DirectoryItemType) => {
+    // Lazily loaded folders fetch their children the first time they're opened
+    if (folder.children_pending && !expandedFolders.has(folder.path)) {
+      onLoadChildren?.(folder.path);
+    }
     setExpandedFolders(prev => {
       const next = new Set(prev);
-      if (next.has(path)) next.delete(path);
-      else next.add(path);
+      if (next.has(folder.path)) next.delete(folder.path);
+      else next.add(folder.path);
       return next;
     });
   };
@@ -233,7 +254,7 @@ const DirectoryTree: React.FC<DirectoryTreeProps> = ({
     const iconPath = getMaterialIconPath(item.name, isFolder, isExpanded);
 
     return (
-      <div key={item.path} className={clsx("flex flex-col pr-1", { 'opacity-50': isEffectivelyExcluded && isFolder })}>
+      <div key={item.path} className={clsx("flex flex-col pr-1", { 'opacity-50': (isEffectivelyExcluded && isFolder) || item.is_default_ignored })}>
         <div
           className={clsx(
             'group flex items-center p-1 rounded',
@@ -274,7 +295,7 @@ const DirectoryTree: React.FC<DirectoryTreeProps> = ({
 
               <span
                 className="cursor-pointer flex items-center"
-                onClick={e => { e.stopPropagation(); if (!isEffectivelyExcluded) toggleFolder(item.path); }}
+                onClick={e => { e.stopPropagation(); if (!isEffectivelyExcluded) toggleFolder(item); }}
               >
                 <img src={iconPath} alt="" className="w-5 h-5 flex-shrink-0" />
               </span>
@@ -307,10 +328,13 @@ const DirectoryTree: React.FC<DirectoryTreeProps> = ({
               { 'cursor-pointer': isFolder && !isEffectivelyExcluded },
               { 'line-through text-muted-foreground': isEffectivelyExcluded && isFolder }
             )}
-            onClick={e => { if (isFolder && !isEffectivelyExcluded) { e.stopPropagation(); toggleFolder(item.path); } }}
-            title={item.name + (isEffectivelyExcluded && isFolder ? ' (Excluded)' : '')}
+            onClick={e => { if (isFolder && !isEffectivelyExcluded) { e.stopPropagation(); toggleFolder(item); } }}
+            title={item.name + (item.symlink_target ? ` -> ${item.symlink_target}` : '') + (isEffectivelyExcluded && isFolder ? ' (Excluded)' : '') + (item.is_default_ignored ? ' (Ignored by default; hidden unless "Show default ignored files" is on)' : '')}
           >
             {item.name}
+            {item.symlink_target && (
+              <span className="text-muted-foreground"> -&gt; {item.symlink_target}</span>
+            )}
           </span>
 
           {isFolder && (
@@ -68,6 +68,22 @@ const SettingsModal: React.FC<SettingsModalProps> = ({
     }
   };
 
+  // Forget the last opened folder so the next launch doesn't try to reopen it
+  const handleForgetLastFolder = async () => {
+    setIsLoading(true);
+    setError(null);
+    try {
+      const result = await invoke<CommandResult<null>>('reset_session');
+      if (!result.success) {
+        setError((result.error as string) ?? 'Failed to forget the last folder.');
+      }
+    } catch (err: any) {
+      setError(`Error forgetting the last folder: ${err.toString()}`);
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
   const handleCancel = () => {
     onOpenChange(false);
   };
@@ -157,6 +173,15 @@ const SettingsModal: React.FC<SettingsModalProps> = ({
         </div>
 
         <DialogFooter>
+          <Button
+            variant="outline"
+            className="mr-auto"
+            onClick={handleForgetLastFolder}
+            disabled={isLoading}
+            title="Start the next launch without reopening the last folder"
+          >
+            Forget Last Folder
+          </Button>
           <Button variant="outline" onClick={handleCancel} disabled={isLoading}>
             Cancel
           </Button>
@@ -1,4 +1,6 @@
 
+import { DirectoryItem, TreeChange } from './types';
+
 /**
  * Truncates a path string from the start if it exceeds maxLength.
  * Prioritizes showing the full final segment (file/directory name).
@@ -51,3 +53,61 @@ export const truncatePathStart = (path: string | null | undefined, maxLength: nu
 
   return "..." + displayFirstPart + separator + lastPart;
 };
+
+// Same order as the backend walk: folders first, then by name
+const compareItems = (a: DirectoryItem, b: DirectoryItem): number => {
+  if (a.is_dir !== b.is_dir) return a.is_dir ? -1 : 1;
+  return a.name < b.name ? -1 : a.name > b.name ? 1 : 0;
+};
+
+const removeEntry = (item: DirectoryItem, path: string, separator: string): DirectoryItem => {
+  const children = item.children
+    .filter(child => child.path !== path)
+    .map(child =>
+      child.is_dir && path.startsWith(child.path + separator)
+        ? removeEntry(child, path, separator)
+        : child
+    )
+    // A folder emptied by the removal disappears, as it would from a fresh walk
+    .filter(child => !child.is_dir || child.children.length > 0 || child.is_excluded_by_config || child.children_pending);
+  return { ...item, children };
+};
+
+const insertEntry = (item: DirectoryItem, entry: DirectoryItem, separator: string): DirectoryItem => {
+  // Folders that haven't been loaded yet pick the entry up when they are
+  if (item.children_pending) return item;
+  const existing = item.children.findIndex(child => child.path === entry.path);
+  if (existing >= 0) {
+    const children = [...item.children];
+    children[existing] = entry;
+    return { ...item, children };
+  }
+  const container = item.children.find(child => child.is_dir && entry.path.startsWith(child.path + separator));
+  if (container) {
+    return {
+      ...item,
+      children: item.children.map(child => (child === container ? insertEntry(child, entry, separator) : child)),
+    };
+  }
+  // Create any folders between this one and the entry
+  const rest = entry.path.slice(item.path.length + separator.length);
+  const nextName = rest.split(separator)[0];
+  const child: DirectoryItem = nextName === rest
+    ? entry
+    : insertEntry(
+      { name: nextName, path: item.path + separator + nextName, is_dir: true, is_selected: false, children: [] },
+      entry,
+      separator
+    );
+  return { ...item, children: [...item.children, child].sort(compareItems) };
+};
+
+/**
+ * Applies a "tree-changed" event to a loaded tree, returning the updated tree.
+ */
+export const applyTreeChange = (tree: DirectoryItem, change: TreeChange): DirectoryItem => {
+  const removed = change.removed.reduce((item, path) => removeEntry(item, path, change.separator), tree);
+  return change.added
+    .filter(entry => entry.path.startsWith(tree.path + change.separator))
+    .reduce((item, entry) => insertEntry(item, entry, change.separator), removed);
+};
@@ -6,6 +6,132 @@ export interface DirectoryItem {
   is_selected: boolean;
   children: DirectoryItem[];
   is_excluded_by_config?: boolean;
+  is_default_ignored?: boolean; // Only shown because default-ignored entries are shown
+  symlink_target?: string; // Where an unfollowed symlink points
+  children_pending?: boolean; // Folder not loaded yet; fetch with load_directory_children
+}
+
+// Files changed since detect_changes last ran on the project (project-relative)
+export interface ChangeReport {
+  had_baseline: boolean; // False on the first call for a project; the lists are then empty
+  added: string[];
+  modified: string[];
+  removed: string[];
+  hashed: number; // Files hashed this time
+  reused: number; // Files whose size and modified time were unchanged, so their hash was reused
+  elapsed_ms: number;
+}
+
+// One rule keeping a path out of the tree, from explain_ignore
+export interface IgnoreReason {
+  kind: string; // e.g. "gitignore", "default_ignore", "config_exclude", "extension_filter"
+  matched_path: string; // The path itself or the parent directory the rule matched
+  source: string | null; // File the rule comes from, e.g. a .gitignore
+  line: number | null;
+  pattern: string | null;
+  message: string;
+}
+
+// Payload of the "live-output" event sent after live mode regenerates the output
+export interface LiveOutput {
+  token_estimate: number;
+  file_count: number;
+  saved_path: string | null;
+  copied_to_clipboard: boolean;
+  error: string | null; // Set instead of the rest when regeneration failed
+}
+
+// Payload of the "tree-changed" event the backend sends while a directory is open
+export interface TreeChange {
+  root: string;
+  separator: string;
+  added: DirectoryItem[]; // Folders come with their subtrees
+  removed: string[]; // Absolute paths
+  renamed: { from: string; to: string }[]; // Absolute paths, in the order they happened
+}
+
+export interface FilterSuggestion {
+  project_types: string[];
+  include_file_types: string;
+  exclude_dirs: string[];
+}
+
+// Something unusual about a file included in a generated output
+export interface FileWarning {
+  path: string;
+  kind: 'long_lines' | 'crlf_line_endings' | 'cr_line_endings' | 'mixed_line_endings' | 'lockfile_skipped' | 'synced_output_folder';
+  message: string;
+}
+
+export interface FileSize {
+  path: string;
+  bytes: number;
+}
+
+// details of an 'output_too_large' error
+export interface OutputTooLargeDetails {
+  limit_mb: number;
+  size_bytes: number;
+  top_files: FileSize[];
+}
+
+// details of a 'secret_file_blocked' error is a list of these
+export interface BlockedFile {
+  path: string;
+  reason: string;
+}
+
+// details of a 'safe_mode_limit' error: deselecting drop_files makes the selection fit
+export interface FitSuggestion {
+  reason: string;
+  drop_files: FileSize[];
+  drop_bytes: number;
+  drop_tokens: number;
+}
+
+// Timing breakdown returned by profile_last_operation
+export interface PhaseTiming {
+  phase: string;
+  ms: number;
+}
+
+export interface OperationProfile {
+  operation: 'scan' | 'generate';
+  finished_at: string;
+  total_ms: number;
+  phases: PhaseTiming[];
+}
+
+export interface KeyFileSuggestion {
+  path: string;
+  reason: string;
+  score: number;
+}
+
+// A file gathered by get_infra_context_files
+export interface InfraFile {
+  path: string;
+  category: 'ci' | 'container' | 'terraform' | 'deployment';
+}
+
+// Compact tree returned by load_directory when called with { flat: true }
+// Node 0 is the root; other paths are prefixes[prefix[i]] + separator + names[i]
+export interface FlatTree {
+  root_path: string;
+  separator: string;
+  prefixes: string[];
+  names: string[];
+  parents: number[];
+  prefix: number[];
+  flags: number[]; // bit 0: directory, bit 1: excluded by config, bit 2: children pending, bit 3: default-ignored
+  symlink_targets?: Record<number, string>; // Node index -> where an unfollowed symlink points
+}
+
+// load_directory returns the tree with extra hints alongside the root item's fields
+export interface DirectoryLoadResult extends DirectoryItem {
+  suggested_filters?: FilterSuggestion | null;
+  suggested_root?: string | null;
+  missing_previous_files?: string[]; // Saved selection entries that no longer exist
 }
 
 export interface Config {
@@ -19,19 +145,143 @@ export interface Config {
   copy_to_clipboard: boolean;
   safe_mode: boolean;
   store_files_chosen: boolean;
+  prune_missing_previous_files: boolean; // Drop saved selection entries that no longer exist on save
   line_numbers: boolean;
+  transforms: string; // Comma-separated steps run over each file in order: line_numbers, truncate:N, strip_comments, redact, replace, command
+  external_command: string; // Program the command step pipes each file through; {path} is the file. Needs approving per project before it runs
+  external_command_timeout_secs: number;
+  external_command_max_kb: number;
+  replacement_rules?: ReplacementRule[]; // Applied by the replace step; run first when the list has no replace step
   show_ignored_in_tree: boolean;
   show_default_ignored_in_tree: boolean;
+  tree_header: string;
+  contents_header: string;
+  file_header_template: string;
+  token_encoding: 'o200k_base' | 'cl100k_base' | 'approximate';
+  target_model: '' | 'gpt-4o' | 'claude' | 'gemini' | 'llama'; // Empty for none
+  output_format: 'text' | 'markdown' | 'xml' | 'json'; // markdown fences each file; xml wraps each file in <document> tags; json is a JsonOutput
+  markdown_front_matter: boolean; // Markdown outputs start with YAML front matter
+  header_include_size: boolean;
+  header_include_modified: boolean;
+  header_include_git_commit: boolean;
+  include_file_hash: boolean;
+  write_manifest: boolean;
+  respect_linguist_attributes: boolean;
+  respect_editorconfig: boolean;
+  never_overwrite_output: boolean;
+  include_loc_summary: boolean;
+  include_symbol_index: boolean;
+  include_dependency_summary: boolean; // Also leaves lockfiles out of the output
+  follow_symlinks: boolean; // When off, symlinks are shown as "name -> target" and not descended into
+  stub_excluded_dirs: boolean; // e.g. "node_modules/ [excluded: 41,230 files, 312.4 MB]"
+  max_output_size_mb: number; // 0 disables the limit
+  max_file_read_mb: number; // 0 reads files whole
+  default_ignores: string[]; // Names always hidden unless show_default_ignored_in_tree, e.g. node_modules, .git
   previous_files: string[];
   exclude_dirs: string;
 }
 
+// Returned by get_config: the active config plus where each field's value comes from
+// A regex find/replace applied to file contents, e.g. masking internal hostnames
+export interface ReplacementRule {
+  pattern: string;
+  replacement: string; // May use capture groups as $1 or ${name}
+}
+
+export interface ConfigWithSources extends Config {
+  sources: Record<string, 'default' | 'global' | 'local' | 'env' | 'session'>; // Keyed by field name
+}
+
+// Per-call options for generate_output
+export interface GenerationOptions {
+  search_pattern?: string | null; // Only include regions matching this regex
+  context_lines?: number | null; // Lines kept around each match (default 3)
+  symbols?: Record<string, string[]>; // Only these definitions from each file, e.g. { "src/processor.rs": ["fn process_output"] }
+  verbatim?: string[]; // Files included exactly as on disk, with no snippets, symbol scoping or line numbers
+  tree_from_selection?: boolean; // Draw the tree from the selected files only, without walking the disk
+  diff_base?: string | null; // Embed each file's diff against this ref instead of its content; unchanged files are left out
+  diff_target?: string | null; // Diff diff_base against this ref instead of the working tree
+}
+
+// Returned by suggest_exclusions, most often excluded first
+export interface ExclusionSuggestion {
+  dir: string; // Relative to the project root
+  project_type: string; // e.g. 'rust' or 'node'
+  times_excluded: number; // How often excluding it brought an output under budget
+}
+
+// Returned by get_selection_drift; paths are relative to the project root
+export interface SelectionDrift {
+  added: string[]; // Selected now but not last time
+  removed: string[]; // Selected last time, still on disk, but not now
+  missing: string[]; // Selected last time but gone from disk
+}
+
+// Returned by load_file_list
+export interface FileListInput {
+  files: string[]; // Absolute paths that exist
+  missing: string[]; // Listed paths that aren't files, as written
+}
+
+// combined_content when the config's output_format is 'json'
+export interface JsonOutput {
+  tree: string;
+  files: JsonOutputFile[];
+  token_estimate: number;
+  symbol_index?: string;
+  line_count_summary?: string;
+  dependency_summary?: string;
+}
+
+export interface JsonOutputFile {
+  path: string;
+  lines: number;
+  tokens: number;
+  metadata: Record<string, string>; // Header details enabled in the config, e.g. size, commit, sha256
+  content: string;
+}
+
+// One selection to generate with generate_batch
+export interface BatchPreset {
+  name: string; // Fills {name} in the file name template, e.g. 'api'
+  selected_files: string[];
+  excluded_dirs?: string[];
+  options?: GenerationOptions;
+}
+
+// Returned by generate_batch, one per preset
+export interface BatchResult {
+  name: string;
+  saved_path?: string | null;
+  file_count: number;
+  token_estimate: number;
+  error?: string | null; // Set when this preset failed; the others still ran
+}
+
 export interface OutputContent {
   tree_structure: string;
   combined_content: string;
   file_details: FileDetail[];
   token_estimate: number;
   saved_path?: string | null;
+  warnings: FileWarning[];
+  model_fit?: ModelFit | null; // Set when the config has a target_model
+}
+
+// The whole output measured in the target model's tokens
+export interface ModelFit {
+  model: string;
+  tokens: number; // Scaled by calibration_factor
+  uncalibrated_tokens: number; // Pass this to record_token_usage as estimated_tokens
+  calibration_factor: number; // 1.0 until usage has been reported for the model
+  context_window: number;
+  exceeds_context_window: boolean;
+}
+
+// Learned from record_token_usage; returned by get_token_calibrations keyed by model
+export interface TokenCalibration {
+  factor: number; // Actual tokens per estimated token
+  samples: number;
 }
 
 export interface AppError {
@@ -44,25 +294,202 @@ export interface AppError {
 export interface FileDetail {
   path: string;
   tokens: number;
+  hash?: string | null;
+  lines: number;
 }
 
+// load_directory_packed and generate_output_packed return the same CommandResult as their
+// JSON counterparts, MessagePack-encoded in an ArrayBuffer
+
 // Define CommandResult interface
 export interface CommandResult<T> {
   success: boolean;
   data?: T | null; // Allow null based on Rust code
   error?: string | AppError | null; // Allow null and AppError based on Rust code
+  code?: string; // Machine-readable error code, e.g. 'confirm_required', 'project_missing'
+  details?: unknown; // Structured error info for some codes, e.g. OutputTooLargeDetails, FitSuggestion or BlockedFile[]
+}
+
+// Details of the 'command_not_approved' result generate_output returns when the project's
+// command transform would run a command the user hasn't approved; see approve_external_command
+export interface CommandApprovalDetails {
+  command: string;
+}
+
+// Payload of the 'directory-tree-updated' event, sent when the background re-walk after a
+// load answered from the index finds the tree changed
+export interface TreeUpdate {
+  root: string;
+  tree: DirectoryItem;
+}
+
+// Payload of the 'menu-action' event sent when a native menu item is chosen
+export interface MenuAction {
+  action: 'open_folder' | 'open_recent' | 'generate' | 'copy_output' | 'settings' | 'diagnostics';
+  path?: string; // The folder, for open_recent
+}
+
+// Details of the 'dangerous_root' refusal load_directory, generate_output and start_live_mode
+// return for a home directory or drive root, until called again with allowDangerousRoot
+export interface DangerousRootDetails {
+  path: string;
+  kind: 'home' | 'root';
 }
 
 // Define AppSettings interface 
 export interface AppSettings {
+  settingsVersion: number; // Layout version of settings.json; older files are migrated on load
   defaultToLocalConfig: boolean;
   promptForDirectoryOnStartup: boolean;
   enableFolderCheckboxes: boolean;
   autoShowOutputPreview: boolean;
+  notifyOnLongOperations: boolean;
+  longOperationThresholdSecs: number;
+  shortcuts: Record<string, string>; // Menu action id -> accelerator, e.g. { generate: 'CmdOrCtrl+G' }
+  scanThreads: number; // 0 = automatic
+  readConcurrency: number; // 0 = automatic
+  ioPriority: 'normal' | 'low' | 'lowOnBattery'; // Low runs scans and reads on a single thread
+  // Seeded into each new project config
+  defaultOutputFile: string;
+  defaultLineNumbers: boolean;
+  defaultFileHeaderTemplate: string;
+  copyOnGenerate: boolean;
+  openOutputAfterSave: boolean;
+  confirmBeforeLargeScan: boolean; // load_directory returns code 'confirm_required' until called with { confirmed: true }
+  lazyTreeLoading: boolean; // load_directory returns only the top level; folders load with load_directory_children
+  watchTree: boolean; // Backend sends "tree-changed" events as files are created, deleted or renamed
+  blockOutputInSyncedFolders: boolean; // Don't save outputs bound for Dropbox/OneDrive/...; by default they're saved with a warning. See save_output_to_app_data
+  clipboardHistorySize: number; // Outputs kept for copy_history_item; 0 disables the history
+  clipboardSizeLimitKb: number; // copy_to_clipboard returns 'confirm_required' above this; 0 disables the check
+  protectGlobalConfig: boolean; // Never write ~/.gptreerc; edits in global mode go to the project config ('saved_to_project_config')
+  externalEditor: string; // e.g. 'code --goto {path}:{line}'; empty opens files in their default app
 }
 
 // Define SessionState interface (from session_state.json)
 export interface SessionState {
   lastDirectory?: string | null;
   lastConfigMode?: 'global' | 'local' | null;
-}
\ No newline at end of file
+  recentDirectories?: RecentDirectory[];
+}
+// Metadata for one config field, from describe_config_schema
+export interface ConfigFieldSchema {
+  field: keyof Config;
+  key: string; // Key in the config file
+  value_type: 'boolean' | 'number' | 'string' | 'array';
+  default: unknown;
+  description: string | null;
+  modes: ('global' | 'local')[]; // Config modes the field is saved in
+  gui_only: boolean; // Skipped by the gptree CLI
+}
+
+// A project pinned with add_favorite
+export interface FavoriteProject {
+  path: string;
+  name: string; // The folder name unless one was given
+  added_at: string; // RFC 3339
+}
+// A previously opened directory, from get_recent_directories (most recent first)
+export interface RecentDirectory {
+  path: string;
+  lastOpened: string; // RFC 3339
+  fileCount: number | null; // Unknown when the tree was loaded lazily
+}
+// get_system_theme result, also the payload of the 'system-theme-changed' event
+export interface SystemTheme {
+  theme: 'dark' | 'light';
+  accentColor?: string | null; // '#rrggbb'
+}
+
+// get_directory_heatmap result: parallel arrays, parents before children, index 0 is the root
+export interface DirectoryHeatmap {
+  root_path: string;
+  names: string[];
+  parents: number[]; // -1 for the root
+  files: number[];
+  bytes: number[];
+  tokens: number[];
+}
+
+// Entry returned by get_clipboard_history; pass its id to copy_history_item
+export interface OutputHistoryItem {
+  id: number;
+  root_dir: string;
+  generated_at: string;
+  file_count: number;
+  token_estimate: number;
+}
+
+// Entry returned by get_session_history
+export interface ActivityEntry {
+  kind: 'load_directory' | 'generate';
+  root_dir: string;
+  started_at: string;
+  duration_ms: number;
+  file_count?: number | null;
+  token_estimate?: number | null;
+}
+
+// Background tasks started with start_load_directory / start_generate_output /
+// start_load_remote_repo.
+// 'task-progress' and 'task-finished' events carry { id, status, done, total };
+// get_task_result(id) then returns the CommandResult the blocking command would have.
+export interface TaskInfo {
+  id: number;
+  kind: 'load_directory' | 'generate' | 'load_remote_repo';
+  status: 'running' | 'completed' | 'cancelled';
+  done: number;
+  total: number;
+}
+
+// Payload of the 'scan-progress' event sent while load_directory walks a directory.
+// cancel_scan stops the walk; load_directory then returns code 'scan_cancelled'.
+export interface ScanProgress {
+  root: string;
+  files_scanned: number;
+}
+
+// probe_last_directory result (null when there's no remembered directory)
+export interface StartupProbe {
+  path: string;
+  exists: boolean;
+  entry_estimate: number; // Counted no further than one past the large-scan limit
+  is_large: boolean;
+  timed_out: boolean;
+  has_index: boolean; // The tree can be shown from the index without waiting for a walk
+}
+
+// get_runtime_stats result: operation counts and durations since launch, kept in memory only
+export interface OperationStats {
+  count: number;
+  totalMs: number;
+  maxMs: number;
+}
+
+export interface RuntimeStats {
+  uptimeSecs: number;
+  operations: Record<string, OperationStats>; // e.g. 'load_directory', 'generate'
+  counters: Record<string, number>; // e.g. 'index_cache_hit', 'scan_cancelled'
+}
+
+// quick_size_histogram result
+export interface SizeBucket {
+  label: string;
+  min_bytes: number;
+  max_bytes?: number | null;
+  files: number;
+  bytes: number;
+}
+
+export interface DirSize {
+  path: string;
+  files: number;
+  bytes: number;
+}
+
+export interface SizeHistogram {
+  buckets: SizeBucket[];
+  largest_dirs: DirSize[];
+  files_sampled: number;
+  bytes_sampled: number;
+  complete: boolean; // false if sampling stopped early
+}